@@ -0,0 +1,34 @@
+//! Retrieve the icons of all the files in a directory concurrently.
+//!
+//! Usage: cargo run --example async_example --features async <directory>
+//! Example: cargo run --example async_example --features async .
+
+use clap::Parser;
+use file_icon_provider::get_file_icon_async;
+use futures::{executor::block_on, future::join_all};
+use std::path::PathBuf;
+
+#[derive(Parser)]
+#[command(version, about = "Retrieve the icons of all the files in a directory concurrently.", long_about = None)]
+struct Cli {
+    /// The directory containing the files.
+    directory: PathBuf,
+}
+
+fn main() {
+    env_logger::init();
+    let cli = Cli::parse();
+    let paths: Vec<PathBuf> = std::fs::read_dir(&cli.directory)
+        .expect("Failed to read directory")
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+    let requests = paths.iter().map(|path| get_file_icon_async(path, 32));
+    let icons = block_on(join_all(requests));
+
+    for (path, icon) in paths.iter().zip(icons) {
+        match icon {
+            Ok(icon) => println!("{}: {}x{}", path.display(), icon.width, icon.height),
+            Err(error) => println!("{}: {}", path.display(), error),
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use eframe::egui::{self, TextureHandle, TextureOptions};
+use file_icon_provider::get_file_icon;
+use std::path::PathBuf;
+
+fn main() -> eframe::Result {
+    env_logger::init();
+    eframe::run_native(
+        "egui example",
+        eframe::NativeOptions::default(),
+        Box::new(|_| Ok(Box::<State>::default())),
+    )
+}
+
+struct File {
+    path: PathBuf,
+    /// Loaded on the first frame showing the file, at the current icon size.
+    texture: Option<TextureHandle>,
+}
+
+struct State {
+    files: Vec<File>,
+    icon_size: u16,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            files: Vec::new(),
+            icon_size: 16,
+        }
+    }
+}
+
+impl eframe::App for State {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Icon size:");
+
+                let slider = egui::Slider::new(&mut self.icon_size, 1..=512).suffix("px");
+
+                if ui.add(slider).changed() {
+                    // The textures are reloaded at the new size.
+                    for file in &mut self.files {
+                        file.texture = None;
+                    }
+                }
+            });
+
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                for file in &mut self.files {
+                    let texture = file.texture.get_or_insert_with(|| {
+                        get_file_icon(&file.path, self.icon_size)
+                            .expect("Icon for file")
+                            .load_egui_texture(
+                                ui.ctx(),
+                                file.path.display().to_string(),
+                                TextureOptions::NEAREST,
+                            )
+                            .expect("Texture for icon")
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.image(&*texture);
+                        ui.label(file.path.display().to_string());
+                    });
+                }
+
+                ui.vertical_centered(|ui| {
+                    if ui.button("Add Files...").clicked() {
+                        if let Some(paths) = rfd::FileDialog::new().pick_files() {
+                            self.files.extend(paths.into_iter().map(|path| File {
+                                path,
+                                texture: None,
+                            }));
+                        }
+                    }
+                });
+            });
+        });
+    }
+}
@@ -0,0 +1,89 @@
+//! List the files of a directory in the terminal, with a tiny colored icon for each file.
+//!
+//! Each icon is drawn with two half block characters, so it covers 2x2 cells.
+//!
+//! Usage: cargo run --example ratatui_example [directory]
+//! Press 'q' or 'Esc' to quit.
+
+use file_icon_provider::Provider;
+use ratatui::{
+    DefaultTerminal,
+    crossterm::event::{self, KeyCode},
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, List},
+};
+use std::{path::PathBuf, rc::Rc};
+
+/// The icon is drawn with 2 characters, each showing 2 cells with the upper half block.
+const ICON_COLUMNS: u16 = 2;
+const ICON_ROWS: u16 = 2;
+
+fn main() -> std::io::Result<()> {
+    env_logger::init();
+    let directory = std::env::args_os()
+        .nth(1)
+        .map_or_else(|| PathBuf::from("."), PathBuf::from);
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(&directory)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+
+    paths.sort();
+
+    let provider: Provider<Rc<[[u8; 4]]>> =
+        Provider::new(16, |icon| icon.to_blocks(ICON_COLUMNS, ICON_ROWS).into())
+            .expect("Failed to create the provider");
+    let lines: Vec<Line> = paths
+        .iter()
+        .map(|path| {
+            let mut spans = match provider.get_file_icon(path) {
+                Ok(blocks) => icon_spans(&blocks),
+                Err(_) => vec![Span::raw(" ".repeat(usize::from(ICON_COLUMNS)))],
+            };
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+
+            spans.push(Span::raw(format!(" {name}")));
+            Line::from(spans)
+        })
+        .collect();
+
+    ratatui::run(|terminal| run(terminal, lines))
+}
+
+fn run(terminal: &mut DefaultTerminal, lines: Vec<Line>) -> std::io::Result<()> {
+    let list = List::new(lines).block(Block::bordered().title("Files"));
+
+    loop {
+        terminal.draw(|frame| frame.render_widget(&list, frame.area()))?;
+
+        if let Some(key) = event::read()?.as_key_press_event() {
+            if matches!(key.code, KeyCode::Char('q') | KeyCode::Esc) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Draws the top row of blocks as foreground and the bottom row as background of upper half blocks.
+fn icon_spans(blocks: &[[u8; 4]]) -> Vec<Span<'static>> {
+    let (top, bottom) = blocks.split_at(usize::from(ICON_COLUMNS));
+
+    top.iter()
+        .zip(bottom)
+        .map(|(top, bottom)| {
+            Span::styled(
+                "▀",
+                Style::default().fg(to_color(*top)).bg(to_color(*bottom)),
+            )
+        })
+        .collect()
+}
+
+/// Transparent blocks are drawn with the default color of the terminal.
+fn to_color([red, green, blue, alpha]: [u8; 4]) -> Color {
+    if alpha < 128 {
+        Color::Reset
+    } else {
+        Color::Rgb(red, green, blue)
+    }
+}
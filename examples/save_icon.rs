@@ -1,11 +1,13 @@
 //! Extract and save the system icon associated with any file.
 //!
-//! Usage: cargo run --example save_icon <source_file> <output_name>
-//! Example: cargo run --example save_icon document.pdf icon.png
+//! Usage: cargo run --example save_icon --features image <source_file> <output_name> [--size <size>] [--filter <filter>]
+//! Example: cargo run --example save_icon --features image document.pdf icon.png --size 128
+//!
+//! With `--filter`, the icon is requested at 32 pixels and resized to `--size` with the filter.
 
-use clap::Parser;
-use file_icon_provider::get_file_icon;
-use image::{DynamicImage, RgbaImage};
+use clap::{Parser, ValueEnum};
+use file_icon_provider::{ResizeFilter, get_file_icon};
+use image::DynamicImage;
 use std::path::PathBuf;
 
 #[derive(Parser)]
@@ -15,15 +17,44 @@ struct Cli {
     file_path: PathBuf,
     /// The file path of the extracted image.
     output_path: PathBuf,
+    /// The size of the saved image.
+    #[arg(long, default_value_t = 32)]
+    size: u16,
+    /// Requests the icon at 32 pixels and resizes it to the size with this filter.
+    #[arg(long, value_enum)]
+    filter: Option<Filter>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Filter {
+    Nearest,
+    Triangle,
+    CatmullRom,
+}
+
+impl From<Filter> for ResizeFilter {
+    fn from(filter: Filter) -> Self {
+        match filter {
+            Filter::Nearest => ResizeFilter::Nearest,
+            Filter::Triangle => ResizeFilter::Triangle,
+            Filter::CatmullRom => ResizeFilter::CatmullRom,
+        }
+    }
 }
 
 fn main() {
     env_logger::init();
     let cli = Cli::parse();
-    let icon = get_file_icon(cli.file_path, 32).expect("Failed to get icon");
-    let image = RgbaImage::from_raw(icon.width, icon.height, icon.pixels)
-        .map(DynamicImage::ImageRgba8)
-        .expect("Failed to convert Icon to Image");
+    let icon = match cli.filter {
+        Some(filter) => get_file_icon(&cli.file_path, 32).map(|icon| {
+            let size = u32::from(cli.size);
+
+            icon.resize_with_filter(size, size, filter.into())
+        }),
+        None => get_file_icon(&cli.file_path, cli.size),
+    }
+    .expect("Failed to get icon");
+    let image = DynamicImage::try_from(icon).expect("Failed to convert Icon to Image");
 
     match image.save_with_format(&cli.output_path, image::ImageFormat::Png) {
         Err(error) => {
@@ -0,0 +1,60 @@
+//! Shows the files of the current directory with their icons.
+//!
+//! Usage: cargo run --example slint_example --features slint
+
+use file_icon_provider::get_file_icon;
+use slint::{Image, ModelRc, SharedString, VecModel};
+
+slint::slint! {
+    import { ListView } from "std-widgets.slint";
+
+    export struct File {
+        icon: image,
+        path: string,
+    }
+
+    export component MainWindow inherits Window {
+        in property <[File]> files;
+        preferred-width: 400px;
+        preferred-height: 600px;
+
+        ListView {
+            for file in files: HorizontalLayout {
+                spacing: 4px;
+
+                Image {
+                    source: file.icon;
+                    image-rendering: pixelated;
+                }
+                Text {
+                    text: file.path;
+                    vertical-alignment: center;
+                }
+            }
+        }
+    }
+}
+
+const ICON_SIZE: u16 = 32;
+
+fn main() -> Result<(), slint::PlatformError> {
+    env_logger::init();
+
+    let files: Vec<File> = std::fs::read_dir(".")
+        .expect("Read the current directory")
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter_map(|path| {
+            let icon = get_file_icon(&path, ICON_SIZE).ok()?;
+
+            Some(File {
+                icon: Image::try_from(icon).ok()?,
+                path: SharedString::from(path.display().to_string()),
+            })
+        })
+        .collect();
+    let window = MainWindow::new()?;
+
+    window.set_files(ModelRc::new(VecModel::from(files)));
+    window.run()
+}
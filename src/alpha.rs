@@ -0,0 +1,540 @@
+//! Alpha handling of [`Icon`].
+
+use crate::{Corner, Icon, PixelFormat, ResizeFilter};
+
+impl Icon {
+    /// Returns a copy of the icon composited over a solid color, without transparency.
+    ///
+    /// This is useful for outputs that do not support transparency, like some terminal image protocols.
+    /// The alpha of `rgba_background` is ignored: the background is opaque and so is the returned icon.
+    ///
+    /// # Example
+    /// ```
+    /// use file_icon_provider::{Icon, PixelFormat};
+    ///
+    /// let icon = Icon {
+    ///     width: 1,
+    ///     height: 1,
+    ///     pixels: vec![255, 0, 0, 0],
+    ///     format: PixelFormat::Rgba8,
+    ///     stride: None,
+    /// };
+    /// let flattened = icon.flatten_onto([255, 255, 255, 255]);
+    ///
+    /// assert_eq!(flattened.pixels, vec![255, 255, 255, 255]);
+    /// ```
+    #[must_use]
+    pub fn flatten_onto(&self, rgba_background: [u8; 4]) -> Icon {
+        let background = self.format.swizzle(rgba_background);
+        let mut icon = self.clone();
+
+        for pixel in icon.rows_mut().flat_map(|row| row.chunks_exact_mut(4)) {
+            let alpha = u16::from(pixel[3]);
+
+            match alpha {
+                0 => pixel[..3].copy_from_slice(&background[..3]),
+                255 => {}
+                _ => {
+                    for (channel, background) in pixel[..3].iter_mut().zip(background) {
+                        let blended = (u16::from(*channel) * alpha
+                            + u16::from(background) * (255 - alpha)
+                            + 127)
+                            / 255;
+
+                        *channel = u8::try_from(blended).unwrap_or(u8::MAX);
+                    }
+                }
+            }
+            pixel[3] = u8::MAX;
+        }
+
+        icon
+    }
+
+    /// Returns a copy of the icon with `badge` composited in one of its corners,
+    /// like the status badges of file managers.
+    ///
+    /// Badges larger than half the icon are scaled down to half of it, keeping their aspect ratio.
+    /// Smaller badges keep their size.
+    ///
+    /// If the pixel buffer of the icon or of the badge does not match its dimensions,
+    /// the icon is returned unchanged.
+    ///
+    /// # Example
+    /// ```
+    /// use file_icon_provider::{Corner, Icon, PixelFormat};
+    ///
+    /// let icon = Icon {
+    ///     width: 2,
+    ///     height: 2,
+    ///     pixels: vec![0; 2 * 2 * 4],
+    ///     format: PixelFormat::Rgba8,
+    ///     stride: None,
+    /// };
+    /// let badge = Icon {
+    ///     width: 1,
+    ///     height: 1,
+    ///     pixels: vec![255, 0, 0, 255],
+    ///     format: PixelFormat::Rgba8,
+    ///     stride: None,
+    /// };
+    /// let badged = icon.overlay(&badge, Corner::BottomRight);
+    ///
+    /// assert_eq!(badged.pixels[12..], [255, 0, 0, 255]);
+    /// ```
+    #[must_use]
+    pub fn overlay(&self, badge: &Icon, position: Corner) -> Icon {
+        let mut icon = self.clone();
+
+        if badge.width == 0 || badge.height == 0 {
+            return icon;
+        }
+
+        let max_width = (self.width / 2).max(1);
+        let max_height = (self.height / 2).max(1);
+        let scaled_badge;
+        let badge = if badge.width > max_width || badge.height > max_height {
+            let (width, height) = fit_within(badge, max_width, max_height);
+
+            scaled_badge = badge.resize_with_filter(width, height, ResizeFilter::Triangle);
+            &scaled_badge
+        } else {
+            badge
+        };
+        let x = match position {
+            Corner::TopLeft | Corner::BottomLeft => 0,
+            Corner::TopRight | Corner::BottomRight => self.width.saturating_sub(badge.width),
+        };
+        let y = match position {
+            Corner::TopLeft | Corner::TopRight => 0,
+            Corner::BottomLeft | Corner::BottomRight => self.height.saturating_sub(badge.height),
+        };
+
+        icon.draw(badge, x, y);
+        icon
+    }
+
+    /// Returns a copy of the icon with its colors premultiplied by their alpha.
+    ///
+    /// Icons have straight alpha, but GPU compositors usually blend premultiplied colors.
+    /// [`Icon::unpremultiplied`] converts back to straight alpha, within rounding.
+    ///
+    /// # Example
+    /// ```
+    /// use file_icon_provider::{Icon, PixelFormat};
+    ///
+    /// let icon = Icon {
+    ///     width: 1,
+    ///     height: 1,
+    ///     pixels: vec![255, 128, 0, 128],
+    ///     format: PixelFormat::Rgba8,
+    ///     stride: None,
+    /// };
+    ///
+    /// assert_eq!(icon.premultiplied().pixels, vec![128, 64, 0, 128]);
+    /// ```
+    #[must_use]
+    pub fn premultiplied(&self) -> Icon {
+        let mut icon = self.clone();
+
+        icon.premultiply_alpha();
+        icon
+    }
+
+    /// Returns a copy of the icon with straight alpha, from colors premultiplied by their alpha.
+    ///
+    /// Fully transparent pixels become transparent black.
+    ///
+    /// # Example
+    /// ```
+    /// use file_icon_provider::{Icon, PixelFormat};
+    ///
+    /// let icon = Icon {
+    ///     width: 1,
+    ///     height: 1,
+    ///     pixels: vec![128, 64, 0, 128],
+    ///     format: PixelFormat::Rgba8,
+    ///     stride: None,
+    /// };
+    ///
+    /// assert_eq!(icon.unpremultiplied().pixels, vec![255, 128, 0, 128]);
+    /// ```
+    #[must_use]
+    pub fn unpremultiplied(&self) -> Icon {
+        let mut icon = self.clone();
+
+        icon.unpremultiply_alpha();
+        icon
+    }
+
+    /// Draws `overlay` over the icon, with its top left corner at `x`, `y`.
+    ///
+    /// The parts of `overlay` outside of the icon are not drawn.
+    pub(crate) fn draw(&mut self, overlay: &Icon, x: u32, y: u32) {
+        if !self.has_valid_buffer() || !overlay.has_valid_buffer() {
+            return;
+        }
+
+        let reordered;
+        let overlay = if overlay.format == self.format {
+            overlay
+        } else {
+            reordered = overlay.clone().into_format(self.format);
+            &reordered
+        };
+
+        let bytes_per_row = self.bytes_per_row();
+        let overlay_bytes_per_row = overlay.bytes_per_row();
+        let rows = (y..self.height).zip(0..overlay.height);
+
+        for (target_y, overlay_y) in rows {
+            let columns = (x..self.width).zip(0..overlay.width);
+
+            for (target_x, overlay_x) in columns {
+                let target = target_y as usize * bytes_per_row + target_x as usize * 4;
+                let source = overlay_y as usize * overlay_bytes_per_row + overlay_x as usize * 4;
+
+                blend(
+                    &mut self.pixels[target..target + 4],
+                    &overlay.pixels[source..source + 4],
+                );
+            }
+        }
+    }
+
+    /// Multiplies the colors of the pixels by their alpha.
+    pub(crate) fn premultiply_alpha(&mut self) {
+        for pixel in self.rows_mut().flat_map(|row| row.chunks_exact_mut(4)) {
+            let alpha = u16::from(pixel[3]);
+
+            if alpha != 255 {
+                for channel in &mut pixel[..3] {
+                    let premultiplied = (u16::from(*channel) * alpha + 127) / 255;
+
+                    *channel = u8::try_from(premultiplied).unwrap_or(u8::MAX);
+                }
+            }
+        }
+    }
+
+    /// Converts pixels whose colors are premultiplied by their alpha to straight alpha.
+    ///
+    /// Fully transparent pixels become transparent black.
+    pub(crate) fn unpremultiply_alpha(&mut self) {
+        for pixel in self.rows_mut().flat_map(|row| row.chunks_exact_mut(4)) {
+            let alpha = u16::from(pixel[3]);
+
+            match alpha {
+                0 => pixel[..3].fill(0),
+                255 => {}
+                _ => {
+                    for channel in &mut pixel[..3] {
+                        let straight = (u16::from(*channel) * 255 + alpha / 2) / alpha;
+
+                        *channel = u8::try_from(straight).unwrap_or(u8::MAX);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Multiplies the alpha of the pixels by `opacity / 255`, the colors are straight so they are kept.
+    pub(crate) fn fade(&mut self, opacity: u8) {
+        let opacity = u16::from(opacity);
+
+        for pixel in self.rows_mut().flat_map(|row| row.chunks_exact_mut(4)) {
+            let alpha = (u16::from(pixel[3]) * opacity + 127) / 255;
+
+            pixel[3] = u8::try_from(alpha).unwrap_or(u8::MAX);
+        }
+    }
+
+    /// Paints the icon with a single color, keeping its shape.
+    ///
+    /// Symbolic icons are drawn with a placeholder color and meant to be recolored this way.
+    /// The alpha of each pixel is multiplied by the alpha of `rgba_color`.
+    pub(crate) fn recolor(&mut self, rgba_color: [u8; 4]) {
+        let color = self.format.swizzle(rgba_color);
+        let color_alpha = u16::from(color[3]);
+
+        for pixel in self.rows_mut().flat_map(|row| row.chunks_exact_mut(4)) {
+            let alpha = (u16::from(pixel[3]) * color_alpha + 127) / 255;
+
+            pixel[..3].copy_from_slice(&color[..3]);
+            pixel[3] = u8::try_from(alpha).unwrap_or(u8::MAX);
+        }
+    }
+}
+
+/// Returns a transparent `size` x `size` icon with `emblem` in its bottom right corner,
+/// where file managers draw emblems.
+#[cfg_attr(not(any(target_os = "linux", feature = "stub")), allow(dead_code))]
+pub(crate) fn emblem_overlay(emblem: &Icon, size: u16) -> Icon {
+    let size = u32::from(size);
+    let transparent = Icon {
+        width: size,
+        height: size,
+        pixels: vec![0; size as usize * size as usize * 4],
+        format: PixelFormat::Rgba8,
+        stride: None,
+    };
+
+    transparent.overlay(emblem, Corner::BottomRight)
+}
+
+/// Returns the largest dimensions within `max_width` x `max_height` with the aspect ratio of `icon`.
+pub(crate) fn fit_within(icon: &Icon, max_width: u32, max_height: u32) -> (u32, u32) {
+    let (width, height) = (u64::from(icon.width), u64::from(icon.height));
+    let (max_width, max_height) = (u64::from(max_width), u64::from(max_height));
+    let (width, height) = if width * max_height >= height * max_width {
+        (max_width, height * max_width / width)
+    } else {
+        (width * max_height / height, max_height)
+    };
+    let side = |side: u64| u32::try_from(side.max(1)).unwrap_or(u32::MAX);
+
+    (side(width), side(height))
+}
+
+/// Composites the straight alpha pixel `source` over `target`.
+fn blend(target: &mut [u8], source: &[u8]) {
+    let source_alpha = u32::from(source[3]);
+    let target_alpha = u32::from(target[3]) * (255 - source_alpha);
+    // The alpha of the result, multiplied by 255.
+    let alpha = source_alpha * 255 + target_alpha;
+
+    if alpha == 0 {
+        target.fill(0);
+        return;
+    }
+
+    for (channel, source) in target[..3].iter_mut().zip(source) {
+        let blended = (u32::from(*source) * source_alpha * 255
+            + u32::from(*channel) * target_alpha
+            + alpha / 2)
+            / alpha;
+
+        *channel = u8::try_from(blended).unwrap_or(u8::MAX);
+    }
+    target[3] = u8::try_from((alpha + 127) / 255).unwrap_or(u8::MAX);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Corner, Icon, PixelFormat};
+
+    #[test]
+    fn test_recolor() {
+        let mut icon = Icon {
+            width: 2,
+            height: 1,
+            pixels: vec![190, 190, 190, 255, 190, 190, 190, 128],
+            format: PixelFormat::Bgra8,
+            stride: None,
+        };
+
+        icon.recolor([255, 0, 0, 255]);
+        assert_eq!(icon.pixels, vec![0, 0, 255, 255, 0, 0, 255, 128]);
+
+        icon.recolor([0, 255, 0, 128]);
+        assert_eq!(icon.pixels, vec![0, 255, 0, 128, 0, 255, 0, 64]);
+    }
+
+    #[test]
+    fn test_fade() {
+        let mut icon = Icon {
+            width: 2,
+            height: 1,
+            pixels: vec![10, 20, 30, 255, 40, 50, 60, 100],
+            format: PixelFormat::Rgba8,
+            stride: None,
+        };
+
+        icon.fade(128);
+        assert_eq!(icon.pixels, vec![10, 20, 30, 128, 40, 50, 60, 50]);
+
+        icon.fade(0);
+        assert_eq!(icon.pixels, vec![10, 20, 30, 0, 40, 50, 60, 0]);
+    }
+
+    #[test]
+    fn test_unpremultiply_alpha() {
+        let mut icon = Icon {
+            width: 4,
+            height: 1,
+            pixels: vec![
+                64, 32, 0, 128, // Semi-transparent
+                10, 20, 30, 255, // Opaque
+                10, 20, 30, 0, // Transparent
+                200, 0, 0, 100, // Invalid, the color is larger than the alpha
+            ],
+            format: PixelFormat::Rgba8,
+            stride: None,
+        };
+
+        icon.unpremultiply_alpha();
+
+        assert_eq!(
+            icon.pixels,
+            vec![
+                128, 64, 0, 128, //
+                10, 20, 30, 255, //
+                0, 0, 0, 0, //
+                255, 0, 0, 100,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_premultiply_alpha_is_inverse_of_unpremultiply_alpha() {
+        for alpha in 1..=255u8 {
+            let channels = (0..=255u8).flat_map(|channel| [channel, channel, channel, alpha]);
+            let icon = Icon {
+                width: 256,
+                height: 1,
+                pixels: channels.collect(),
+                format: PixelFormat::Rgba8,
+                stride: None,
+            };
+            let premultiplied = icon.premultiplied();
+            let straight = premultiplied.unpremultiplied();
+            // Premultiplying loses precision, up to half a step of the straight color.
+            let tolerance = (255 + u16::from(alpha)) / (2 * u16::from(alpha));
+
+            for (channel, expected) in straight.pixels.iter().zip(&icon.pixels) {
+                assert!(u16::from(channel.abs_diff(*expected)) <= tolerance);
+            }
+
+            assert_eq!(straight.premultiplied(), premultiplied);
+        }
+    }
+
+    #[test]
+    fn test_flatten_onto() {
+        let icon = Icon {
+            width: 3,
+            height: 1,
+            pixels: vec![
+                255, 0, 0, 128, // Semi-transparent
+                10, 20, 30, 255, // Opaque
+                10, 20, 30, 0, // Transparent
+            ],
+            format: PixelFormat::Rgba8,
+            stride: None,
+        };
+        let flattened = icon.flatten_onto([0, 0, 255, 0]);
+
+        assert_eq!(flattened.width, 3);
+        assert_eq!(flattened.height, 1);
+        assert_eq!(
+            flattened.pixels,
+            vec![
+                128, 0, 127, 255, //
+                10, 20, 30, 255, //
+                0, 0, 255, 255,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_draw() {
+        let mut icon = Icon {
+            width: 2,
+            height: 2,
+            pixels: vec![
+                0, 0, 255, 255, // Opaque
+                0, 0, 255, 255, // Opaque
+                0, 0, 0, 0, // Transparent
+                0, 0, 255, 128, // Semi-transparent
+            ],
+            format: PixelFormat::Rgba8,
+            stride: None,
+        };
+        let overlay = Icon {
+            width: 2,
+            height: 1,
+            pixels: vec![
+                255, 0, 0, 128, // Semi-transparent
+                255, 0, 0, 255, // Opaque, outside of the icon
+            ],
+            format: PixelFormat::Rgba8,
+            stride: None,
+        };
+
+        icon.draw(&overlay, 1, 1);
+        icon.draw(&overlay, 2, 0);
+
+        assert_eq!(
+            icon.pixels,
+            vec![
+                0, 0, 255, 255, //
+                0, 0, 255, 255, //
+                0, 0, 0, 0, //
+                170, 0, 85, 192,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_overlay_corners() {
+        let icon = Icon {
+            width: 4,
+            height: 4,
+            pixels: vec![0; 4 * 4 * 4],
+            format: PixelFormat::Rgba8,
+            stride: None,
+        };
+        let badge = Icon {
+            width: 1,
+            height: 1,
+            pixels: vec![255, 0, 0, 255],
+            format: PixelFormat::Rgba8,
+            stride: None,
+        };
+        let red_pixel = |icon: &Icon| {
+            icon.pixels
+                .chunks_exact(4)
+                .position(|pixel| pixel == [255, 0, 0, 255])
+        };
+
+        assert_eq!(red_pixel(&icon.overlay(&badge, Corner::TopLeft)), Some(0));
+        assert_eq!(red_pixel(&icon.overlay(&badge, Corner::TopRight)), Some(3));
+        assert_eq!(
+            red_pixel(&icon.overlay(&badge, Corner::BottomLeft)),
+            Some(12)
+        );
+        assert_eq!(
+            red_pixel(&icon.overlay(&badge, Corner::BottomRight)),
+            Some(15)
+        );
+    }
+
+    #[test]
+    fn test_overlay_scales_large_badges() {
+        let icon = Icon {
+            width: 4,
+            height: 4,
+            pixels: vec![0; 4 * 4 * 4],
+            format: PixelFormat::Rgba8,
+            stride: None,
+        };
+        let badge = Icon {
+            width: 8,
+            height: 4,
+            pixels: vec![255; 8 * 4 * 4],
+            format: PixelFormat::Rgba8,
+            stride: None,
+        };
+        let badged = icon.overlay(&badge, Corner::BottomRight);
+        let opaque: Vec<usize> = badged
+            .pixels
+            .chunks_exact(4)
+            .enumerate()
+            .filter(|(_, pixel)| pixel[3] == 255)
+            .map(|(index, _)| index)
+            .collect();
+
+        assert_eq!((badged.width, badged.height), (4, 4));
+        assert_eq!(opaque, vec![14, 15]);
+    }
+}
@@ -0,0 +1,179 @@
+//! Packing of icons into a single atlas, see [`Provider::export_atlas`](crate::Provider::export_atlas).
+
+use std::cmp::Reverse;
+
+use crate::{Icon, PixelFormat};
+
+/// A rectangle of an atlas in pixels, see [`Provider::export_atlas`](crate::Provider::export_atlas).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Rect {
+    /// The left edge of the rectangle.
+    pub x: u32,
+    /// The top edge of the rectangle.
+    pub y: u32,
+    /// The width of the rectangle.
+    pub width: u32,
+    /// The height of the rectangle.
+    pub height: u32,
+}
+
+/// Packs `icons` into a single transparent icon in `format`, and returns the rectangle of each icon.
+///
+/// The icons are placed on shelves from the tallest to the shortest, left to right.
+/// The atlas is about as wide as the square root of the area of the icons, so it is roughly square.
+/// The icons whose pixel buffer does not match their dimensions get a rectangle but are not drawn.
+pub(crate) fn pack(icons: &[&Icon], format: PixelFormat) -> (Icon, Vec<Rect>) {
+    let area: u64 = icons
+        .iter()
+        .map(|icon| u64::from(icon.width) * u64::from(icon.height))
+        .sum();
+    let side = area.isqrt() + u64::from(area.isqrt().pow(2) < area);
+    let widest = icons
+        .iter()
+        .map(|icon| icon.width)
+        .max()
+        .unwrap_or_default();
+    let width = widest.max(u32::try_from(side).unwrap_or(u32::MAX));
+    let mut order: Vec<usize> = (0..icons.len()).collect();
+    let mut rects = vec![Rect::default(); icons.len()];
+    let (mut x, mut y, mut shelf_height) = (0u32, 0u32, 0u32);
+
+    order.sort_by_key(|index| Reverse(icons[*index].height));
+
+    for index in order {
+        let icon = icons[index];
+
+        if x.saturating_add(icon.width) > width {
+            x = 0;
+            y = y.saturating_add(shelf_height);
+            shelf_height = 0;
+        }
+
+        rects[index] = Rect {
+            x,
+            y,
+            width: icon.width,
+            height: icon.height,
+        };
+        x = x.saturating_add(icon.width);
+        shelf_height = shelf_height.max(icon.height);
+    }
+
+    let height = y.saturating_add(shelf_height);
+    let mut atlas = Icon {
+        width,
+        height,
+        pixels: vec![0; width as usize * height as usize * 4],
+        format,
+        stride: None,
+    };
+
+    for (icon, rect) in icons.iter().zip(&rects) {
+        copy_into(&mut atlas, icon, *rect);
+    }
+
+    (atlas, rects)
+}
+
+/// Copies the pixels of `icon` into `atlas` at `rect`, in the format of the atlas.
+fn copy_into(atlas: &mut Icon, icon: &Icon, rect: Rect) {
+    if icon.width == 0 || icon.height == 0 || !icon.has_valid_buffer() {
+        return;
+    }
+
+    let reordered;
+    let icon = if icon.format == atlas.format {
+        icon
+    } else {
+        reordered = icon.clone().into_format(atlas.format);
+        &reordered
+    };
+    let atlas_width = atlas.width as usize;
+
+    for (row_index, row) in icon.rows().enumerate() {
+        let offset = ((rect.y as usize + row_index) * atlas_width + rect.x as usize) * 4;
+
+        atlas.pixels[offset..offset + row.len()].copy_from_slice(row);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Icon, PixelFormat};
+
+    use super::{Rect, pack};
+
+    fn solid(width: u32, height: u32, value: u8) -> Icon {
+        Icon {
+            width,
+            height,
+            pixels: vec![value; width as usize * height as usize * 4],
+            format: PixelFormat::Rgba8,
+            stride: None,
+        }
+    }
+
+    #[test]
+    fn test_pack() {
+        let icons = [solid(2, 2, 1), solid(4, 4, 2), solid(2, 1, 3)];
+        let (atlas, rects) = pack(&icons.iter().collect::<Vec<_>>(), PixelFormat::Rgba8);
+
+        assert_eq!((atlas.width, atlas.height), (5, 6));
+        assert_eq!(
+            rects,
+            [
+                Rect {
+                    x: 0,
+                    y: 4,
+                    width: 2,
+                    height: 2
+                },
+                Rect {
+                    x: 0,
+                    y: 0,
+                    width: 4,
+                    height: 4
+                },
+                Rect {
+                    x: 2,
+                    y: 4,
+                    width: 2,
+                    height: 1
+                },
+            ]
+        );
+
+        for (icon, rect) in icons.iter().zip(&rects) {
+            for y in rect.y..rect.y + rect.height {
+                for x in rect.x..rect.x + rect.width {
+                    assert_eq!(
+                        atlas.get_pixel(x, y),
+                        icon.get_pixel(x - rect.x, y - rect.y)
+                    );
+                }
+            }
+        }
+
+        assert_eq!(atlas.get_pixel(4, 0), Some([0; 4]));
+    }
+
+    #[test]
+    fn test_pack_converts_the_format() {
+        let icon = Icon {
+            width: 1,
+            height: 1,
+            pixels: vec![1, 2, 3, 4],
+            format: PixelFormat::Rgba8,
+            stride: None,
+        };
+        let (atlas, rects) = pack(&[&icon], PixelFormat::Bgra8);
+
+        assert_eq!(atlas.pixels, vec![3, 2, 1, 4]);
+        assert_eq!(rects.len(), 1);
+
+        let (empty, rects) = pack(&[], PixelFormat::Rgba8);
+
+        assert_eq!((empty.width, empty.height), (0, 0));
+        assert!(rects.is_empty());
+    }
+}
@@ -0,0 +1,208 @@
+//! Downsampling of [`Icon`] into a few colors, for text user interfaces.
+
+use std::ops::Range;
+
+use crate::Icon;
+
+impl Icon {
+    /// Returns the average color of the icon.
+    ///
+    /// Colors are weighted by their alpha, so transparent pixels do not count.
+    /// The alpha of the returned color is the average alpha of the icon.
+    ///
+    /// Returns transparent black if the icon has no pixels or if its pixel buffer
+    /// does not match its dimensions.
+    ///
+    /// # Example
+    /// ```
+    /// use file_icon_provider::{Icon, PixelFormat};
+    ///
+    /// let icon = Icon {
+    ///     width: 2,
+    ///     height: 1,
+    ///     pixels: vec![255, 0, 0, 255, 0, 0, 255, 0],
+    ///     format: PixelFormat::Rgba8,
+    ///     stride: None,
+    /// };
+    ///
+    /// assert_eq!(icon.dominant_color(), [255, 0, 0, 128]);
+    /// ```
+    #[must_use]
+    pub fn dominant_color(&self) -> [u8; 4] {
+        if !self.has_valid_buffer() {
+            return [0; 4];
+        }
+
+        self.average_color(0..self.width as usize, 0..self.height as usize)
+    }
+
+    /// Splits the icon into a grid of `cols` x `rows` cells and returns the average color of each cell,
+    /// row by row.
+    ///
+    /// This is meant to draw tiny icons in a terminal, for example with one character per cell,
+    /// or with half blocks to get two cells per character.
+    /// The colors are averaged the same way as [`Icon::dominant_color`].
+    ///
+    /// Returns `cols * rows` colors, transparent black if the icon has no pixels or if its
+    /// pixel buffer does not match its dimensions.
+    ///
+    /// # Example
+    /// ```
+    /// use file_icon_provider::{Icon, PixelFormat};
+    ///
+    /// let icon = Icon {
+    ///     width: 2,
+    ///     height: 2,
+    ///     pixels: [[255, 0, 0, 255], [0, 0, 255, 255]].repeat(2).concat(),
+    ///     format: PixelFormat::Rgba8,
+    ///     stride: None,
+    /// };
+    ///
+    /// assert_eq!(icon.to_blocks(2, 1), [[255, 0, 0, 255], [0, 0, 255, 255]]);
+    /// ```
+    #[must_use]
+    pub fn to_blocks(&self, cols: u16, rows: u16) -> Vec<[u8; 4]> {
+        let cell_count = usize::from(cols) * usize::from(rows);
+
+        if self.width == 0 || self.height == 0 || !self.has_valid_buffer() {
+            return vec![[0; 4]; cell_count];
+        }
+
+        let mut blocks = Vec::with_capacity(cell_count);
+
+        for row in 0..rows {
+            let y_range = cell_range(row, rows, self.height);
+
+            for col in 0..cols {
+                blocks.push(self.average_color(cell_range(col, cols, self.width), y_range.clone()));
+            }
+        }
+
+        blocks
+    }
+
+    /// Returns the average color of the pixels in the area, weighted by their alpha.
+    fn average_color(&self, x_range: Range<usize>, y_range: Range<usize>) -> [u8; 4] {
+        let bytes_per_row = self.bytes_per_row();
+        let mut premultiplied = [0u64; 3];
+        let mut alpha = 0u64;
+        let mut count = 0u64;
+
+        for y in y_range {
+            let row = &self.pixels[y * bytes_per_row..(y + 1) * bytes_per_row];
+
+            for pixel in row[x_range.start * 4..x_range.end * 4].chunks_exact(4) {
+                let pixel_alpha = u64::from(pixel[3]);
+
+                for (channel, value) in premultiplied.iter_mut().zip(pixel) {
+                    *channel += u64::from(*value) * pixel_alpha;
+                }
+                alpha += pixel_alpha;
+                count += 1;
+            }
+        }
+
+        if alpha == 0 {
+            return [0; 4];
+        }
+
+        let [first, second, third] = premultiplied.map(|channel| divide_rounded(channel, alpha));
+
+        // The colors are returned in RGBA whatever the format of the icon.
+        self.format
+            .swizzle([first, second, third, divide_rounded(alpha, count)])
+    }
+}
+
+/// Returns the range of pixels covered by the cell `index` out of `count` cells,
+/// never empty so cells smaller than a pixel repeat the nearest pixel.
+fn cell_range(index: u16, count: u16, pixels: u32) -> Range<usize> {
+    let pixels = pixels as usize;
+    let start = usize::from(index) * pixels / usize::from(count);
+    let end = (usize::from(index) + 1) * pixels / usize::from(count);
+
+    start..end.max(start + 1)
+}
+
+fn divide_rounded(value: u64, divisor: u64) -> u8 {
+    u8::try_from((value + divisor / 2) / divisor).unwrap_or(u8::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Icon, PixelFormat};
+
+    #[test]
+    fn test_dominant_color_ignores_transparent_pixels() {
+        let icon = Icon {
+            width: 2,
+            height: 2,
+            pixels: vec![
+                10, 20, 30, 255, //
+                10, 20, 30, 255, //
+                255, 255, 255, 0, //
+                255, 255, 255, 0,
+            ],
+            format: PixelFormat::Rgba8,
+            stride: None,
+        };
+
+        assert_eq!(icon.dominant_color(), [10, 20, 30, 128]);
+        assert_eq!(
+            icon.into_format(PixelFormat::Bgra8).dominant_color(),
+            [10, 20, 30, 128]
+        );
+    }
+
+    #[test]
+    fn test_to_blocks() {
+        let icon = Icon {
+            width: 4,
+            height: 2,
+            pixels: [
+                [255, 0, 0, 255].repeat(2),
+                [0, 0, 255, 255].repeat(2),
+                [0, 255, 0, 255].repeat(4),
+            ]
+            .concat(),
+            format: PixelFormat::Rgba8,
+            stride: None,
+        };
+
+        assert_eq!(
+            icon.to_blocks(2, 2),
+            [
+                [255, 0, 0, 255],
+                [0, 0, 255, 255],
+                [0, 255, 0, 255],
+                [0, 255, 0, 255]
+            ]
+        );
+        assert_eq!(icon.to_blocks(1, 1), [[64, 128, 64, 255]]);
+        assert_eq!(icon.to_blocks(8, 1).len(), 8);
+        assert!(icon.to_blocks(0, 3).is_empty());
+    }
+
+    #[test]
+    fn test_degenerate_icons() {
+        let empty = Icon {
+            width: 0,
+            height: 0,
+            pixels: Vec::new(),
+            format: PixelFormat::Rgba8,
+            stride: None,
+        };
+        let invalid = Icon {
+            width: 2,
+            height: 2,
+            pixels: vec![255; 4],
+            format: PixelFormat::Rgba8,
+            stride: None,
+        };
+
+        assert_eq!(empty.dominant_color(), [0; 4]);
+        assert_eq!(empty.to_blocks(2, 1), [[0; 4]; 2]);
+        assert_eq!(invalid.dominant_color(), [0; 4]);
+        assert_eq!(invalid.to_blocks(1, 1), [[0; 4]]);
+    }
+}
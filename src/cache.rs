@@ -0,0 +1,344 @@
+//! Cache used by [`Provider`](crate::Provider) to store converted icons.
+
+use std::{collections::BTreeMap, time::Instant};
+
+/// Icon cache, optionally bounded.
+///
+/// When the capacity is exceeded, the least recently used entry is evicted.
+pub(crate) struct Cache<K, T> {
+    entries: BTreeMap<K, Entry<T>>,
+    /// Keys ordered from the least recently used to the most recently used.
+    recency: BTreeMap<u64, K>,
+    tick: u64,
+    capacity: Option<usize>,
+    /// The values removed since the last call to [`Cache::take_removed`].
+    removed: Vec<T>,
+}
+
+struct Entry<T> {
+    value: T,
+    tick: u64,
+    /// When the entry was inserted or returned by [`Cache::get`] for the last time.
+    accessed: Instant,
+}
+
+impl<K: Ord + Clone, T: Clone> Cache<K, T> {
+    pub(crate) fn new(capacity: Option<usize>) -> Self {
+        Self {
+            entries: BTreeMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+            capacity,
+            removed: Vec::new(),
+        }
+    }
+
+    pub(crate) fn capacity(&self) -> Option<usize> {
+        self.capacity
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns the value cached for `key` and marks it as the most recently used.
+    pub(crate) fn get(&mut self, key: &K) -> Option<T> {
+        let tick = self.next_tick();
+        let entry = self.entries.get_mut(key)?;
+
+        if let Some(key) = self.recency.remove(&entry.tick) {
+            self.recency.insert(tick, key);
+        }
+        entry.tick = tick;
+        entry.accessed = Instant::now();
+
+        Some(entry.value.clone())
+    }
+
+    /// Returns the value cached for `key`, without marking it as recently used.
+    pub(crate) fn peek(&self, key: &K) -> Option<&T> {
+        self.entries.get(key).map(|entry| &entry.value)
+    }
+
+    /// Returns `true` if `key` is cached, without marking it as recently used.
+    pub(crate) fn contains_key(&self, key: &K) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    /// Caches `value` unless `key` is already cached, and returns the cached value.
+    pub(crate) fn insert(&mut self, key: K, value: T) -> T {
+        if let Some(cached) = self.get(&key) {
+            return cached;
+        }
+
+        if self.capacity == Some(0) {
+            return value;
+        }
+
+        let tick = self.next_tick();
+
+        self.recency.insert(tick, key.clone());
+        self.entries.insert(
+            key,
+            Entry {
+                value: value.clone(),
+                tick,
+                accessed: Instant::now(),
+            },
+        );
+        self.evict();
+
+        value
+    }
+
+    pub(crate) fn clear(&mut self) {
+        let entries = std::mem::take(&mut self.entries);
+
+        self.removed
+            .extend(entries.into_values().map(|entry| entry.value));
+        self.recency.clear();
+    }
+
+    /// Removes the entry of `key`, if any.
+    pub(crate) fn remove(&mut self, key: &K) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.recency.remove(&entry.tick);
+            self.removed.push(entry.value);
+        }
+    }
+
+    /// Removes the entries whose key matches `predicate`.
+    pub(crate) fn remove_if(&mut self, mut predicate: impl FnMut(&K) -> bool) {
+        let recency = &mut self.recency;
+        let removed = &mut self.removed;
+
+        self.entries.retain(|key, entry| {
+            let remove = predicate(key);
+
+            if remove {
+                recency.remove(&entry.tick);
+                removed.push(entry.value.clone());
+            }
+
+            !remove
+        });
+    }
+
+    /// Removes the entries that were last accessed before `instant`.
+    pub(crate) fn remove_accessed_before(&mut self, instant: Instant) {
+        let recency = &mut self.recency;
+        let removed = &mut self.removed;
+
+        self.entries.retain(|_, entry| {
+            let remove = entry.accessed < instant;
+
+            if remove {
+                recency.remove(&entry.tick);
+                removed.push(entry.value.clone());
+            }
+
+            !remove
+        });
+    }
+
+    /// Returns the values removed or evicted since the last call, so they can be released.
+    pub(crate) fn take_removed(&mut self) -> Vec<T> {
+        std::mem::take(&mut self.removed)
+    }
+
+    fn evict(&mut self) {
+        let Some(capacity) = self.capacity else {
+            return;
+        };
+
+        while self.entries.len() > capacity {
+            let Some((_, key)) = self.recency.pop_first() else {
+                break;
+            };
+
+            if let Some(entry) = self.entries.remove(&key) {
+                self.removed.push(entry.value);
+            }
+        }
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::Cache;
+
+    #[test]
+    fn test_unbounded() {
+        let mut cache = Cache::new(None);
+
+        for i in 0..100 {
+            cache.insert(i, i);
+        }
+
+        assert_eq!(cache.len(), 100);
+        assert_eq!(cache.get(&0), Some(0));
+    }
+
+    #[test]
+    fn test_oldest_entry_is_evicted() {
+        let mut cache = Cache::new(Some(2));
+
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        cache.insert("c", 3);
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&"a"), None);
+        assert_eq!(cache.get(&"b"), Some(2));
+        assert_eq!(cache.get(&"c"), Some(3));
+    }
+
+    #[test]
+    fn test_peek_does_not_mark_entry_as_used() {
+        let mut cache = Cache::new(Some(2));
+
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+
+        assert_eq!(cache.peek(&"a"), Some(&1));
+
+        cache.insert("c", 3);
+
+        assert_eq!(cache.peek(&"a"), None);
+        assert_eq!(cache.peek(&"b"), Some(&2));
+    }
+
+    #[test]
+    fn test_recently_used_entry_is_kept() {
+        let mut cache = Cache::new(Some(2));
+
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        cache.get(&"a");
+        cache.insert("c", 3);
+
+        assert_eq!(cache.get(&"a"), Some(1));
+        assert_eq!(cache.get(&"b"), None);
+    }
+
+    #[test]
+    fn test_remove_if() {
+        let mut cache = Cache::new(Some(2));
+
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        cache.remove_if(|key| *key == "a");
+        cache.insert("c", 3);
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&"a"), None);
+        assert_eq!(cache.get(&"b"), Some(2));
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut cache = Cache::new(Some(2));
+
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        cache.remove(&"a");
+        cache.remove(&"z");
+        cache.insert("c", 3);
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&"a"), None);
+        assert_eq!(cache.get(&"b"), Some(2));
+        assert_eq!(cache.take_removed(), [1]);
+    }
+
+    #[test]
+    fn test_remove_accessed_before() {
+        let mut cache = Cache::new(None);
+        let pause = || std::thread::sleep(Duration::from_millis(2));
+
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        pause();
+
+        let instant = Instant::now();
+
+        pause();
+        cache.get(&"b");
+        cache.insert("c", 3);
+        cache.remove_accessed_before(instant);
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&"a"), None);
+        assert_eq!(cache.get(&"b"), Some(2));
+    }
+
+    #[test]
+    fn test_contains_key_keeps_recency() {
+        let mut cache = Cache::new(Some(2));
+
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+
+        assert!(cache.contains_key(&"a"));
+        assert!(!cache.contains_key(&"c"));
+
+        cache.insert("c", 3);
+
+        assert!(!cache.contains_key(&"a"));
+        assert!(cache.contains_key(&"b"));
+    }
+
+    #[test]
+    fn test_clear() {
+        let mut cache = Cache::new(Some(2));
+
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        cache.clear();
+        cache.insert("c", 3);
+
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.get(&"a"), None);
+    }
+
+    #[test]
+    fn test_take_removed() {
+        let mut cache = Cache::new(Some(2));
+
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        cache.insert("c", 3);
+        assert_eq!(cache.take_removed(), [1]);
+        assert!(cache.take_removed().is_empty());
+
+        cache.remove_if(|key| *key == "b");
+        cache.remove_accessed_before(Instant::now());
+        cache.insert("d", 4);
+        cache.clear();
+
+        assert_eq!(cache.take_removed(), [2, 3, 4]);
+    }
+
+    #[test]
+    fn test_insert_keeps_cached_value() {
+        let mut cache = Cache::new(None);
+
+        assert_eq!(cache.insert("a", 1), 1);
+        assert_eq!(cache.insert("a", 2), 1);
+    }
+
+    #[test]
+    fn test_null_capacity() {
+        let mut cache = Cache::new(Some(0));
+
+        assert_eq!(cache.insert("a", 1), 1);
+        assert_eq!(cache.len(), 0);
+    }
+}
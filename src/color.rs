@@ -0,0 +1,175 @@
+//! Color adjustments of [`Icon`].
+
+use crate::Icon;
+
+impl Icon {
+    /// Returns a copy of the icon in shades of `rgb_color`, keeping its transparency.
+    ///
+    /// The brightness of each pixel scales the color, so the details of the icon stay visible.
+    /// Use [`Icon::tint_preserving_alpha`] to paint the icon with a flat color instead.
+    ///
+    /// # Example
+    /// ```
+    /// use file_icon_provider::{Icon, PixelFormat};
+    ///
+    /// let icon = Icon {
+    ///     width: 2,
+    ///     height: 1,
+    ///     pixels: vec![255, 255, 255, 255, 0, 0, 0, 128],
+    ///     format: PixelFormat::Rgba8,
+    ///     stride: None,
+    /// };
+    /// let tinted = icon.tint([255, 128, 0]);
+    ///
+    /// assert_eq!(tinted.pixels, vec![255, 128, 0, 255, 0, 0, 0, 128]);
+    /// ```
+    #[must_use]
+    pub fn tint(&self, rgb_color: [u8; 3]) -> Icon {
+        let [red, green, blue] = rgb_color;
+        let color = self.format.swizzle([red, green, blue, u8::MAX]);
+        let mut icon = self.clone();
+
+        for pixel in icon.rows_mut().flat_map(|row| row.chunks_exact_mut(4)) {
+            let luminance = u16::from(self.luminance(pixel));
+
+            for (channel, color) in pixel[..3].iter_mut().zip(color) {
+                let tinted = (luminance * u16::from(color) + 127) / 255;
+
+                *channel = u8::try_from(tinted).unwrap_or(u8::MAX);
+            }
+        }
+
+        icon
+    }
+
+    /// Returns a copy of the icon painted with `rgb_color`, only its transparency is kept.
+    ///
+    /// This draws the silhouette of the icon, for example to match the color of the text.
+    ///
+    /// # Example
+    /// ```
+    /// use file_icon_provider::{Icon, PixelFormat};
+    ///
+    /// let icon = Icon {
+    ///     width: 2,
+    ///     height: 1,
+    ///     pixels: vec![10, 20, 30, 255, 40, 50, 60, 128],
+    ///     format: PixelFormat::Rgba8,
+    ///     stride: None,
+    /// };
+    /// let tinted = icon.tint_preserving_alpha([255, 128, 0]);
+    ///
+    /// assert_eq!(tinted.pixels, vec![255, 128, 0, 255, 255, 128, 0, 128]);
+    /// ```
+    #[must_use]
+    pub fn tint_preserving_alpha(&self, rgb_color: [u8; 3]) -> Icon {
+        let [red, green, blue] = rgb_color;
+        let mut icon = self.clone();
+
+        icon.recolor([red, green, blue, u8::MAX]);
+        icon
+    }
+
+    /// Returns a copy of the icon in shades of gray, keeping its transparency.
+    ///
+    /// This is useful to draw disabled items.
+    ///
+    /// # Example
+    /// ```
+    /// use file_icon_provider::{Icon, PixelFormat};
+    ///
+    /// let icon = Icon {
+    ///     width: 1,
+    ///     height: 1,
+    ///     pixels: vec![255, 0, 0, 200],
+    ///     format: PixelFormat::Rgba8,
+    ///     stride: None,
+    /// };
+    ///
+    /// assert_eq!(icon.grayscale().pixels, vec![76, 76, 76, 200]);
+    /// ```
+    #[must_use]
+    pub fn grayscale(&self) -> Icon {
+        self.tint([u8::MAX; 3])
+    }
+
+    /// Returns the perceived brightness of a pixel of the icon, with the weights of Rec. 601.
+    fn luminance(&self, pixel: &[u8]) -> u8 {
+        let [red, green, blue, _] = self
+            .format
+            .swizzle([pixel[0], pixel[1], pixel[2], pixel[3]]);
+        let luminance =
+            (u32::from(red) * 299 + u32::from(green) * 587 + u32::from(blue) * 114 + 500) / 1000;
+
+        u8::try_from(luminance).unwrap_or(u8::MAX)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Icon, PixelFormat};
+
+    fn icon(format: PixelFormat) -> Icon {
+        Icon {
+            width: 3,
+            height: 1,
+            pixels: vec![
+                0, 0, 255, 255, // Opaque
+                255, 255, 255, 128, // Semi-transparent
+                0, 0, 0, 0, // Transparent
+            ],
+            format,
+            stride: None,
+        }
+    }
+
+    #[test]
+    fn test_tint() {
+        assert_eq!(
+            icon(PixelFormat::Rgba8).tint([200, 100, 0]).pixels,
+            vec![
+                23, 11, 0, 255, //
+                200, 100, 0, 128, //
+                0, 0, 0, 0,
+            ]
+        );
+        assert_eq!(
+            icon(PixelFormat::Bgra8).tint([200, 100, 0]).pixels,
+            vec![
+                0, 30, 60, 255, //
+                0, 100, 200, 128, //
+                0, 0, 0, 0,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tint_preserving_alpha() {
+        assert_eq!(
+            icon(PixelFormat::Bgra8)
+                .tint_preserving_alpha([200, 100, 0])
+                .pixels,
+            vec![
+                0, 100, 200, 255, //
+                0, 100, 200, 128, //
+                0, 100, 200, 0,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_grayscale() {
+        let grayscale = icon(PixelFormat::Rgba8).grayscale();
+
+        assert_eq!(grayscale.width, 3);
+        assert_eq!(grayscale.height, 1);
+        assert_eq!(
+            grayscale.pixels,
+            vec![
+                29, 29, 29, 255, //
+                255, 255, 255, 128, //
+                0, 0, 0, 0,
+            ]
+        );
+    }
+}
@@ -0,0 +1,218 @@
+//! Cropping and padding of [`Icon`], for layouts.
+
+use crate::Icon;
+
+impl Icon {
+    /// Returns a copy of the icon cropped to the bounding box of its non-transparent pixels.
+    ///
+    /// A fully transparent icon is cropped to a single transparent pixel.
+    /// If the icon has no pixels, or if its pixel buffer does not match its dimensions,
+    /// it is returned unchanged.
+    ///
+    /// # Example
+    /// ```
+    /// use file_icon_provider::{Icon, PixelFormat};
+    ///
+    /// let mut pixels = vec![0; 3 * 3 * 4];
+    /// pixels[16..20].copy_from_slice(&[255, 0, 0, 255]);
+    ///
+    /// let icon = Icon {
+    ///     width: 3,
+    ///     height: 3,
+    ///     pixels,
+    ///     format: PixelFormat::Rgba8,
+    ///     stride: None,
+    /// };
+    /// let cropped = icon.crop_transparent_border();
+    ///
+    /// assert_eq!((cropped.width, cropped.height), (1, 1));
+    /// assert_eq!(cropped.pixels, vec![255, 0, 0, 255]);
+    /// ```
+    #[must_use]
+    pub fn crop_transparent_border(&self) -> Icon {
+        if self.width == 0 || self.height == 0 || !self.has_valid_buffer() {
+            return self.clone();
+        }
+
+        let width = self.width as usize;
+        let mut bounds: Option<(usize, usize, usize, usize)> = None;
+        let pixels = self.rows().enumerate().flat_map(|(y, row)| {
+            row.chunks_exact(4)
+                .enumerate()
+                .map(move |(x, pixel)| (x, y, pixel))
+        });
+
+        for (x, y, pixel) in pixels {
+            if pixel[3] == 0 {
+                continue;
+            }
+
+            bounds = Some(match bounds {
+                Some((left, top, right, bottom)) => {
+                    (left.min(x), top.min(y), right.max(x), bottom.max(y))
+                }
+                None => (x, y, x, y),
+            });
+        }
+
+        let Some((left, top, right, bottom)) = bounds else {
+            return Icon {
+                width: 1,
+                height: 1,
+                pixels: vec![0; 4],
+                format: self.format,
+                stride: None,
+            };
+        };
+
+        if (left, top, right, bottom) == (0, 0, width - 1, self.height as usize - 1) {
+            return self.clone();
+        }
+
+        let mut pixels = Vec::with_capacity((right - left + 1) * (bottom - top + 1) * 4);
+
+        for row in self.rows().skip(top).take(bottom - top + 1) {
+            pixels.extend_from_slice(&row[left * 4..(right + 1) * 4]);
+        }
+
+        Icon {
+            width: u32::try_from(right - left + 1).unwrap_or(u32::MAX),
+            height: u32::try_from(bottom - top + 1).unwrap_or(u32::MAX),
+            pixels,
+            format: self.format,
+            stride: None,
+        }
+    }
+
+    /// Returns a copy of the icon centered in a square filled with `rgba_fill`.
+    ///
+    /// The side of the square is the largest dimension of the icon. When the margins
+    /// cannot be even, the extra pixel goes to the right or to the bottom.
+    /// If the icon is already square, has no pixels, or if its pixel buffer does not match
+    /// its dimensions, it is returned unchanged.
+    ///
+    /// # Example
+    /// ```
+    /// use file_icon_provider::{Icon, PixelFormat};
+    ///
+    /// let icon = Icon {
+    ///     width: 1,
+    ///     height: 3,
+    ///     pixels: vec![255; 3 * 4],
+    ///     format: PixelFormat::Rgba8,
+    ///     stride: None,
+    /// };
+    /// let square = icon.pad_to_square([0, 0, 0, 0]);
+    ///
+    /// assert_eq!((square.width, square.height), (3, 3));
+    /// assert_eq!(square.pixels[..12], [0, 0, 0, 0, 255, 255, 255, 255, 0, 0, 0, 0]);
+    /// ```
+    #[must_use]
+    pub fn pad_to_square(&self, rgba_fill: [u8; 4]) -> Icon {
+        if self.width == self.height
+            || self.width == 0
+            || self.height == 0
+            || !self.has_valid_buffer()
+        {
+            return self.clone();
+        }
+
+        let side = self.width.max(self.height);
+        let left = ((side - self.width) / 2) as usize;
+        let top = ((side - self.height) / 2) as usize;
+        let stride = side as usize;
+        let mut pixels = self.format.swizzle(rgba_fill).repeat(stride * stride);
+
+        for (y, row) in self.rows().enumerate() {
+            let offset = ((top + y) * stride + left) * 4;
+
+            pixels[offset..offset + row.len()].copy_from_slice(row);
+        }
+
+        Icon {
+            width: side,
+            height: side,
+            pixels,
+            format: self.format,
+            stride: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Icon, PixelFormat};
+
+    #[test]
+    fn test_crop_transparent_border() {
+        let mut pixels = vec![0; 4 * 3 * 4];
+        pixels[(4 + 1) * 4..(4 + 3) * 4].copy_from_slice(&[1, 2, 3, 255, 4, 5, 6, 128]);
+
+        let icon = Icon {
+            width: 4,
+            height: 3,
+            pixels,
+            format: PixelFormat::Rgba8,
+            stride: None,
+        };
+        let cropped = icon.crop_transparent_border();
+
+        assert_eq!((cropped.width, cropped.height), (2, 1));
+        assert_eq!(cropped.pixels, vec![1, 2, 3, 255, 4, 5, 6, 128]);
+    }
+
+    #[test]
+    fn test_crop_degenerate_icons() {
+        let transparent = Icon {
+            width: 2,
+            height: 2,
+            pixels: vec![0; 2 * 2 * 4],
+            format: PixelFormat::Bgra8,
+            stride: None,
+        };
+        let cropped = transparent.crop_transparent_border();
+
+        assert_eq!((cropped.width, cropped.height), (1, 1));
+        assert_eq!(cropped.pixels, vec![0; 4]);
+        assert_eq!(cropped.format, PixelFormat::Bgra8);
+
+        let opaque = Icon {
+            width: 2,
+            height: 2,
+            pixels: vec![255; 2 * 2 * 4],
+            format: PixelFormat::Rgba8,
+            stride: None,
+        };
+
+        assert_eq!(opaque.crop_transparent_border(), opaque);
+
+        let empty = Icon {
+            width: 0,
+            height: 0,
+            pixels: Vec::new(),
+            format: PixelFormat::Rgba8,
+            stride: None,
+        };
+
+        assert_eq!(empty.crop_transparent_border(), empty);
+    }
+
+    #[test]
+    fn test_pad_to_square() {
+        let icon = Icon {
+            width: 2,
+            height: 1,
+            pixels: vec![1, 2, 3, 4, 5, 6, 7, 8],
+            format: PixelFormat::Bgra8,
+            stride: None,
+        };
+        let square = icon.pad_to_square([10, 20, 30, 40]);
+
+        assert_eq!((square.width, square.height), (2, 2));
+        assert_eq!(
+            square.pixels,
+            vec![1, 2, 3, 4, 5, 6, 7, 8, 30, 20, 10, 40, 30, 20, 10, 40]
+        );
+        assert_eq!(square.pad_to_square([0; 4]), square);
+    }
+}
@@ -0,0 +1,75 @@
+//! Resolution of the path of an open file, to get its icon from a file descriptor or a handle.
+
+use std::path::PathBuf;
+
+use crate::Error;
+
+/// Returns the path of the file open with the descriptor `fd`.
+///
+/// The kernel exposes the path of every descriptor of the process in `/proc/self/fd`.
+/// [`Error::PathDoesNotExist`] is returned if the file was deleted, it no longer has a path.
+#[cfg(target_os = "linux")]
+pub(crate) fn fd_path(fd: std::os::fd::BorrowedFd<'_>) -> Result<PathBuf, Error> {
+    use std::os::{fd::AsRawFd, unix::fs::MetadataExt};
+
+    let link = format!("/proc/self/fd/{}", fd.as_raw_fd());
+    let path = std::fs::read_link(&link).map_err(|error| {
+        Error::failed(format!(
+            "Invalid file descriptor {}: {error}",
+            fd.as_raw_fd()
+        ))
+    })?;
+
+    // Pipes and sockets have a pseudo path like "pipe:[1234]".
+    if !path.is_absolute() {
+        return Err(Error::failed(format!(
+            "The file descriptor {} is not a file",
+            fd.as_raw_fd()
+        )));
+    }
+
+    // The link of a deleted file is its former path followed by " (deleted)",
+    // the metadata of the link are the ones of the open file.
+    let metadata = std::fs::metadata(&link).map_err(Error::failed)?;
+
+    if metadata.nlink() == 0 {
+        return Err(Error::PathDoesNotExist);
+    }
+
+    Ok(path)
+}
+
+/// Returns the path of the file open with `handle`.
+///
+/// The path has the extended-length prefix, like `\\?\C:\file`.
+#[cfg(target_os = "windows")]
+pub(crate) fn handle_path(
+    handle: std::os::windows::io::BorrowedHandle<'_>,
+) -> Result<PathBuf, Error> {
+    use std::{
+        ffi::OsString,
+        os::windows::{ffi::OsStringExt, io::AsRawHandle},
+    };
+    use windows::Win32::{
+        Foundation::HANDLE,
+        Storage::FileSystem::{FILE_NAME_NORMALIZED, GetFinalPathNameByHandleW},
+    };
+
+    let handle = HANDLE(handle.as_raw_handle());
+    // The length includes the null character when the buffer is too small.
+    let length = unsafe { GetFinalPathNameByHandleW(handle, &mut [], FILE_NAME_NORMALIZED) };
+
+    if length == 0 {
+        return Err(Error::failed(windows::core::Error::from_thread()));
+    }
+
+    let mut buffer = vec![0u16; length as usize];
+    let length =
+        unsafe { GetFinalPathNameByHandleW(handle, &mut buffer, FILE_NAME_NORMALIZED) } as usize;
+
+    if length == 0 || length >= buffer.len() {
+        return Err(Error::failed(windows::core::Error::from_thread()));
+    }
+
+    Ok(PathBuf::from(OsString::from_wide(&buffer[..length])))
+}
@@ -0,0 +1,162 @@
+//! Iteration over the icons of the entries of a directory.
+
+use std::{
+    fs::{DirEntry, ReadDir},
+    path::{Path, PathBuf},
+};
+
+use crate::{Error, Icon, Options, implementation};
+
+/// Iterator over the entries of a directory and their icons, see [`icons_in_dir`](crate::icons_in_dir).
+///
+/// Each icon is retrieved when the iterator advances, so a listing can be displayed progressively.
+/// Hidden entries are skipped unless [`DirectoryIcons::include_hidden`] is used.
+pub struct DirectoryIcons {
+    entries: ReadDir,
+    size: u16,
+    include_hidden: bool,
+    /// Shared by all the entries, so the platform resources are created only once.
+    provider: Result<implementation::Provider, Error>,
+    options: Options,
+}
+
+impl DirectoryIcons {
+    pub(crate) fn new(entries: ReadDir, size: u16) -> Self {
+        Self {
+            entries,
+            size,
+            include_hidden: false,
+            provider: implementation::Provider::new(size),
+            options: Options::default(),
+        }
+    }
+
+    /// Sets whether hidden entries are iterated.
+    ///
+    /// On Windows, hidden entries have the hidden attribute.
+    /// On the other platforms, their name starts with a dot.
+    #[must_use]
+    pub fn include_hidden(mut self, include_hidden: bool) -> Self {
+        self.include_hidden = include_hidden;
+        self
+    }
+
+    fn get_icon(&self, path: &Path) -> Result<Icon, Error> {
+        match &self.provider {
+            Ok(provider) => provider.get_file_icon(path, self.size, &self.options),
+            // Every entry fails with the error of the backend, like `Error::Backend` without display.
+            Err(error) => Err(error.duplicate()),
+        }
+    }
+}
+
+impl Iterator for DirectoryIcons {
+    type Item = (PathBuf, Result<Icon, Error>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let entry = match self.entries.next()? {
+                Ok(entry) => entry,
+                // The entry can't be read, the error is reported without its path.
+                Err(error) => return Some((PathBuf::new(), Err(Error::Io(error)))),
+            };
+
+            if !self.include_hidden && is_hidden(&entry) {
+                continue;
+            }
+
+            let path = entry.path();
+            let icon = self.get_icon(&path);
+
+            return Some((path, icon));
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn is_hidden(entry: &DirEntry) -> bool {
+    use std::os::windows::fs::MetadataExt;
+
+    const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+
+    entry
+        .metadata()
+        .is_ok_and(|metadata| metadata.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0)
+}
+
+#[cfg(not(target_os = "windows"))]
+fn is_hidden(entry: &DirEntry) -> bool {
+    entry.file_name().as_encoded_bytes().starts_with(b".")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use crate::{DirectoryIcons, icons_in_dir};
+
+    /// Directory with a visible and a hidden file, removed when dropped.
+    struct TestDirectory(PathBuf);
+
+    impl TestDirectory {
+        fn new() -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "file_icon_provider_icons_in_dir_{}",
+                std::process::id()
+            ));
+
+            fs::create_dir_all(&path).expect("create directory");
+            fs::write(path.join("visible.txt"), "").expect("create visible file");
+            fs::write(path.join(".hidden"), "").expect("create hidden file");
+
+            #[cfg(target_os = "windows")]
+            {
+                use windows::{
+                    Win32::Storage::FileSystem::{FILE_ATTRIBUTE_HIDDEN, SetFileAttributesW},
+                    core::HSTRING,
+                };
+
+                let hidden = HSTRING::from(path.join(".hidden").as_path());
+
+                unsafe { SetFileAttributesW(&hidden, FILE_ATTRIBUTE_HIDDEN) }.expect("hide file");
+            }
+
+            Self(path)
+        }
+    }
+
+    impl Drop for TestDirectory {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn file_names(icons: DirectoryIcons) -> Vec<String> {
+        let mut names: Vec<String> = icons
+            .map(|(path, icon)| {
+                assert!(icon.is_ok(), "no icon for '{}'", path.display());
+                path.file_name()
+                    .expect("file name")
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect();
+
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_icons_in_dir() {
+        let directory = TestDirectory::new();
+        let icons = icons_in_dir(&directory.0, 32).expect("read directory");
+
+        assert_eq!(file_names(icons), ["visible.txt"]);
+
+        let icons = icons_in_dir(&directory.0, 32)
+            .expect("read directory")
+            .include_hidden(true);
+
+        assert_eq!(file_names(icons), [".hidden", "visible.txt"]);
+    }
+}
@@ -0,0 +1,76 @@
+//! Conversions from [`Icon`] to the types of `egui`.
+
+use egui::{ColorImage, Context, TextureHandle, TextureOptions};
+
+use crate::{Error, Icon, PixelFormat};
+
+impl TryFrom<Icon> for ColorImage {
+    type Error = Error;
+
+    /// Fails with [`Error::InvalidBuffer`] if the length of the pixel buffer does not match the dimensions.
+    fn try_from(icon: Icon) -> Result<Self, Self::Error> {
+        icon.check_buffer()?;
+
+        let icon = icon.into_packed();
+        let size = [icon.width as usize, icon.height as usize];
+
+        Ok(ColorImage::from_rgba_unmultiplied(
+            size,
+            &icon.into_format(PixelFormat::Rgba8).pixels,
+        ))
+    }
+}
+
+impl Icon {
+    /// Uploads the icon to the GPU as an `egui` texture, it is freed when the handle is dropped.
+    ///
+    /// `name` identifies the texture for debugging.
+    ///
+    /// # Errors
+    /// Fails with [`Error::InvalidBuffer`] if the length of the pixel buffer does not match the dimensions.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use file_icon_provider::get_file_icon;
+    ///
+    /// fn show_icon(ui: &mut egui::Ui) {
+    ///     if let Ok(icon) = get_file_icon("path/to/file", 32) {
+    ///         let texture = icon
+    ///             .load_egui_texture(ui.ctx(), "path/to/file", egui::TextureOptions::NEAREST)
+    ///             .expect("valid icon");
+    ///
+    ///         ui.image(&texture);
+    ///     }
+    /// }
+    /// ```
+    pub fn load_egui_texture(
+        self,
+        ctx: &Context,
+        name: impl Into<String>,
+        options: TextureOptions,
+    ) -> Result<TextureHandle, Error> {
+        Ok(ctx.load_texture(name, ColorImage::try_from(self)?, options))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::{Color32, ColorImage};
+
+    use crate::{Icon, PixelFormat};
+
+    #[test]
+    fn test_into_color_image() {
+        let icon = Icon {
+            width: 2,
+            height: 1,
+            pixels: vec![255, 0, 0, 255, 0, 0, 255, 255],
+            format: PixelFormat::Bgra8,
+            stride: None,
+        };
+        let image = ColorImage::try_from(icon).expect("convert icon");
+
+        assert_eq!(image.size, [2, 1]);
+        assert_eq!(image.pixels, vec![Color32::BLUE, Color32::RED]);
+    }
+}
@@ -0,0 +1,234 @@
+//! Provider shared by the whole process, enabled by the `global` feature.
+
+use std::{path::Path, sync::OnceLock, time::Duration};
+
+use crate::{CacheStats, ColorScheme, DEFAULT_TIMEOUT, Error, Icon, Provider};
+
+/// Size of the icons returned by the inner provider, only used to allocate its buffers
+/// because the size is passed with each request.
+const GLOBAL_ICON_SIZE: u16 = 32;
+
+static GLOBAL_PROVIDER: OnceLock<GlobalProvider> = OnceLock::new();
+
+/// A [`Provider`] shared by the whole process, returned by [`global`].
+///
+/// It avoids passing a provider through the whole application, with some trade-offs:
+/// * The icons are returned as [`Icon`], they are copied out of the cache for each request.
+///   Use a [`Provider`] with a converter to cache the images of a renderer instead.
+/// * The cache is shared by all the users of the process, including other libraries:
+///   [`GlobalProvider::clear`] clears it for everybody.
+/// * The settings can only be changed once, before the first use,
+///   with [`set_global_provider_config`].
+pub struct GlobalProvider {
+    /// The error if the provider can't be created, returned by each request.
+    provider: Result<Provider<Icon>, Error>,
+}
+
+impl GlobalProvider {
+    fn new(config: GlobalProviderConfig) -> Result<Self, Error> {
+        let mut builder = Provider::builder()
+            .icon_size(GLOBAL_ICON_SIZE)
+            .color_scheme(config.color_scheme)
+            .scale(config.scale)
+            .timeout(config.timeout)
+            .converter(|icon| icon);
+
+        if let Some(theme) = config.theme {
+            builder = builder.theme(theme);
+        }
+
+        if let Some(capacity) = config.capacity {
+            builder = builder.capacity(capacity);
+        }
+
+        Ok(Self {
+            provider: Ok(builder.build()?),
+        })
+    }
+
+    /// Retrieves the icon of a file at `size`, from the cache if possible.
+    ///
+    /// # Errors
+    /// See [`Error`] for the reasons of failures.
+    ///
+    /// # Example
+    /// ```
+    /// use file_icon_provider::global;
+    ///
+    /// if let Ok(icon) = global().icon("path/to/file", 64) {
+    ///     println!("Icon dimensions: {}x{}", icon.width, icon.height);
+    /// }
+    /// ```
+    ///
+    /// # Caveats
+    ///
+    /// On linux, this function must be called on the main thread.
+    pub fn icon(&self, path: impl AsRef<Path>, size: u16) -> Result<Icon, Error> {
+        self.provider()?.get_owned(path.as_ref(), size)
+    }
+
+    /// Returns how many icons were served from the cache and how many had to be retrieved,
+    /// see [`Provider::stats`].
+    pub fn stats(&self) -> CacheStats {
+        self.provider
+            .as_ref()
+            .map(Provider::stats)
+            .unwrap_or_default()
+    }
+
+    /// Removes all the cached icons, see [`Provider::clear`].
+    pub fn clear(&self) {
+        if let Ok(provider) = &self.provider {
+            provider.clear();
+        }
+    }
+
+    fn provider(&self) -> Result<&Provider<Icon>, Error> {
+        self.provider.as_ref().map_err(Error::duplicate)
+    }
+}
+
+/// Settings of the [`GlobalProvider`], see [`set_global_provider_config`].
+#[derive(Debug, Clone)]
+pub struct GlobalProviderConfig {
+    capacity: Option<usize>,
+    color_scheme: ColorScheme,
+    scale: f32,
+    theme: Option<String>,
+    timeout: Duration,
+}
+
+impl Default for GlobalProviderConfig {
+    fn default() -> Self {
+        Self {
+            capacity: None,
+            color_scheme: ColorScheme::default(),
+            scale: 1.0,
+            theme: None,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+}
+
+impl GlobalProviderConfig {
+    /// Creates the default settings: unbounded cache, system color scheme and scale of 1.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bounds the cache to `max_entries` icons, see [`Provider::with_capacity`].
+    #[must_use]
+    pub fn capacity(mut self, max_entries: usize) -> Self {
+        self.capacity = Some(max_entries);
+        self
+    }
+
+    /// Sets the color scheme icons are rendered for, see [`Provider::set_color_scheme`].
+    #[must_use]
+    pub fn color_scheme(mut self, color_scheme: ColorScheme) -> Self {
+        self.color_scheme = color_scheme;
+        self
+    }
+
+    /// Sets the number of physical pixels per logical pixel, see [`Provider::set_scale`].
+    #[must_use]
+    pub fn scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// Sets the name of the icon theme to use instead of the default one,
+    /// see [`ProviderBuilder::theme`](crate::ProviderBuilder::theme).
+    #[must_use]
+    pub fn theme(mut self, theme_name: impl Into<String>) -> Self {
+        self.theme = Some(theme_name.into());
+        self
+    }
+
+    /// Sets the maximum time to wait for the system to return an icon,
+    /// see [`ProviderBuilder::timeout`](crate::ProviderBuilder::timeout).
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+/// Returns the provider shared by the whole process, it is created on the first call.
+///
+/// See [`GlobalProvider`] for the trade-offs compared to a [`Provider`].
+///
+/// # Example
+/// ```
+/// use file_icon_provider::global;
+///
+/// if let Ok(icon) = global().icon("path/to/file", 64) {
+///     println!("Icon dimensions: {}x{}", icon.width, icon.height);
+/// }
+/// ```
+pub fn global() -> &'static GlobalProvider {
+    GLOBAL_PROVIDER.get_or_init(|| {
+        GlobalProvider::new(GlobalProviderConfig::default()).unwrap_or_else(|error| {
+            GlobalProvider {
+                provider: Err(error),
+            }
+        })
+    })
+}
+
+/// Configures the provider returned by [`global`], it must be called before the first use.
+///
+/// # Errors
+/// Fails with [`Error::Failed`] if the global provider is already created,
+/// or if the settings are invalid.
+///
+/// # Example
+/// ```
+/// use file_icon_provider::{GlobalProviderConfig, global, set_global_provider_config};
+///
+/// set_global_provider_config(GlobalProviderConfig::new().capacity(256))?;
+///
+/// if let Ok(icon) = global().icon("path/to/file", 64) {
+///     println!("Icon dimensions: {}x{}", icon.width, icon.height);
+/// }
+/// # Ok::<(), file_icon_provider::Error>(())
+/// ```
+pub fn set_global_provider_config(config: GlobalProviderConfig) -> Result<(), Error> {
+    let already_created = || Error::failed("The global provider is already created");
+
+    if GLOBAL_PROVIDER.get().is_some() {
+        return Err(already_created());
+    }
+
+    GLOBAL_PROVIDER
+        .set(GlobalProvider::new(config)?)
+        .map_err(|_| already_created())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::assert_matches;
+
+    use crate::{Error, GlobalProviderConfig, get_file_icon, global, set_global_provider_config};
+
+    #[test]
+    fn test_global() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+
+        assert_eq!(
+            global().icon(&file_path, 32).expect("global icon"),
+            get_file_icon(&file_path, 32).expect("icon")
+        );
+        assert!(global().icon(&file_path, 32).is_ok());
+        assert!(global().stats().hits >= 1);
+        assert_matches!(
+            set_global_provider_config(GlobalProviderConfig::new()),
+            Err(Error::Failed(_))
+        );
+        assert_matches!(
+            global().icon("NOT EXISTING", 32),
+            Err(Error::PathDoesNotExist)
+        );
+    }
+}
@@ -0,0 +1,66 @@
+//! Conversions from [`Icon`] to the types of the `image` crate.
+
+use image::{DynamicImage, RgbaImage};
+
+use crate::{Error, Icon, PixelFormat};
+
+impl TryFrom<Icon> for RgbaImage {
+    type Error = Error;
+
+    /// Fails with [`Error::InvalidBuffer`] if the length of the pixel buffer does not match the dimensions.
+    fn try_from(icon: Icon) -> Result<Self, Self::Error> {
+        icon.check_buffer()?;
+
+        let icon = icon.into_packed().into_format(PixelFormat::Rgba8);
+
+        // The length was checked, `from_raw` only fails if the buffer is too short.
+        RgbaImage::from_raw(icon.width, icon.height, icon.pixels)
+            .ok_or_else(|| Error::Conversion("the pixel buffer is too short".into()))
+    }
+}
+
+impl TryFrom<Icon> for DynamicImage {
+    type Error = Error;
+
+    /// Fails with [`Error::InvalidBuffer`] if the length of the pixel buffer does not match the dimensions.
+    fn try_from(icon: Icon) -> Result<Self, Self::Error> {
+        RgbaImage::try_from(icon).map(DynamicImage::ImageRgba8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::DynamicImage;
+
+    use crate::{Icon, PixelFormat};
+
+    #[test]
+    fn test_into_dynamic_image() {
+        let icon = Icon {
+            width: 2,
+            height: 1,
+            pixels: vec![1, 2, 3, 4, 5, 6, 7, 8],
+            format: PixelFormat::Rgba8,
+            stride: None,
+        };
+        let image = DynamicImage::try_from(icon).expect("convert icon");
+
+        assert_eq!(image.width(), 2);
+        assert_eq!(image.height(), 1);
+        assert_eq!(image.as_bytes(), [1, 2, 3, 4, 5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn test_padded_rows_are_removed() {
+        let icon = Icon {
+            width: 1,
+            height: 2,
+            pixels: vec![1, 2, 3, 4, 0, 0, 0, 0, 5, 6, 7, 8, 0, 0, 0, 0],
+            format: PixelFormat::Rgba8,
+            stride: Some(8),
+        };
+        let image = DynamicImage::try_from(icon).expect("convert icon");
+
+        assert_eq!(image.as_bytes(), [1, 2, 3, 4, 5, 6, 7, 8]);
+    }
+}
@@ -0,0 +1,1279 @@
+//! Linux backend reading the freedesktop icon themes and the shared-mime-info database directly.
+//!
+//! Unlike the GTK backend, it does not need a display and can be used from any thread.
+
+use std::{
+    collections::HashMap,
+    env,
+    ffi::OsStr,
+    fs::{self, File},
+    io::{BufReader, Read},
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, LazyLock, Mutex, PoisonError,
+        mpsc::{RecvTimeoutError, Sender, channel},
+    },
+    time::Duration,
+};
+
+use super::symbolic;
+use crate::{
+    Error, FileTypeInfo, Icon, IconSource, KnownFolder, Options, PixelFormat, ResizeFilter,
+    alpha::emblem_overlay, logging::debug, parallel::get_icons_in_chunks,
+    theme::notify_theme_changed,
+};
+
+pub(crate) fn get_file_icon(
+    path: impl AsRef<Path>,
+    size: u16,
+    options: &Options,
+) -> Result<Icon, Error> {
+    let content_type = path_content_type(path.as_ref())?;
+
+    options.check_cancelled()?;
+    get_content_type_icon(&content_type, size, options)
+}
+
+pub(crate) fn get_file_thumbnail(
+    _path: impl AsRef<Path>,
+    _size: u16,
+    _options: &Options,
+) -> Result<Icon, Error> {
+    Err(Error::Unsupported)
+}
+
+pub(crate) fn get_file_icons(
+    paths: &[&Path],
+    size: u16,
+    options: &Options,
+) -> Vec<Result<Icon, Error>> {
+    paths
+        .iter()
+        .map(|path| get_file_icon(path, size, options))
+        .collect()
+}
+
+pub(crate) fn get_file_icons_parallel(
+    paths: &[&Path],
+    size: u16,
+    threads: usize,
+    options: &Options,
+) -> Vec<Result<Icon, Error>> {
+    // The themes and the MIME database are loaded once and shared by the threads.
+    get_icons_in_chunks(paths, threads, |paths| get_file_icons(paths, size, options))
+}
+
+pub(crate) fn get_file_icon_sizes(
+    path: &Path,
+    sizes: &[u16],
+    options: &Options,
+) -> Result<Vec<Icon>, Error> {
+    let content_type = path_content_type(path)?;
+
+    sizes
+        .iter()
+        .map(|size| get_content_type_icon(&content_type, *size, options))
+        .collect()
+}
+
+pub(crate) fn get_icon_for_extension(
+    extension: &str,
+    size: u16,
+    options: &Options,
+) -> Result<Icon, Error> {
+    get_icon_for_name(&format!("file.{extension}"), size, options)
+}
+
+pub(crate) fn get_icon_for_name(
+    file_name: &str,
+    size: u16,
+    options: &Options,
+) -> Result<Icon, Error> {
+    // Only the file name is used to guess the content type, the file does not have to exist.
+    let content_type = MIME_DATABASE
+        .mime_type_for_name(file_name)
+        .unwrap_or(OCTET_STREAM);
+
+    get_content_type_icon(content_type, size, options)
+}
+
+pub(crate) fn get_icon_for_mime_type(
+    mime_type: &str,
+    size: u16,
+    options: &Options,
+) -> Result<Icon, Error> {
+    let Some(content_type) = MIME_DATABASE.resolve(mime_type) else {
+        return Err(Error::failed(format!("Unknown MIME type '{mime_type}'")));
+    };
+
+    get_content_type_icon(content_type, size, options)
+}
+
+pub(crate) fn get_folder_icon(size: u16, options: &Options) -> Result<Icon, Error> {
+    get_named_icon(&["folder"], size, options)
+}
+
+pub(crate) fn get_default_file_icon(size: u16, options: &Options) -> Result<Icon, Error> {
+    get_named_icon(&["text-x-generic", "application-x-generic"], size, options)
+}
+
+pub(crate) fn get_known_folder_icon(
+    folder: KnownFolder,
+    size: u16,
+    options: &Options,
+) -> Result<Icon, Error> {
+    get_named_icon(folder.icon_names(), size, options)
+}
+
+pub(crate) fn get_volume_icon(path: &Path, size: u16, options: &Options) -> Result<Icon, Error> {
+    // The devices are unknown, the desktop mounts the removable media in these folders.
+    let names: &[&str] = if path.starts_with("/media") || path.starts_with("/run/media") {
+        &["drive-removable-media", "drive-harddisk"]
+    } else {
+        &["drive-harddisk"]
+    };
+
+    get_named_icon(names, size, options)
+}
+
+pub(crate) fn get_application_icon(
+    executable: &Path,
+    size: u16,
+    options: &Options,
+) -> Result<Icon, Error> {
+    // Executables have no icon, the one of the desktop entry launching the program is used.
+    match desktop_entry_icon(executable, &data_dirs()) {
+        Some(icon) if Path::new(&icon).is_absolute() => {
+            let path = Path::new(&icon);
+            let icon = match path.extension().and_then(OsStr::to_str) {
+                Some("svg") => render_svg(path, size)?,
+                _ => decode_png(path)?,
+            };
+
+            Ok(fit(icon, size))
+        }
+        Some(icon) => get_named_icon(&[&icon, "application-x-executable"], size, options),
+        None => get_named_icon(&["application-x-executable"], size, options),
+    }
+}
+
+pub(crate) fn get_icon_from_resource(
+    _path: &Path,
+    _index: i32,
+    _size: u16,
+    _options: &Options,
+) -> Result<Icon, Error> {
+    Err(Error::Unsupported)
+}
+
+pub(crate) fn get_link_overlay(size: u16, options: &Options) -> Result<Icon, Error> {
+    let emblem = get_named_icon(&["emblem-symbolic-link"], (size / 2).max(1), options)?;
+
+    Ok(emblem_overlay(&emblem, size))
+}
+
+pub(crate) fn get_file_type_info(
+    path: &Path,
+    size: u16,
+    options: &Options,
+) -> Result<FileTypeInfo, Error> {
+    let content_type = path_content_type(path)?;
+    let description = mime_type_description(&content_type).unwrap_or_else(|| content_type.clone());
+    let icon = get_content_type_icon(&content_type, size, options)
+        .inspect_err(|error| debug!("No icon for '{content_type}': {error}"))
+        .ok();
+
+    Ok(FileTypeInfo {
+        identifier: content_type,
+        description,
+        icon,
+    })
+}
+
+pub(crate) fn get_available_sizes(path: &Path, options: &Options) -> Result<Vec<u16>, Error> {
+    let content_type = path_content_type(path)?;
+    let names = MIME_DATABASE.icon_names(&content_type);
+
+    themes(options)
+        .iter()
+        .find_map(|theme| names.iter().find_map(|name| theme.icon_sizes(name)))
+        .ok_or_else(|| Error::failed(format!("No icon named '{}'", names.join("', '"))))
+}
+
+pub(crate) fn get_icon_source(path: &Path) -> IconSource {
+    // The icons are always looked up in the icon themes from the content type.
+    match path_content_type(path) {
+        Ok(content_type) if content_type != OCTET_STREAM => IconSource::TypeAssociation,
+        _ => IconSource::Generic,
+    }
+}
+
+const OCTET_STREAM: &str = "application/octet-stream";
+
+/// Guesses the content type of a file from its name, or from its content if the name is not enough.
+fn path_content_type(path: &Path) -> Result<String, Error> {
+    let metadata = path.metadata().map_err(Error::failed)?;
+
+    if metadata.is_dir() {
+        return Ok("inode/directory".to_owned());
+    }
+
+    let name = path.file_name().and_then(OsStr::to_str).unwrap_or_default();
+
+    if let Some(mime_type) = MIME_DATABASE.mime_type_for_name(name) {
+        return Ok(mime_type.to_owned());
+    }
+
+    let content_type = if metadata.permissions().mode() & 0o111 != 0 {
+        "application/x-executable"
+    } else if metadata.len() == 0 {
+        "application/x-zerosize"
+    } else if is_text(path) {
+        "text/plain"
+    } else {
+        OCTET_STREAM
+    };
+
+    Ok(content_type.to_owned())
+}
+
+/// Returns `true` if the beginning of the file looks like text.
+fn is_text(path: &Path) -> bool {
+    let mut buffer = Vec::new();
+
+    File::open(path)
+        .and_then(|file| file.take(512).read_to_end(&mut buffer))
+        .is_ok_and(|_| !buffer.contains(&0) && std::str::from_utf8(&buffer).is_ok())
+}
+
+fn get_content_type_icon(content_type: &str, size: u16, options: &Options) -> Result<Icon, Error> {
+    let names = MIME_DATABASE.icon_names(content_type);
+    let names: Vec<&str> = names.iter().map(String::as_str).collect();
+
+    get_named_icon(&names, size, options)
+}
+
+/// Loads the first icon of `names` found in the icon themes.
+///
+/// The symbolic variants of the icons are tried first if requested by `options`.
+fn get_named_icon(names: &[&str], size: u16, options: &Options) -> Result<Icon, Error> {
+    symbolic::get_named_icon(names, size, options, load_named_icon)
+}
+
+/// Loads the first icon of `names` found in the icon themes, without looking for symbolic variants.
+fn load_named_icon(names: &[&str], size: u16, options: &Options) -> Result<Icon, Error> {
+    let Some(path) = find_icon(names, size, options) else {
+        return Err(Error::failed(format!(
+            "No icon named '{}'",
+            names.join("', '")
+        )));
+    };
+
+    options.check_cancelled()?;
+
+    debug!("Loading icon '{}'", path.display());
+
+    let icon = match path.extension().and_then(OsStr::to_str) {
+        Some("svg") => render_svg(&path, size)?,
+        _ => decode_png(&path)?,
+    };
+
+    Ok(fit(icon, size))
+}
+
+/// Scales `icon` so its largest side is `size`, like GTK does.
+fn fit(icon: Icon, size: u16) -> Icon {
+    let size = u64::from(size);
+    let largest = u64::from(icon.width.max(icon.height));
+
+    if largest == size || largest == 0 {
+        return icon;
+    }
+
+    let scale = |side: u32| u32::try_from((u64::from(side) * size / largest).max(1)).unwrap_or(1);
+
+    icon.resize_with_filter(
+        scale(icon.width),
+        scale(icon.height),
+        ResizeFilter::Triangle,
+    )
+}
+
+fn decode_png(path: &Path) -> Result<Icon, Error> {
+    use png::{ColorType, Decoder, Transformations};
+
+    let file = File::open(path).map_err(Error::failed)?;
+    let mut decoder = Decoder::new(BufReader::new(file));
+
+    decoder.set_transformations(Transformations::normalize_to_color8() | Transformations::ALPHA);
+
+    let mut reader = decoder.read_info().map_err(Error::failed)?;
+    let Some(buffer_size) = reader.output_buffer_size() else {
+        return Err(Error::failed("PNG image too large"));
+    };
+    let mut buffer = vec![0; buffer_size];
+    let info = reader.next_frame(&mut buffer).map_err(Error::failed)?;
+
+    buffer.truncate(info.buffer_size());
+
+    let pixels = match info.color_type {
+        ColorType::Rgba => buffer,
+        ColorType::GrayscaleAlpha => buffer
+            .chunks_exact(2)
+            .flat_map(|pixel| [pixel[0], pixel[0], pixel[0], pixel[1]])
+            .collect(),
+        color_type => {
+            return Err(Error::failed(format!(
+                "Unexpected PNG color type {color_type:?}"
+            )));
+        }
+    };
+
+    Ok(Icon {
+        width: info.width,
+        height: info.height,
+        pixels,
+        format: PixelFormat::Rgba8,
+        stride: None,
+    })
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn render_svg(path: &Path, size: u16) -> Result<Icon, Error> {
+    use resvg::{tiny_skia, usvg};
+
+    let data = fs::read(path).map_err(Error::failed)?;
+    let tree = usvg::Tree::from_data(&data, &usvg::Options::default()).map_err(Error::failed)?;
+    let size = u32::from(size);
+    let Some(mut pixmap) = tiny_skia::Pixmap::new(size, size) else {
+        return Err(Error::failed("Failed to create pixmap"));
+    };
+    let tree_size = tree.size();
+    let scale = size as f32 / tree_size.width().max(tree_size.height());
+
+    resvg::render(
+        &tree,
+        tiny_skia::Transform::from_scale(scale, scale),
+        &mut pixmap.as_mut(),
+    );
+
+    // The pixmap is premultiplied, icons have straight alpha.
+    let pixels = pixmap
+        .pixels()
+        .iter()
+        .flat_map(|pixel| {
+            let color = pixel.demultiply();
+
+            [color.red(), color.green(), color.blue(), color.alpha()]
+        })
+        .collect();
+
+    Ok(Icon {
+        width: size,
+        height: size,
+        pixels,
+        format: PixelFormat::Rgba8,
+        stride: None,
+    })
+}
+
+/// Returns the data directories, from the most important to the least important.
+fn data_dirs() -> Vec<PathBuf> {
+    let data_home = env::var_os("XDG_DATA_HOME")
+        .filter(|data_home| !data_home.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| home_dir().map(|home| home.join(".local/share")));
+    let data_dirs = env::var_os("XDG_DATA_DIRS")
+        .filter(|data_dirs| !data_dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".into());
+
+    data_home
+        .into_iter()
+        .chain(env::split_paths(&data_dirs))
+        .collect()
+}
+
+fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
+static MIME_DATABASE: LazyLock<MimeDatabase> = LazyLock::new(MimeDatabase::load);
+
+/// The parts of the shared-mime-info database needed to find the icon of a file.
+#[derive(Default)]
+struct MimeDatabase {
+    globs: Vec<Glob>,
+    aliases: HashMap<String, String>,
+    icons: HashMap<String, String>,
+    generic_icons: HashMap<String, String>,
+}
+
+struct Glob {
+    weight: u32,
+    mime_type: String,
+    /// Lowercase, unless the glob is case sensitive.
+    pattern: String,
+    case_sensitive: bool,
+}
+
+impl MimeDatabase {
+    fn load() -> Self {
+        let mut database = Self::default();
+
+        for mime_dir in data_dirs().iter().map(|data_dir| data_dir.join("mime")) {
+            if let Ok(content) = fs::read_to_string(mime_dir.join("globs2")) {
+                database.add_globs(&content);
+            }
+
+            for (file_name, map) in [
+                ("aliases", &mut database.aliases),
+                ("icons", &mut database.icons),
+                ("generic-icons", &mut database.generic_icons),
+            ] {
+                if let Ok(content) = fs::read_to_string(mime_dir.join(file_name)) {
+                    add_pairs(map, &content);
+                }
+            }
+        }
+
+        database
+    }
+
+    /// Adds the globs of a `globs2` file, made of lines like `50:text/plain:*.txt`.
+    fn add_globs(&mut self, content: &str) {
+        for line in content.lines().filter(|line| !line.starts_with('#')) {
+            let mut fields = line.split(':');
+            let (Some(weight), Some(mime_type), Some(pattern)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                continue;
+            };
+            let Ok(weight) = weight.parse() else {
+                continue;
+            };
+
+            if pattern == "__NOGLOBS__" {
+                continue;
+            }
+
+            let case_sensitive = fields.next().is_some_and(|flags| flags.contains("cs"));
+
+            self.globs.push(Glob {
+                weight,
+                mime_type: mime_type.to_owned(),
+                pattern: if case_sensitive {
+                    pattern.to_owned()
+                } else {
+                    pattern.to_lowercase()
+                },
+                case_sensitive,
+            });
+        }
+    }
+
+    /// Returns the MIME type matching a file name.
+    ///
+    /// Literal names win over patterns, then the highest weight and the longest pattern.
+    fn mime_type_for_name(&self, name: &str) -> Option<&str> {
+        let lowercase_name = name.to_lowercase();
+
+        self.globs
+            .iter()
+            .filter(|glob| {
+                let name = if glob.case_sensitive {
+                    name
+                } else {
+                    &lowercase_name
+                };
+
+                glob_matches(&glob.pattern, name)
+            })
+            .max_by_key(|glob| {
+                let is_literal = !glob.pattern.contains(['*', '?', '[']);
+
+                (is_literal, glob.weight, glob.pattern.len())
+            })
+            .map(|glob| glob.mime_type.as_str())
+    }
+
+    /// Returns the canonical name of a MIME type, or `None` if the MIME type is unknown.
+    fn resolve<'a>(&'a self, mime_type: &'a str) -> Option<&'a str> {
+        if let Some(canonical) = self.aliases.get(mime_type) {
+            return Some(canonical.as_str());
+        }
+
+        let is_known = self.icons.contains_key(mime_type)
+            || self.generic_icons.contains_key(mime_type)
+            || self.globs.iter().any(|glob| glob.mime_type == mime_type);
+
+        is_known.then_some(mime_type)
+    }
+
+    /// Returns the names of the icons of a MIME type, from the most specific to the most generic.
+    fn icon_names(&self, mime_type: &str) -> Vec<String> {
+        let media = mime_type.split('/').next().unwrap_or(mime_type);
+        let mut names = Vec::new();
+
+        if mime_type == "inode/directory" {
+            names.push("folder".to_owned());
+        }
+        names.extend(self.icons.get(mime_type).cloned());
+        names.push(mime_type.replace('/', "-"));
+        names.extend(self.generic_icons.get(mime_type).cloned());
+        names.push(format!("{media}-x-generic"));
+        names.dedup();
+        names
+    }
+}
+
+/// Returns the description of a MIME type, like "PDF document".
+///
+/// The descriptions are in the XML file of each MIME type, they are only read when requested.
+fn mime_type_description(mime_type: &str) -> Option<String> {
+    data_dirs().iter().find_map(|data_dir| {
+        let content = fs::read_to_string(data_dir.join("mime").join(format!("{mime_type}.xml")));
+
+        parse_comment(&content.ok()?)
+    })
+}
+
+/// Returns the untranslated `<comment>` of the XML file of a MIME type.
+fn parse_comment(content: &str) -> Option<String> {
+    // The translations have a `xml:lang` attribute, the untranslated comment has none.
+    let start = content.find("<comment>")? + "<comment>".len();
+    let length = content[start..].find("</comment>")?;
+
+    Some(
+        content[start..start + length]
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&apos;", "'")
+            .replace("&amp;", "&"),
+    )
+}
+
+/// Adds the pairs of a file made of lines like `key value` or `key:value`,
+/// the pairs already in `map` are kept.
+fn add_pairs(map: &mut HashMap<String, String>, content: &str) {
+    for line in content.lines().filter(|line| !line.starts_with('#')) {
+        if let Some((key, value)) = line.split_once([' ', ':']) {
+            map.entry(key.to_owned())
+                .or_insert_with(|| value.trim().to_owned());
+        }
+    }
+}
+
+/// Matches a file name against a shell glob supporting `*`, `?` and `[...]`.
+fn glob_matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    matches(&pattern, &name)
+}
+
+fn matches(pattern: &[char], name: &[char]) -> bool {
+    match pattern {
+        [] => name.is_empty(),
+        ['*', rest @ ..] => (0..=name.len()).any(|skip| matches(rest, &name[skip..])),
+        ['?', rest @ ..] => !name.is_empty() && matches(rest, &name[1..]),
+        ['[', class @ ..] if class.contains(&']') => {
+            let end = class.iter().position(|c| *c == ']').unwrap_or_default();
+
+            match name {
+                [c, name @ ..] => {
+                    class_matches(&class[..end], *c) && matches(&class[end + 1..], name)
+                }
+                [] => false,
+            }
+        }
+        [c, rest @ ..] => name.first() == Some(c) && matches(rest, &name[1..]),
+    }
+}
+
+/// Matches a character against the content of a `[...]` class, like `a-z` or `!0-9`.
+fn class_matches(class: &[char], c: char) -> bool {
+    let (negated, class) = match class {
+        ['!', class @ ..] => (true, class),
+        class => (false, class),
+    };
+    let mut found = false;
+    let mut index = 0;
+
+    while index < class.len() {
+        if index + 2 < class.len() && class[index + 1] == '-' {
+            found |= (class[index]..=class[index + 2]).contains(&c);
+            index += 3;
+        } else {
+            found |= class[index] == c;
+            index += 1;
+        }
+    }
+
+    found != negated
+}
+
+/// Returns the directories icon themes are searched in, from the most important to the least important.
+fn icon_dirs() -> Vec<PathBuf> {
+    home_dir()
+        .map(|home| home.join(".icons"))
+        .into_iter()
+        .chain(data_dirs().iter().map(|data_dir| data_dir.join("icons")))
+        .collect()
+}
+
+/// Name of the icon theme selected by the user in the GTK settings, read again by [`refresh_theme`].
+static SYSTEM_THEME: LazyLock<Mutex<Option<String>>> =
+    LazyLock::new(|| Mutex::new(read_system_theme()));
+
+/// Reads the name of the icon theme selected by the user in the GTK settings.
+fn read_system_theme() -> Option<String> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .filter(|config_home| !config_home.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| home_dir().map(|home| home.join(".config")))?;
+
+    ["gtk-4.0", "gtk-3.0"].iter().find_map(|gtk| {
+        let content = fs::read_to_string(config_home.join(gtk).join("settings.ini")).ok()?;
+
+        parse_ini(&content)
+            .get("Settings")?
+            .get("gtk-icon-theme-name")
+            .cloned()
+    })
+}
+
+fn system_theme() -> Option<String> {
+    SYSTEM_THEME
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
+
+/// How often the GTK settings are read to detect a change of the icon theme.
+const THEME_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Set while the GTK settings are polled, the polling thread stops once it is dropped.
+static THEME_POLLING: Mutex<Option<Sender<()>>> = Mutex::new(None);
+
+/// Starts a thread polling the GTK settings, without GTK there is no main loop notifying their changes.
+#[allow(clippy::unnecessary_wraps)]
+pub(crate) fn watch_theme() -> Result<(), Error> {
+    let mut polling = THEME_POLLING.lock().unwrap_or_else(PoisonError::into_inner);
+
+    if polling.is_some() {
+        return Ok(());
+    }
+
+    let (sender, receiver) = channel();
+
+    std::thread::spawn(move || {
+        // Nothing is sent, the channel only wakes the thread up when it is disconnected.
+        while let Err(RecvTimeoutError::Timeout) = receiver.recv_timeout(THEME_POLL_INTERVAL) {
+            let theme = read_system_theme();
+            let mut system_theme = SYSTEM_THEME.lock().unwrap_or_else(PoisonError::into_inner);
+
+            if *system_theme != theme {
+                debug!("The icon theme changed to {theme:?}");
+                *system_theme = theme;
+                drop(system_theme);
+                notify_theme_changed();
+            }
+        }
+    });
+    *polling = Some(sender);
+
+    Ok(())
+}
+
+/// Stops the thread polling the GTK settings.
+pub(crate) fn unwatch_theme() {
+    THEME_POLLING
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take();
+}
+
+/// Reads the icon theme of the user again, and forgets the loaded themes.
+pub(crate) fn refresh_theme() {
+    *SYSTEM_THEME.lock().unwrap_or_else(PoisonError::into_inner) = read_system_theme();
+    ICON_THEMES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clear();
+}
+
+const FALLBACK_THEME: &str = "hicolor";
+
+/// Finds the file of the first icon of `names`, looking in the requested theme,
+/// then in the theme of the user, then in the fallback theme, and their parents.
+fn find_icon(names: &[&str], size: u16, options: &Options) -> Option<PathBuf> {
+    let found = themes(options).iter().find_map(|theme| {
+        names
+            .iter()
+            .find_map(|name| theme.lookup(name, u32::from(size)))
+    });
+
+    // Some applications install their icons outside of the themes.
+    found.or_else(|| {
+        names.iter().find_map(|name| {
+            data_dirs().iter().find_map(|data_dir| {
+                ["png", "svg"]
+                    .iter()
+                    .map(|extension| data_dir.join("pixmaps").join(format!("{name}.{extension}")))
+                    .find(|path| path.is_file())
+            })
+        })
+    })
+}
+
+/// Returns the themes icons are looked up in: the requested theme, the theme of the user,
+/// the fallback theme, and their parents.
+fn themes(options: &Options) -> Vec<Arc<IconTheme>> {
+    let mut themes = Vec::new();
+
+    let system_theme = system_theme();
+
+    for theme_name in options
+        .theme
+        .iter()
+        .chain(system_theme.iter())
+        .map(String::as_str)
+        .chain(["default", FALLBACK_THEME])
+    {
+        add_theme(theme_name, &mut themes);
+    }
+
+    themes
+}
+
+/// Adds a theme and the themes it inherits from to `themes`, unless they are already in it.
+fn add_theme(name: &str, themes: &mut Vec<Arc<IconTheme>>) {
+    if themes.iter().any(|theme| theme.name == name) {
+        return;
+    }
+
+    let Some(theme) = IconTheme::get(name) else {
+        debug!("Icon theme '{name}' not found");
+        return;
+    };
+
+    themes.push(theme.clone());
+
+    for parent in &theme.inherits {
+        add_theme(parent, themes);
+    }
+}
+
+static ICON_THEMES: LazyLock<Mutex<HashMap<String, Option<Arc<IconTheme>>>>> =
+    LazyLock::new(Mutex::default);
+
+struct IconTheme {
+    name: String,
+    /// The directories of the theme, one per icon directory containing it.
+    base_dirs: Vec<PathBuf>,
+    inherits: Vec<String>,
+    subdirs: Vec<Subdir>,
+}
+
+/// A directory of an icon theme, with the sizes of the icons it contains.
+#[derive(Debug, PartialEq)]
+struct Subdir {
+    path: String,
+    size: u32,
+    scale: u32,
+    kind: SubdirKind,
+}
+
+#[derive(Debug, PartialEq)]
+enum SubdirKind {
+    Fixed,
+    Scalable { min_size: u32, max_size: u32 },
+    Threshold(u32),
+}
+
+impl IconTheme {
+    /// Returns the theme named `name`, loading it on the first call.
+    fn get(name: &str) -> Option<Arc<IconTheme>> {
+        ICON_THEMES
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(name.to_owned())
+            .or_insert_with(|| Self::load(name).map(Arc::new))
+            .clone()
+    }
+
+    fn load(name: &str) -> Option<Self> {
+        let base_dirs: Vec<PathBuf> = icon_dirs()
+            .iter()
+            .map(|icon_dir| icon_dir.join(name))
+            .filter(|base_dir| base_dir.is_dir())
+            .collect();
+        let index = base_dirs
+            .iter()
+            .find_map(|base_dir| fs::read_to_string(base_dir.join("index.theme")).ok())?;
+
+        Some(Self::parse(name, base_dirs, &index))
+    }
+
+    fn parse(name: &str, base_dirs: Vec<PathBuf>, index: &str) -> Self {
+        let sections = parse_ini(index);
+        let theme = sections.get("Icon Theme");
+        let list = |key: &str| -> Vec<String> {
+            theme
+                .and_then(|theme| theme.get(key))
+                .map(|value| {
+                    value
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|item| !item.is_empty())
+                        .map(str::to_owned)
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        let subdirs = list("Directories")
+            .into_iter()
+            .chain(list("ScaledDirectories"))
+            .filter_map(|path| {
+                let section = sections.get(&path)?;
+                let number = |key: &str| section.get(key).and_then(|value| value.parse().ok());
+                let size = number("Size")?;
+                let kind = match section.get("Type").map(String::as_str) {
+                    Some("Fixed") => SubdirKind::Fixed,
+                    Some("Scalable") => SubdirKind::Scalable {
+                        min_size: number("MinSize").unwrap_or(size),
+                        max_size: number("MaxSize").unwrap_or(size),
+                    },
+                    _ => SubdirKind::Threshold(number("Threshold").unwrap_or(2)),
+                };
+
+                Some(Subdir {
+                    path,
+                    size,
+                    scale: number("Scale").unwrap_or(1),
+                    kind,
+                })
+            })
+            .collect();
+
+        Self {
+            name: name.to_owned(),
+            base_dirs,
+            inherits: list("Inherits"),
+            subdirs,
+        }
+    }
+
+    /// Finds the file of an icon of this theme, preferring the directories matching `size`,
+    /// then the directories with the closest size.
+    fn lookup(&self, name: &str, size: u32) -> Option<PathBuf> {
+        let mut closest: Option<(u32, PathBuf)> = None;
+
+        for subdir in &self.subdirs {
+            let distance = subdir.size_distance(size);
+
+            if closest
+                .as_ref()
+                .is_some_and(|(closest_distance, _)| *closest_distance <= distance)
+            {
+                continue;
+            }
+
+            if let Some(path) = self.find_file(subdir, name) {
+                if distance == 0 {
+                    return Some(path);
+                }
+
+                closest = Some((distance, path));
+            }
+        }
+
+        closest.map(|(_, path)| path)
+    }
+
+    /// Returns the sizes of an icon of this theme, without the scalable ones,
+    /// or `None` if the theme does not have the icon.
+    fn icon_sizes(&self, name: &str) -> Option<Vec<u16>> {
+        let mut found = false;
+        let mut sizes = Vec::new();
+
+        for subdir in &self.subdirs {
+            if self.find_file(subdir, name).is_none() {
+                continue;
+            }
+
+            found = true;
+
+            // The icons of scaled directories are the same sizes with more pixels.
+            if subdir.scale == 1 && !matches!(subdir.kind, SubdirKind::Scalable { .. }) {
+                sizes.extend(u16::try_from(subdir.size).ok());
+            }
+        }
+
+        sizes.sort_unstable();
+        sizes.dedup();
+
+        found.then_some(sizes)
+    }
+
+    fn find_file(&self, subdir: &Subdir, name: &str) -> Option<PathBuf> {
+        self.base_dirs.iter().find_map(|base_dir| {
+            ["png", "svg"]
+                .iter()
+                .map(|extension| {
+                    base_dir
+                        .join(&subdir.path)
+                        .join(format!("{name}.{extension}"))
+                })
+                .find(|path| path.is_file())
+        })
+    }
+}
+
+impl Subdir {
+    /// Returns how far the icons of this directory are from `size`, 0 if they match.
+    fn size_distance(&self, size: u32) -> u32 {
+        let (min_size, max_size) = match self.kind {
+            SubdirKind::Fixed => (self.size, self.size),
+            SubdirKind::Scalable { min_size, max_size } => (min_size, max_size),
+            SubdirKind::Threshold(threshold) => (
+                self.size.saturating_sub(threshold),
+                self.size.saturating_add(threshold),
+            ),
+        };
+        let distance = if size < min_size {
+            min_size - size
+        } else {
+            size.saturating_sub(max_size)
+        };
+
+        // The icons of scaled directories are for HiDPI displays.
+        if self.scale == 1 {
+            distance
+        } else {
+            distance.saturating_add(1)
+        }
+    }
+}
+
+/// Returns the `Icon` of the desktop entry launching `executable`, in the `applications`
+/// directories of `data_dirs`.
+///
+/// The entries are matched by the file name of the program of their `TryExec` or `Exec` key,
+/// the arguments of `Exec` are ignored.
+fn desktop_entry_icon(executable: &Path, data_dirs: &[PathBuf]) -> Option<String> {
+    let file_name = executable.file_name()?;
+
+    data_dirs.iter().find_map(|data_dir| {
+        fs::read_dir(data_dir.join("applications"))
+            .ok()?
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension() == Some(OsStr::new("desktop")))
+            .find_map(|path| {
+                let sections = parse_ini(&fs::read_to_string(path).ok()?);
+                let entry = sections.get("Desktop Entry")?;
+                let launches = ["TryExec", "Exec"]
+                    .iter()
+                    .filter_map(|key| entry.get(*key)?.split_whitespace().next())
+                    .any(|program| {
+                        Path::new(program.trim_matches('"')).file_name() == Some(file_name)
+                    });
+
+                if launches {
+                    entry.get("Icon").cloned()
+                } else {
+                    None
+                }
+            })
+    })
+}
+
+/// Parses a desktop entry style file into its sections.
+fn parse_ini(content: &str) -> HashMap<String, HashMap<String, String>> {
+    let mut sections: HashMap<String, HashMap<String, String>> = HashMap::new();
+    let mut current = None;
+
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(section) = line
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            current = Some(section.to_owned());
+        } else if let (Some(section), Some((key, value))) = (&current, line.split_once('=')) {
+            sections
+                .entry(section.clone())
+                .or_default()
+                .insert(key.trim().to_owned(), value.trim().to_owned());
+        }
+    }
+
+    sections
+}
+
+pub(crate) struct Provider;
+
+impl Provider {
+    #[allow(clippy::unnecessary_wraps)]
+    pub fn new(_icon_size: u16) -> Result<Self, Error> {
+        Ok(Self)
+    }
+
+    /// Returns the key used to cache the icon of `path`, or `None` if its icon must not be cached.
+    #[allow(clippy::unused_self)]
+    pub fn cache_key(&self, path: &Path) -> Option<String> {
+        if path.is_dir()
+            || path.is_symlink()
+            || path
+                .metadata()
+                .is_ok_and(|metadata| metadata.permissions().mode() & 0o111 != 0)
+        {
+            return None;
+        }
+
+        // Desktop entries have the icon of their application.
+        if path.extension() == Some(OsStr::new("desktop")) {
+            return None;
+        }
+
+        // Files sharing an extension can have different content types, and so different icons.
+        path_content_type(path).ok()
+    }
+
+    #[allow(clippy::unused_self)]
+    pub fn get_file_icon(&self, path: &Path, size: u16, options: &Options) -> Result<Icon, Error> {
+        get_file_icon(path, options.scaled_size(size), options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{
+        IconTheme, MimeDatabase, Subdir, SubdirKind, desktop_entry_icon, glob_matches,
+        parse_comment,
+    };
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("*.txt", "notes.txt"));
+        assert!(glob_matches("makefile", "makefile"));
+        assert!(glob_matches("*.[1-9]", "ls.1"));
+        assert!(glob_matches("*.?z", "archive.gz"));
+        assert!(glob_matches("*.[!a]", "file.b"));
+        assert!(!glob_matches("*.txt", "notes.txt.bak"));
+        assert!(!glob_matches("*.[1-9]", "ls.a"));
+        assert!(!glob_matches("*.[!a]", "file.a"));
+    }
+
+    #[test]
+    fn test_mime_type_for_name() {
+        let mut database = MimeDatabase::default();
+
+        database.add_globs(
+            "# comment\n\
+             50:text/plain:*.txt\n\
+             50:text/x-readme:readme.txt\n\
+             50:application/gzip:*.gz\n\
+             60:application/x-compressed-tar:*.tar.gz\n\
+             50:text/x-c++src:*.C:cs\n",
+        );
+
+        assert_eq!(database.mime_type_for_name("notes.TXT"), Some("text/plain"));
+        assert_eq!(
+            database.mime_type_for_name("README.txt"),
+            Some("text/x-readme")
+        );
+        assert_eq!(
+            database.mime_type_for_name("archive.tar.gz"),
+            Some("application/x-compressed-tar")
+        );
+        assert_eq!(database.mime_type_for_name("main.C"), Some("text/x-c++src"));
+        assert_eq!(database.mime_type_for_name("main.c"), None);
+    }
+
+    #[test]
+    fn test_icon_names() {
+        let mut database = MimeDatabase::default();
+
+        database
+            .generic_icons
+            .insert("text/x-rust".to_owned(), "text-x-script".to_owned());
+
+        assert_eq!(
+            database.icon_names("text/x-rust"),
+            ["text-x-rust", "text-x-script", "text-x-generic"]
+        );
+    }
+
+    #[test]
+    fn test_parse_comment() {
+        let content = "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+             <mime-type type=\"text/x-c++src\">\n\
+             <comment xml:lang=\"fr\">code source C++</comment>\n\
+             <comment>C++ source code &amp; headers</comment>\n\
+             </mime-type>\n";
+
+        assert_eq!(
+            parse_comment(content).as_deref(),
+            Some("C++ source code & headers")
+        );
+        assert_eq!(parse_comment("<mime-type/>"), None);
+    }
+
+    #[test]
+    fn test_parse_index_theme() {
+        let theme = IconTheme::parse(
+            "Test",
+            vec![PathBuf::from("/icons/Test")],
+            "[Icon Theme]\n\
+             Name=Test\n\
+             Inherits=Parent, hicolor\n\
+             Directories=16x16/mimetypes,scalable/mimetypes\n\
+             \n\
+             [16x16/mimetypes]\n\
+             Size=16\n\
+             Type=Fixed\n\
+             \n\
+             [scalable/mimetypes]\n\
+             Size=64\n\
+             MinSize=8\n\
+             MaxSize=512\n\
+             Type=Scalable\n",
+        );
+
+        assert_eq!(theme.inherits, ["Parent", "hicolor"]);
+        assert_eq!(
+            theme.subdirs,
+            [
+                Subdir {
+                    path: "16x16/mimetypes".to_owned(),
+                    size: 16,
+                    scale: 1,
+                    kind: SubdirKind::Fixed,
+                },
+                Subdir {
+                    path: "scalable/mimetypes".to_owned(),
+                    size: 64,
+                    scale: 1,
+                    kind: SubdirKind::Scalable {
+                        min_size: 8,
+                        max_size: 512,
+                    },
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_icon_sizes() {
+        let base_dir = std::env::temp_dir().join(format!(
+            "file_icon_provider_test_icon_sizes_{}",
+            std::process::id()
+        ));
+
+        for file in [
+            "16x16/mimetypes/text-x-generic.png",
+            "48x48/mimetypes/text-x-generic.png",
+            "48x48@2/mimetypes/text-x-generic.png",
+            "scalable/mimetypes/text-x-generic.svg",
+        ] {
+            let path = base_dir.join(file);
+
+            std::fs::create_dir_all(path.parent().expect("parent")).expect("create directory");
+            std::fs::write(path, []).expect("create icon");
+        }
+
+        let theme = IconTheme::parse(
+            "Test",
+            vec![base_dir.clone()],
+            "[Icon Theme]\n\
+             Directories=16x16/mimetypes,48x48/mimetypes,scalable/mimetypes\n\
+             ScaledDirectories=48x48@2/mimetypes\n\
+             [16x16/mimetypes]\n\
+             Size=16\n\
+             [48x48/mimetypes]\n\
+             Size=48\n\
+             [48x48@2/mimetypes]\n\
+             Size=48\n\
+             Scale=2\n\
+             [scalable/mimetypes]\n\
+             Size=64\n\
+             Type=Scalable\n",
+        );
+
+        assert_eq!(theme.icon_sizes("text-x-generic"), Some(vec![16, 48]));
+        assert_eq!(theme.icon_sizes("text-x-script"), None);
+
+        std::fs::remove_dir_all(base_dir).expect("remove theme");
+    }
+
+    #[test]
+    fn test_desktop_entry_icon() {
+        let data_dir = std::env::temp_dir().join(format!(
+            "file_icon_provider_test_desktop_entry_icon_{}",
+            std::process::id()
+        ));
+        let applications = data_dir.join("applications");
+
+        std::fs::create_dir_all(&applications).expect("create directory");
+        std::fs::write(
+            applications.join("editor.desktop"),
+            "[Desktop Entry]\nName=Editor\nExec=\"/usr/bin/editor\" --new-window %F\nIcon=accessories-text-editor\n",
+        )
+        .expect("create desktop entry");
+        std::fs::write(
+            applications.join("editor.txt"),
+            "[Desktop Entry]\nExec=viewer\nIcon=viewer\n",
+        )
+        .expect("create file");
+
+        let data_dirs = [data_dir.clone()];
+
+        assert_eq!(
+            desktop_entry_icon(Path::new("/opt/editor/editor"), &data_dirs),
+            Some("accessories-text-editor".to_string())
+        );
+        assert_eq!(
+            desktop_entry_icon(Path::new("/usr/bin/viewer"), &data_dirs),
+            None
+        );
+
+        std::fs::remove_dir_all(data_dir).expect("remove data directory");
+    }
+
+    #[test]
+    fn test_size_distance() {
+        let subdir = |size, kind| Subdir {
+            path: String::new(),
+            size,
+            scale: 1,
+            kind,
+        };
+
+        assert_eq!(subdir(32, SubdirKind::Fixed).size_distance(32), 0);
+        assert_eq!(subdir(32, SubdirKind::Fixed).size_distance(48), 16);
+        assert_eq!(subdir(32, SubdirKind::Threshold(2)).size_distance(30), 0);
+        assert_eq!(subdir(32, SubdirKind::Threshold(2)).size_distance(24), 6);
+        assert_eq!(
+            subdir(
+                64,
+                SubdirKind::Scalable {
+                    min_size: 8,
+                    max_size: 512
+                }
+            )
+            .size_distance(300),
+            0
+        );
+    }
+}
@@ -1,105 +1,653 @@
-use std::{
-    cell::RefCell, collections::BTreeMap, ffi::OsStr, os::unix::fs::PermissionsExt, path::Path,
+use std::{cell::Cell, ffi::OsStr, os::unix::fs::PermissionsExt, path::Path, sync::OnceLock};
+
+use gtk::IconTheme;
+
+use super::symbolic;
+use crate::{
+    Corner, Error, FileTypeInfo, Icon, IconSource, KnownFolder, Options, PixelFormat,
+    alpha::emblem_overlay, logging::debug, theme::notify_theme_changed,
 };
 
-use crate::Icon;
-use log::error;
+pub(crate) fn get_file_icon(
+    path: impl AsRef<Path>,
+    size: u16,
+    options: &Options,
+) -> Result<Icon, Error> {
+    initialize_gtk()?;
+    get_path_icon(path.as_ref(), size, options)
+}
+
+pub(crate) fn get_file_thumbnail(
+    path: impl AsRef<Path>,
+    size: u16,
+    _options: &Options,
+) -> Result<Icon, Error> {
+    use gio::{Cancellable, File, FileQueryInfoFlags, prelude::FileExt};
+    use gtk::gdk_pixbuf::Pixbuf;
+
+    initialize_gtk()?;
 
-pub(crate) fn get_file_icon(path: impl AsRef<Path>, size: u16) -> Option<Icon> {
-    use gio::{
-        Cancellable, File, FileQueryInfoFlags,
-        prelude::{Cast, FileExt},
+    // The thumbnails are generated by the file managers, only the freedesktop cache is read.
+    let file = File::for_path(path.as_ref());
+    let file_info = file
+        .query_info(
+            "thumbnail::path,thumbnail::is-valid",
+            FileQueryInfoFlags::NONE,
+            None::<&Cancellable>,
+        )
+        .map_err(Error::failed)?;
+    let Some(thumbnail_path) = file_info.attribute_byte_string("thumbnail::path") else {
+        return Err(Error::failed("No cached thumbnail"));
     };
-    use gtk::{IconLookupFlags, IconTheme, prelude::IconThemeExt};
 
-    if !gtk::is_initialized() {
-        if let Err(error) = gtk::init() {
-            error!("Failed to initialize GTK: {error}");
-            return None;
-        }
+    if !file_info.boolean("thumbnail::is-valid") {
+        return Err(Error::failed("The cached thumbnail is outdated"));
     }
 
-    let file = File::for_path(path);
-    let file_info = match file.query_info("*", FileQueryInfoFlags::NONE, None::<&Cancellable>) {
-        Ok(file_info) => file_info,
-        Err(error) => {
-            error!("Can't get file info: {error}");
-            return None;
+    let size = i32::from(size);
+    let pixbuf = Pixbuf::from_file_at_scale(thumbnail_path.as_str(), size, size, true)
+        .map_err(Error::failed)?;
+
+    pixbuf_to_icon(&pixbuf).ok_or_else(|| Error::failed("Invalid thumbnail"))
+}
+
+pub(crate) fn get_file_icons(
+    paths: &[&Path],
+    size: u16,
+    options: &Options,
+) -> Vec<Result<Icon, Error>> {
+    if let Err(error) = initialize_gtk() {
+        return paths.iter().map(|_| Err(error.duplicate())).collect();
+    }
+
+    paths
+        .iter()
+        .map(|path| get_path_icon(path, size, options))
+        .collect()
+}
+
+/// GTK is not thread-safe, the icons are retrieved one after the other on the calling thread.
+pub(crate) fn get_file_icons_parallel(
+    paths: &[&Path],
+    size: u16,
+    _threads: usize,
+    options: &Options,
+) -> Vec<Result<Icon, Error>> {
+    get_file_icons(paths, size, options)
+}
+
+pub(crate) fn get_file_icon_sizes(
+    path: &Path,
+    sizes: &[u16],
+    options: &Options,
+) -> Result<Vec<Icon>, Error> {
+    initialize_gtk()?;
+
+    sizes
+        .iter()
+        .map(|size| get_path_icon(path, *size, options))
+        .collect()
+}
+
+pub(crate) fn get_icon_for_extension(
+    extension: &str,
+    size: u16,
+    options: &Options,
+) -> Result<Icon, Error> {
+    get_icon_for_name(&format!("file.{extension}"), size, options)
+}
+
+pub(crate) fn get_icon_for_name(
+    file_name: &str,
+    size: u16,
+    options: &Options,
+) -> Result<Icon, Error> {
+    initialize_gtk()?;
+
+    // Only the file name is used to guess the content type, the file does not have to exist.
+    let (content_type, _) = gio::functions::content_type_guess(Some(file_name), None);
+
+    get_content_type_icon(&content_type, size, options)
+}
+
+pub(crate) fn get_icon_for_mime_type(
+    mime_type: &str,
+    size: u16,
+    options: &Options,
+) -> Result<Icon, Error> {
+    initialize_gtk()?;
+
+    let Some(content_type) = gio::functions::content_type_from_mime_type(mime_type) else {
+        return Err(Error::failed(format!("Unknown MIME type '{mime_type}'")));
+    };
+
+    get_content_type_icon(&content_type, size, options)
+}
+
+pub(crate) fn get_folder_icon(size: u16, options: &Options) -> Result<Icon, Error> {
+    initialize_gtk()?;
+    get_named_icon(&["folder"], size, options)
+}
+
+pub(crate) fn get_default_file_icon(size: u16, options: &Options) -> Result<Icon, Error> {
+    initialize_gtk()?;
+    get_named_icon(&["text-x-generic", "application-x-generic"], size, options)
+}
+
+pub(crate) fn get_known_folder_icon(
+    folder: KnownFolder,
+    size: u16,
+    options: &Options,
+) -> Result<Icon, Error> {
+    initialize_gtk()?;
+    get_named_icon(folder.icon_names(), size, options)
+}
+
+pub(crate) fn get_volume_icon(path: &Path, size: u16, options: &Options) -> Result<Icon, Error> {
+    use gio::prelude::{FileExt, MountExt, VolumeMonitorExt};
+
+    initialize_gtk()?;
+
+    // The mount containing the path is the one with the longest root.
+    let mount = gio::VolumeMonitor::get()
+        .mounts()
+        .into_iter()
+        .filter_map(|mount| Some((mount.root().path()?, mount)))
+        .filter(|(root, _)| path.starts_with(root))
+        .max_by_key(|(root, _)| root.components().count());
+
+    match mount {
+        Some((_, mount)) => get_gio_icon(&mount.icon(), size, options),
+        // The system partitions, like the root partition, are not listed by the volume monitor.
+        None => get_named_icon(&["drive-harddisk"], size, options),
+    }
+}
+
+pub(crate) fn get_application_icon(
+    executable: &Path,
+    size: u16,
+    options: &Options,
+) -> Result<Icon, Error> {
+    use gio::prelude::AppInfoExt;
+
+    initialize_gtk()?;
+
+    // Executables have no icon, the one of the desktop entry launching the program is used.
+    let icon = gio::AppInfo::all()
+        .into_iter()
+        .find(|app_info| app_info.executable().file_name() == executable.file_name())
+        .and_then(|app_info| app_info.icon());
+
+    match icon {
+        Some(icon) => get_gio_icon(&icon, size, options),
+        None => get_named_icon(&["application-x-executable"], size, options),
+    }
+}
+
+pub(crate) fn get_icon_from_resource(
+    _path: &Path,
+    _index: i32,
+    _size: u16,
+    _options: &Options,
+) -> Result<Icon, Error> {
+    Err(Error::Unsupported)
+}
+
+pub(crate) fn get_link_overlay(size: u16, options: &Options) -> Result<Icon, Error> {
+    initialize_gtk()?;
+
+    let emblem = get_named_icon(&["emblem-symbolic-link"], (size / 2).max(1), options)?;
+
+    Ok(emblem_overlay(&emblem, size))
+}
+
+pub(crate) fn get_file_type_info(
+    path: &Path,
+    size: u16,
+    options: &Options,
+) -> Result<FileTypeInfo, Error> {
+    // Content types are MIME types on Linux, and their descriptions do not need GTK.
+    let content_type = path_content_type(path, None)?;
+    let description = gio::functions::content_type_get_description(&content_type).to_string();
+    let icon = initialize_gtk()
+        .and_then(|()| get_content_type_icon(&content_type, size, options))
+        .inspect_err(|error| debug!("No icon for '{content_type}': {error}"))
+        .ok();
+
+    Ok(FileTypeInfo {
+        identifier: content_type,
+        description,
+        icon,
+    })
+}
+
+pub(crate) fn get_available_sizes(path: &Path, options: &Options) -> Result<Vec<u16>, Error> {
+    initialize_gtk()?;
+
+    let content_type = path_content_type(path, None)?;
+    let names = content_type_icon_names(&content_type)?;
+    let names: Vec<&str> = names.iter().map(String::as_str).collect();
+
+    if let Some(theme_name) = &options.theme {
+        use gtk::prelude::IconThemeExt;
+
+        let icon_theme = IconTheme::new();
+
+        icon_theme.set_custom_theme(Some(theme_name));
+
+        if let Some(sizes) = icon_sizes(&icon_theme, &names) {
+            return Ok(sizes);
         }
+    }
+
+    let Some(icon_theme) = IconTheme::default() else {
+        return Err(Error::failed("Failed to create icon theme"));
     };
+
+    icon_sizes(&icon_theme, &names)
+        .ok_or_else(|| Error::failed(format!("No icon named '{}'", names.join("', '"))))
+}
+
+pub(crate) fn get_icon_source(path: &Path) -> IconSource {
+    // The icons are always looked up in the icon theme from the content type.
+    match path_content_type(path, None) {
+        Ok(content_type) if !gio::functions::content_type_is_unknown(&content_type) => {
+            IconSource::TypeAssociation
+        }
+        _ => IconSource::Generic,
+    }
+}
+
+/// Result of the initialization of GTK, it is only attempted once.
+static GTK_INITIALIZATION: OnceLock<Result<(), gtk::glib::BoolError>> = OnceLock::new();
+
+/// Initializes GTK on the first call.
+///
+/// Fails instead of panicking when no display is available, with [`Error::Backend`],
+/// or when GTK was initialized on another thread.
+fn initialize_gtk() -> Result<(), Error> {
+    let wrong_thread = || Error::failed("GTK must be used from the thread it was initialized on");
+
+    // gtk::init() panics if GTK was already initialized on another thread.
+    if gtk::is_initialized() && !gtk::is_initialized_main_thread() {
+        return Err(wrong_thread());
+    }
+
+    GTK_INITIALIZATION
+        .get_or_init(gtk::init)
+        .as_ref()
+        .map_err(|error| Error::Backend(error.to_string()))?;
+
+    if !gtk::is_initialized_main_thread() {
+        return Err(wrong_thread());
+    }
+
+    Ok(())
+}
+
+fn get_path_icon(path: &Path, size: u16, options: &Options) -> Result<Icon, Error> {
+    use gio::{Cancellable, prelude::CancellableExt};
+
+    let cancellable = Cancellable::new();
+    // The query is interrupted if the token is cancelled from another thread.
+    let _guard = options.cancellation.as_ref().map(|token| {
+        let cancellable = cancellable.clone();
+
+        token.on_cancel(move || cancellable.cancel())
+    });
+    let content_type = path_content_type(path, Some(&cancellable))?;
+
+    get_content_type_icon(&content_type, size, options)
+}
+
+/// Returns the content type of a file, for example "text/plain".
+///
+/// Content types are resolved by gio and do not need GTK to be initialized.
+fn path_content_type(path: &Path, cancellable: Option<&gio::Cancellable>) -> Result<String, Error> {
+    use gio::{FileQueryInfoFlags, IOErrorEnum, prelude::FileExt};
+
+    let file = gio_file(path);
+    let file_info = file
+        .query_info(
+            "standard::content-type",
+            FileQueryInfoFlags::NONE,
+            cancellable,
+        )
+        .map_err(|error| match error.kind::<IOErrorEnum>() {
+            Some(IOErrorEnum::Cancelled) => Error::Cancelled,
+            _ => Error::failed(error),
+        })?;
     let Some(content_type) = file_info.content_type() else {
-        error!("Unable to get file content type");
-        return None;
+        return Err(Error::failed("Unable to get file content type"));
     };
-    let icon = gio::functions::content_type_get_icon(&content_type);
-    let Some(icon) = icon.dynamic_cast_ref::<gio::ThemedIcon>() else {
-        error!("Failed to cast icon into gio::ThemedIcon");
-        return None;
+
+    Ok(content_type.to_string())
+}
+
+/// Returns the file of gio at `path`, which can also be a URI like `smb://server/share/file.txt`.
+fn gio_file(path: &Path) -> gio::File {
+    match path.to_str() {
+        // Paths can contain "://" too, the ones of existing files are preferred.
+        Some(uri) if uri.contains("://") && !path.exists() => gio::File::for_uri(uri),
+        _ => gio::File::for_path(path),
+    }
+}
+
+fn get_content_type_icon(content_type: &str, size: u16, options: &Options) -> Result<Icon, Error> {
+    let icon = gio::functions::content_type_get_icon(content_type);
+
+    get_gio_icon(&icon, size, options)
+}
+
+/// Renders a `gio::Icon` according to its concrete type.
+fn get_gio_icon(icon: &gio::Icon, size: u16, options: &Options) -> Result<Icon, Error> {
+    use gio::prelude::{Cast, ObjectExt};
+
+    if let Some(icon) = icon.dynamic_cast_ref::<gio::ThemedIcon>() {
+        let names: Vec<String> = icon.names().iter().map(ToString::to_string).collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+
+        get_named_icon(&names, size, options)
+    } else if let Some(icon) = icon.dynamic_cast_ref::<gio::EmblemedIcon>() {
+        get_emblemed_icon(icon, size, options)
+    } else if let Some(icon) = icon.dynamic_cast_ref::<gio::FileIcon>() {
+        load_file_icon(&icon.file(), size)
+    } else {
+        Err(Error::failed(format!(
+            "Unsupported icon type {}",
+            icon.type_().name()
+        )))
+    }
+}
+
+/// Corners the emblems are drawn in, in order.
+const EMBLEM_CORNERS: [Corner; 4] = [
+    Corner::BottomRight,
+    Corner::BottomLeft,
+    Corner::TopRight,
+    Corner::TopLeft,
+];
+
+/// Renders the base icon with its emblems in its corners, like file managers.
+///
+/// The emblems which can't be rendered are skipped.
+fn get_emblemed_icon(
+    icon: &gio::EmblemedIcon,
+    size: u16,
+    options: &Options,
+) -> Result<Icon, Error> {
+    use gio::prelude::EmblemedIconExt;
+
+    let mut emblemed = get_gio_icon(&icon.icon(), size, options)?;
+
+    for (emblem, corner) in icon.emblems().iter().zip(EMBLEM_CORNERS) {
+        match get_gio_icon(&emblem.icon(), (size / 2).max(1), options) {
+            Ok(emblem) => emblemed = emblemed.overlay(&emblem, corner),
+            Err(error) => debug!("Skipping an emblem: {error}"),
+        }
+    }
+
+    Ok(emblemed)
+}
+
+/// Loads the image file of a `gio::FileIcon`.
+fn load_file_icon(file: &gio::File, size: u16) -> Result<Icon, Error> {
+    use gio::prelude::FileExt;
+
+    let Some(path) = file.path() else {
+        return Err(Error::failed(format!(
+            "The icon '{}' is not a local file",
+            file.uri()
+        )));
     };
+    let size = i32::from(size);
+    let pixbuf = gtk::gdk_pixbuf::Pixbuf::from_file_at_scale(path, size, size, true)
+        .map_err(Error::failed)?;
+
+    pixbuf_to_icon(&pixbuf).ok_or_else(|| Error::failed("Invalid icon file"))
+}
+
+/// Returns the names of the icons of a content type, from the most specific to the most generic.
+fn content_type_icon_names(content_type: &str) -> Result<Vec<String>, Error> {
+    let icon = gio::functions::content_type_get_icon(content_type);
+
+    themed_icon_names(&icon).ok_or_else(|| Error::failed("The icon is not a gio::ThemedIcon"))
+}
+
+/// Returns the names of a themed icon, or of the base icon of an emblemed icon.
+fn themed_icon_names(icon: &gio::Icon) -> Option<Vec<String>> {
+    use gio::prelude::{Cast, EmblemedIconExt};
+
+    if let Some(icon) = icon.dynamic_cast_ref::<gio::EmblemedIcon>() {
+        return themed_icon_names(&icon.icon());
+    }
+
+    let icon = icon.dynamic_cast_ref::<gio::ThemedIcon>()?;
+
+    Some(icon.names().iter().map(ToString::to_string).collect())
+}
+
+/// Sizes the icon themes are probed at by [`icon_sizes`], GTK 3 has no binding listing them.
+const PROBED_SIZES: [u16; 11] = [16, 22, 24, 32, 48, 64, 96, 128, 192, 256, 512];
+
+/// Returns the sizes of the first icon of `names` found in `icon_theme`, without the scalable ones.
+fn icon_sizes(icon_theme: &IconTheme, names: &[&str]) -> Option<Vec<u16>> {
+    use gtk::{IconLookupFlags, prelude::IconThemeExt};
+
+    let name = names.iter().find(|name| icon_theme.has_icon(name))?;
+    // The base size is the size of the file found for the probed size, 0 if it is scalable.
+    let mut sizes: Vec<u16> = PROBED_SIZES
+        .iter()
+        .filter_map(|size| icon_theme.lookup_icon(name, i32::from(*size), IconLookupFlags::empty()))
+        .filter_map(|icon_info| u16::try_from(icon_info.base_size()).ok())
+        .filter(|size| *size > 0)
+        .collect();
+
+    sizes.sort_unstable();
+    sizes.dedup();
+
+    Some(sizes)
+}
+
+/// Loads the first icon of `names` found in the icon theme.
+///
+/// The symbolic variants of the icons are tried first if requested by `options`.
+fn get_named_icon(names: &[&str], size: u16, options: &Options) -> Result<Icon, Error> {
+    options.check_cancelled()?;
+    symbolic::get_named_icon(names, size, options, get_themed_icon)
+}
+
+/// Loads the first icon of `names` found in the icon theme of `options`, or in the default one.
+fn get_themed_icon(names: &[&str], size: u16, options: &Options) -> Result<Icon, Error> {
+    if let Some(theme_name) = &options.theme {
+        use gtk::prelude::IconThemeExt;
+
+        let icon_theme = IconTheme::new();
+
+        icon_theme.set_custom_theme(Some(theme_name));
+
+        match load_icon(&icon_theme, names, size) {
+            Ok(icon) => return Ok(icon),
+            Err(error) => debug!("Using the default icon theme instead of '{theme_name}': {error}"),
+        }
+    }
+
     let Some(icon_theme) = IconTheme::default() else {
-        error!("Failed to create icon theme");
-        return None;
+        return Err(Error::failed("Failed to create icon theme"));
     };
 
-    for name in icon.names() {
-        if let Some(pixbuf) = icon_theme
-            .load_icon(&name, i32::from(size), IconLookupFlags::empty())
-            .ok()
-            .flatten()
-        && let Ok(width) = u32::try_from(pixbuf.width())
-        && let Ok(height) = u32::try_from(pixbuf.height())
-        {
-            return Some(Icon {
-                width,
-                height,
-                pixels: pixbuf.read_pixel_bytes().to_vec(),
-            });
+    load_icon(&icon_theme, names, size)
+}
+
+/// Loads the first icon of `names` found in `icon_theme`.
+fn load_icon(icon_theme: &IconTheme, names: &[&str], size: u16) -> Result<Icon, Error> {
+    use gtk::{IconLookupFlags, prelude::IconThemeExt};
+
+    let mut last_error = None;
+
+    for name in names {
+        match icon_theme.load_icon(name, i32::from(size), IconLookupFlags::empty()) {
+            Ok(Some(pixbuf)) => {
+                if let Some(icon) = pixbuf_to_icon(&pixbuf) {
+                    return Ok(icon);
+                }
+            }
+            Ok(None) => {}
+            Err(error) => last_error = Some(error),
         }
     }
 
-    None
+    Err(match last_error {
+        Some(error) => Error::failed(error),
+        None => Error::failed(format!("No icon named '{}'", names.join("', '"))),
+    })
 }
 
-pub(crate) struct Provider<T: Clone> {
-    icon_size: u16,
-    converter: fn(Icon) -> T,
-    icons_cache: RefCell<BTreeMap<String, T>>,
+fn pixbuf_to_icon(pixbuf: &gtk::gdk_pixbuf::Pixbuf) -> Option<Icon> {
+    // Thumbnails of opaque images can be stored without alpha channel.
+    let pixbuf = if pixbuf.has_alpha() {
+        pixbuf.clone()
+    } else {
+        pixbuf.add_alpha(false, 0, 0, 0).ok()?
+    };
+    let width = u32::try_from(pixbuf.width()).ok()?;
+    let height = u32::try_from(pixbuf.height()).ok()?;
+    let stride = usize::try_from(pixbuf.rowstride()).ok()?;
+    let mut pixels = pixbuf.read_pixel_bytes().to_vec();
+
+    // The rows can be padded, except the last one.
+    pixels.resize(stride * height as usize, 0);
+
+    Some(Icon {
+        width,
+        height,
+        pixels,
+        format: PixelFormat::Rgba8,
+        stride: (stride != width as usize * 4).then_some(stride),
+    })
 }
 
-impl<T: Clone> Provider<T> {
-    #[allow(clippy::unnecessary_wraps)]
-    pub fn new(icon_size: u16, converter: fn(Icon) -> T) -> Option<Self> {
-        Some(Self {
-            icon_size,
-            converter,
-            icons_cache: RefCell::new(BTreeMap::new()),
-        })
+/// Notifies the changes of the default icon theme, which GTK emits from its main loop.
+pub(crate) fn watch_theme() -> Result<(), Error> {
+    use gtk::prelude::IconThemeExt;
+
+    thread_local! {
+        /// GTK is only used from the main thread, this is set once for the process.
+        static WATCHING: Cell<bool> = const { Cell::new(false) };
+    }
+
+    initialize_gtk()?;
+
+    if WATCHING.get() {
+        return Ok(());
     }
 
-    pub fn get_file_icon(&self, path: impl AsRef<Path>) -> Option<T> {
-        let path = path.as_ref();
+    let Some(icon_theme) = IconTheme::default() else {
+        return Err(Error::failed("Failed to create icon theme"));
+    };
+
+    icon_theme.connect_changed(|_| notify_theme_changed());
+    WATCHING.set(true);
+
+    Ok(())
+}
+
+/// The handler of the icon theme only runs on its changes, it is kept for the whole process.
+pub(crate) fn unwatch_theme() {}
+
+/// Reloads the default icon theme if its directories changed.
+pub(crate) fn refresh_theme() {
+    use gtk::prelude::IconThemeExt;
+
+    if let Err(error) = initialize_gtk() {
+        debug!("Failed to refresh the icon theme: {error}");
+        return;
+    }
+
+    if let Some(icon_theme) = IconTheme::default() {
+        icon_theme.rescan_if_needed();
+    }
+}
+
+pub(crate) struct Provider;
 
+impl Provider {
+    #[allow(clippy::unnecessary_wraps)]
+    pub fn new(_icon_size: u16) -> Result<Self, Error> {
+        Ok(Self)
+    }
+
+    /// Returns the key used to cache the icon of `path`, or `None` if its icon must not be cached.
+    #[allow(clippy::unused_self)]
+    pub fn cache_key(&self, path: &Path) -> Option<String> {
         if path.is_dir()
             || path.is_symlink()
             || path
                 .metadata()
                 .is_ok_and(|metadata| metadata.permissions().mode() & 0o111 != 0)
         {
-            return get_file_icon(path, self.icon_size).map(self.converter);
+            return None;
         }
 
-        match path.extension().and_then(OsStr::to_str) {
-            Some("desktop") | None => get_file_icon(path, self.icon_size).map(self.converter),
-            Some(extension) => match self.icons_cache.borrow_mut().entry(extension.to_owned()) {
-                std::collections::btree_map::Entry::Vacant(vacant_entry) => Some(
-                    vacant_entry
-                        .insert(get_file_icon(path, self.icon_size).map(self.converter)?)
-                        .clone(),
-                ),
-                std::collections::btree_map::Entry::Occupied(occupied_entry) => {
-                    Some(occupied_entry.get().clone())
-                }
-            },
+        // Desktop entries have the icon of their application.
+        if path.extension() == Some(OsStr::new("desktop")) {
+            return None;
         }
+
+        // Files sharing an extension can have different content types, and so different icons.
+        path_content_type(path, None).ok()
+    }
+
+    #[allow(clippy::unused_self)]
+    pub fn get_file_icon(&self, path: &Path, size: u16, options: &Options) -> Result<Icon, Error> {
+        get_file_icon(path, options.scaled_size(size), options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{assert_matches, process::Command};
+
+    use crate::{Error, Options};
+
+    #[test]
+    fn test_themed_icon_names() {
+        use gio::prelude::Cast;
+
+        let themed = gio::ThemedIcon::new("text-x-generic");
+        let emblem = gio::Emblem::new(&gio::ThemedIcon::new("emblem-important"));
+        let emblemed = gio::EmblemedIcon::new(&themed, Some(&emblem));
+        let file_icon = gio::FileIcon::new(&gio::File::for_path("icon.png"));
+        let names = super::themed_icon_names(emblemed.upcast_ref()).expect("base icon names");
+
+        assert_eq!(names.first().map(String::as_str), Some("text-x-generic"));
+        assert!(super::themed_icon_names(file_icon.upcast_ref()).is_none());
+    }
+
+    /// Set in the process spawned by [`test_no_display`].
+    const NO_DISPLAY_VARIABLE: &str = "FILE_ICON_PROVIDER_TEST_NO_DISPLAY";
+
+    #[test]
+    fn test_no_display() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+
+        if std::env::var_os(NO_DISPLAY_VARIABLE).is_some() {
+            assert_matches!(
+                super::get_file_icon(file_path, 32, &Options::default()),
+                Err(Error::Backend(_))
+            );
+            return;
+        }
+
+        // GTK can only be initialized once per process, the test runs again in a process without display.
+        let status = Command::new(std::env::current_exe().expect("locate test executable"))
+            .args(["--exact", "implementation::linux::tests::test_no_display"])
+            .env(NO_DISPLAY_VARIABLE, "1")
+            .env_remove("DISPLAY")
+            .env_remove("WAYLAND_DISPLAY")
+            .status()
+            .expect("run test without display");
+
+        assert!(status.success());
     }
 }
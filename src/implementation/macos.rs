@@ -1,104 +1,470 @@
+use block2::{RcBlock, StackBlock};
 use objc2::{AnyThread, rc::Retained};
 use objc2_app_kit::{
-    NSBitmapImageRep, NSCompositingOperation, NSGraphicsContext, NSImage, NSWorkspace,
+    NSAppearance, NSAppearanceNameAqua, NSAppearanceNameDarkAqua, NSBitmapImageRep,
+    NSCompositingOperation, NSGraphicsContext, NSImage, NSWorkspace,
+};
+use objc2_foundation::{
+    NSBundle, NSDistributedNotificationCenter, NSError, NSFileManager, NSNotification, NSPoint,
+    NSRect, NSSize, NSString, NSURL, NSURLContentTypeKey, ns_string,
+};
+use objc2_quick_look_thumbnailing::{
+    QLThumbnailGenerationRequest, QLThumbnailGenerationRequestRepresentationTypes,
+    QLThumbnailGenerator, QLThumbnailRepresentation,
 };
-use objc2_foundation::{NSPoint, NSRect, NSSize, NSString};
 use objc2_uniform_type_identifiers::UTType;
 
-use crate::Icon;
+use crate::{
+    ColorScheme, Error, FileTypeInfo, Icon, IconSource, KnownFolder, Options, PixelFormat,
+    ResizeFilter, logging::debug, parallel::get_icons_in_chunks, theme::notify_theme_changed,
+};
 use std::{
-    cell::RefCell,
-    collections::{BTreeMap, btree_map},
+    ffi::{OsStr, c_char},
+    os::unix::ffi::OsStrExt,
     path::Path,
+    ptr::NonNull,
+    sync::{Mutex, Once, PoisonError, mpsc::channel},
 };
 
-use log::error;
+pub(crate) fn get_file_icon(
+    path: impl AsRef<Path>,
+    size: u16,
+    options: &Options,
+) -> Result<Icon, Error> {
+    let file_path = path_to_nsstring(path)?;
+    let image = NSWorkspace::sharedWorkspace().iconForFile(&file_path);
+
+    options.check_cancelled()?;
+    render_icon(&image, size, options)
+}
 
-pub(crate) fn get_file_icon(path: impl AsRef<Path>, size: u16) -> Option<Icon> {
+pub(crate) fn get_file_thumbnail(
+    path: impl AsRef<Path>,
+    size: u16,
+    options: &Options,
+) -> Result<Icon, Error> {
     let file_path = path_to_nsstring(path)?;
-    let shared_workspace = NSWorkspace::sharedWorkspace();
-    let image = shared_workspace.iconForFile(&file_path);
+    let url = NSURL::fileURLWithPath(&file_path);
+    let thumbnail_size = f64::from(size);
+    let request = unsafe {
+        QLThumbnailGenerationRequest::initWithFileAtURL_size_scale_representationTypes(
+            QLThumbnailGenerationRequest::alloc(),
+            &url,
+            NSSize::new(thumbnail_size, thumbnail_size),
+            1.0,
+            QLThumbnailGenerationRequestRepresentationTypes::Thumbnail,
+        )
+    };
+    let (sender, receiver) = channel();
+    // The handler is called on a background queue, the calling thread waits for it.
+    let completion_handler = RcBlock::new(
+        move |representation: *mut QLThumbnailRepresentation, error: *mut NSError| {
+            let reply = match unsafe { representation.as_ref() } {
+                Some(representation) => Ok(unsafe { representation.NSImage() }),
+                None => Err(unsafe { error.as_ref() }.map_or_else(
+                    || "No thumbnail".to_owned(),
+                    |error| error.localizedDescription().to_string(),
+                )),
+            };
+            let _ = sender.send(reply);
+        },
+    );
+
+    unsafe {
+        QLThumbnailGenerator::sharedGenerator()
+            .generateBestRepresentationForRequest_completionHandler(&request, &completion_handler);
+    }
+
+    let image = receiver
+        .recv()
+        .map_err(|_| Error::failed("The thumbnail generator did not reply"))?
+        .map_err(|message| {
+            debug!("QLThumbnailGenerator failed for '{file_path}': {message}");
+            Error::failed(message)
+        })?;
+
+    render_icon(&image, size, options)
+}
+
+pub(crate) fn get_file_icons(
+    paths: &[&Path],
+    size: u16,
+    options: &Options,
+) -> Vec<Result<Icon, Error>> {
+    // The provider draws every icon into the same bitmap.
+    let provider = match Provider::new(size) {
+        Ok(provider) => provider,
+        Err(error) => {
+            let message = error.to_string();
+
+            return paths
+                .iter()
+                .map(|_| Err(Error::failed(message.clone())))
+                .collect();
+        }
+    };
+
+    paths
+        .iter()
+        .map(|path| provider.get_file_icon(path, size, options))
+        .collect()
+}
+
+pub(crate) fn get_file_icons_parallel(
+    paths: &[&Path],
+    size: u16,
+    threads: usize,
+    options: &Options,
+) -> Vec<Result<Icon, Error>> {
+    // Each thread draws into the bitmap of its own provider, graphics contexts are per thread.
+    get_icons_in_chunks(paths, threads, |paths| get_file_icons(paths, size, options))
+}
+
+pub(crate) fn get_file_icon_sizes(
+    path: &Path,
+    sizes: &[u16],
+    options: &Options,
+) -> Result<Vec<Icon>, Error> {
+    let Some(largest) = sizes.iter().max() else {
+        return Ok(Vec::new());
+    };
+    // The icon is drawn once at the largest size, the smaller sizes are downscaled from it.
+    let icon = get_file_icon(path, *largest, options)?;
+
+    Ok(sizes
+        .iter()
+        .map(|size| {
+            let size = u32::from(*size);
+
+            icon.resize_with_filter(size, size, ResizeFilter::Triangle)
+        })
+        .collect())
+}
+
+pub(crate) fn get_icon_for_extension(
+    extension: &str,
+    size: u16,
+    options: &Options,
+) -> Result<Icon, Error> {
+    let Some(ut_type) = UTType::typeWithFilenameExtension(&NSString::from_str(extension)) else {
+        return Err(Error::failed(format!(
+            "No uniform type for extension '{extension}'"
+        )));
+    };
+    let image = NSWorkspace::sharedWorkspace().iconForContentType(&ut_type);
+
+    render_icon(&image, size, options)
+}
+
+pub(crate) fn get_icon_for_name(
+    file_name: &str,
+    size: u16,
+    options: &Options,
+) -> Result<Icon, Error> {
+    // Uniform types are only derived from extensions, names without extension are generic documents.
+    match Path::new(file_name).extension().and_then(OsStr::to_str) {
+        Some(extension) => get_icon_for_extension(extension, size, options),
+        None => get_default_file_icon(size, options),
+    }
+}
+
+pub(crate) fn get_icon_for_mime_type(
+    mime_type: &str,
+    size: u16,
+    options: &Options,
+) -> Result<Icon, Error> {
+    let Some(ut_type) = UTType::typeWithMIMEType(&NSString::from_str(mime_type)) else {
+        return Err(Error::failed(format!(
+            "No uniform type for MIME type '{mime_type}'"
+        )));
+    };
+    let image = NSWorkspace::sharedWorkspace().iconForContentType(&ut_type);
+
+    render_icon(&image, size, options)
+}
+
+pub(crate) fn get_folder_icon(size: u16, options: &Options) -> Result<Icon, Error> {
+    get_type_identifier_icon("public.folder", size, options)
+}
+
+pub(crate) fn get_default_file_icon(size: u16, options: &Options) -> Result<Icon, Error> {
+    get_type_identifier_icon("public.data", size, options)
+}
+
+pub(crate) fn get_volume_icon(path: &Path, size: u16, options: &Options) -> Result<Icon, Error> {
+    // The workspace gives the icon of the device for the root of a volume.
+    get_file_icon(path, size, options)
+}
+
+pub(crate) fn get_application_icon(
+    executable: &Path,
+    size: u16,
+    options: &Options,
+) -> Result<Icon, Error> {
+    // The executable of an application is in `Name.app/Contents/MacOS`, the bundle has the icon.
+    let bundle = executable.ancestors().skip(1).find(|path| {
+        path.extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("app"))
+    });
+
+    get_file_icon(bundle.unwrap_or(executable), size, options)
+}
+
+pub(crate) fn get_icon_from_resource(
+    _path: &Path,
+    _index: i32,
+    _size: u16,
+    _options: &Options,
+) -> Result<Icon, Error> {
+    Err(Error::Unsupported)
+}
+
+pub(crate) fn get_link_overlay(size: u16, options: &Options) -> Result<Icon, Error> {
+    // The badge Finder draws over the icons of aliases and symbolic links.
+    get_core_types_icon("AliasBadgeIcon", size, options)
+}
+
+pub(crate) fn get_known_folder_icon(
+    folder: KnownFolder,
+    size: u16,
+    options: &Options,
+) -> Result<Icon, Error> {
+    let name = match folder {
+        KnownFolder::Home => "HomeFolderIcon",
+        KnownFolder::Desktop => "DesktopFolderIcon",
+        KnownFolder::Documents => "DocumentsFolderIcon",
+        KnownFolder::Downloads => "DownloadsFolder",
+        KnownFolder::Music => "MusicFolderIcon",
+        KnownFolder::Pictures => "PicturesFolderIcon",
+        KnownFolder::Videos => "MovieFolderIcon",
+        KnownFolder::RecycleBin => "TrashIcon",
+        // Finder has no icon for the computer that does not depend on the model of the Mac.
+        KnownFolder::ThisPc => return Err(Error::Unsupported),
+    };
+
+    get_core_types_icon(name, size, options)
+}
+
+/// Renders one of the icons of the system in the resources of `CoreTypes.bundle`.
+fn get_core_types_icon(name: &str, size: u16, options: &Options) -> Result<Icon, Error> {
+    let path = format!("{CORE_TYPES_RESOURCES_PATH}/{name}.icns");
+    let Some(image) = NSImage::initWithContentsOfFile(NSImage::alloc(), &NSString::from_str(&path))
+    else {
+        return Err(Error::failed(format!("Failed to load the icon '{path}'")));
+    };
+
+    render_icon(&image, size, options)
+}
+
+const CORE_TYPES_RESOURCES_PATH: &str =
+    "/System/Library/CoreServices/CoreTypes.bundle/Contents/Resources";
+
+pub(crate) fn get_file_type_info(
+    path: &Path,
+    size: u16,
+    options: &Options,
+) -> Result<FileTypeInfo, Error> {
+    let file_path = path_to_nsstring(path)?;
+    let url = NSURL::fileURLWithPath(&file_path);
+    let mut content_type = None;
+
+    // SAFETY: The value of the content type key is an `UTType`.
+    let result =
+        unsafe { url.getResourceValue_forKey_error(&mut content_type, NSURLContentTypeKey) };
+
+    if let Err(error) = result {
+        let message = error.localizedDescription().to_string();
+
+        debug!("NSURL getResourceValue failed for '{file_path}': {message}");
+        return Err(Error::failed(message));
+    }
+
+    let Some(ut_type) =
+        content_type.and_then(|content_type| content_type.downcast::<UTType>().ok())
+    else {
+        return Err(Error::failed("Unable to get file content type"));
+    };
+    let identifier = ut_type.identifier().to_string();
+    let description = ut_type
+        .localizedDescription()
+        .map_or_else(|| identifier.clone(), |description| description.to_string());
+    let image = NSWorkspace::sharedWorkspace().iconForFile(&file_path);
+
+    Ok(FileTypeInfo {
+        identifier,
+        description,
+        icon: render_icon(&image, size, options).ok(),
+    })
+}
+
+pub(crate) fn get_available_sizes(path: &Path, _options: &Options) -> Result<Vec<u16>, Error> {
+    let file_path = path_to_nsstring(path)?;
+    let image = NSWorkspace::sharedWorkspace().iconForFile(&file_path);
+    // Vector representations, like PDF, have no pixel size.
+    let mut sizes: Vec<u16> = image
+        .representations()
+        .iter()
+        .filter_map(|representation| u16::try_from(representation.pixelsWide()).ok())
+        .filter(|size| *size > 0)
+        .collect();
+
+    sizes.sort_unstable();
+    sizes.dedup();
+
+    Ok(sizes)
+}
+
+pub(crate) fn get_icon_source(path: &Path) -> IconSource {
+    // Applications and other bundles have their own icon.
+    if path_to_nsstring(path)
+        .is_ok_and(|file_path| NSWorkspace::sharedWorkspace().isFilePackageAtPath(&file_path))
+    {
+        return IconSource::FileSpecific;
+    }
+
+    if path.is_dir() {
+        return IconSource::TypeAssociation;
+    }
+
+    let Some(extension) = path.extension().and_then(OsStr::to_str) else {
+        return IconSource::Generic;
+    };
+
+    // Unknown extensions get a dynamic type, which has the generic document icon.
+    match UTType::typeWithFilenameExtension(&NSString::from_str(extension)) {
+        Some(ut_type) if !ut_type.isDynamic() => IconSource::TypeAssociation,
+        _ => IconSource::Generic,
+    }
+}
+
+/// Renders the icon of a uniform type identifier, for example "public.folder".
+fn get_type_identifier_icon(identifier: &str, size: u16, options: &Options) -> Result<Icon, Error> {
+    let Some(ut_type) = UTType::typeWithIdentifier(&NSString::from_str(identifier)) else {
+        return Err(Error::failed(format!(
+            "No uniform type for identifier '{identifier}'"
+        )));
+    };
+    let image = NSWorkspace::sharedWorkspace().iconForContentType(&ut_type);
+
+    render_icon(&image, size, options)
+}
+
+fn render_icon(image: &NSImage, size: u16, options: &Options) -> Result<Icon, Error> {
     let bitmap_representation = create_bitmap_representation(size)?;
     let context = create_context(&bitmap_representation)?;
-    let size = u32::from(size);
 
-    Some(Icon {
-        width: size,
-        height: size,
-        pixels: get_pixels(&image, &context, &bitmap_representation, size)?,
-    })
+    draw_icon(
+        image,
+        &context,
+        &bitmap_representation,
+        u32::from(size),
+        options,
+    )
 }
 
-pub struct Provider<T: Clone> {
-    shared_workspace: Retained<NSWorkspace>,
-    bitmap_representation: Retained<NSBitmapImageRep>,
-    context: Option<Retained<NSGraphicsContext>>,
-    icon_size: u32,
-    cache: RefCell<BTreeMap<String, T>>,
-    converter: fn(Icon) -> T,
-}
-
-impl<T> Provider<T>
-where
-    T: Clone,
-{
-    pub fn new(icon_size: u16, converter: fn(Icon) -> T) -> Option<Self> {
-        let mut provider = Self {
-            shared_workspace: NSWorkspace::sharedWorkspace(),
-            bitmap_representation: create_bitmap_representation(icon_size)?,
-            context: None,
-            icon_size: u32::from(icon_size),
-            cache: RefCell::new(BTreeMap::new()),
-            converter,
+/// Notifies the changes of the appearance of the system, which change the icons of the
+/// providers using [`ColorScheme::System`].
+///
+/// The distributed notifications are delivered by the run loop of the main thread.
+#[allow(clippy::unnecessary_wraps)]
+pub(crate) fn watch_theme() -> Result<(), Error> {
+    static WATCHING: Once = Once::new();
+
+    WATCHING.call_once(|| {
+        let name = ns_string!("AppleInterfaceThemeChangedNotification");
+        let block = RcBlock::new(|_: NonNull<NSNotification>| notify_theme_changed());
+        let observer = unsafe {
+            NSDistributedNotificationCenter::defaultCenter()
+                .addObserverForName_object_queue_usingBlock(Some(name), None, None, &block)
         };
 
-        provider.context = create_context(&provider.bitmap_representation);
+        // The observer stays registered until the end of the process.
+        std::mem::forget(observer);
+    });
+
+    Ok(())
+}
+
+/// The observer only runs on the notifications, it is kept for the whole process.
+pub(crate) fn unwatch_theme() {}
+
+/// The icons are rendered by the workspace on each request, there is nothing to reload.
+pub(crate) fn refresh_theme() {}
+
+pub(crate) struct Provider {
+    canvas: Mutex<Canvas>,
+}
+
+/// The bitmap and the graphics context icons are drawn into.
+struct Canvas {
+    bitmap_representation: Retained<NSBitmapImageRep>,
+    context: Retained<NSGraphicsContext>,
+    size: u16,
+}
 
-        Some(provider)
+impl Canvas {
+    fn new(size: u16) -> Result<Self, Error> {
+        let bitmap_representation = create_bitmap_representation(size)?;
+        let context = create_context(&bitmap_representation)?;
+
+        Ok(Self {
+            bitmap_representation,
+            context,
+            size,
+        })
     }
+}
 
-    pub fn get_file_icon(&self, path: impl AsRef<Path>) -> Option<T> {
-        match Self::get_uttype_identifier(&path) {
-            Some(identifier) => match self.cache.borrow_mut().entry(identifier) {
-                btree_map::Entry::Vacant(vacant_entry) => {
-                    let icon = self.get_icon(path)?;
-
-                    Some(vacant_entry.insert(icon).clone())
-                }
-                btree_map::Entry::Occupied(occupied_entry) => Some(occupied_entry.get().clone()),
-            },
-            None => self.get_icon(path),
-        }
+// SAFETY: AppKit objects are not thread safe but drawing into a bitmap graphics context
+// is allowed from any thread as long as the context is not used concurrently.
+// The canvas is only accessed through the provider mutex which guarantees that.
+unsafe impl Send for Canvas {}
+
+impl Provider {
+    pub fn new(icon_size: u16) -> Result<Self, Error> {
+        Ok(Self {
+            canvas: Mutex::new(Canvas::new(icon_size)?),
+        })
     }
 
-    fn get_uttype_identifier(path: impl AsRef<Path>) -> Option<String> {
-        if path.as_ref().is_dir() {
-            return None;
+    /// Returns the key used to cache the icon of `path`, or `None` if its icon must not be cached.
+    #[allow(clippy::unused_self)]
+    pub fn cache_key(&self, path: &Path) -> Option<String> {
+        if path.is_dir() {
+            // Paths can't contain a null character so the key can't be a file type.
+            return bundle_identifier(path).map(|identifier| format!("\0bundle:{identifier}"));
         }
 
-        let extension = NSString::from_str(path.as_ref().extension()?.to_str()?);
+        let extension = NSString::from_str(path.extension()?.to_str()?);
         let ut_type = UTType::typeWithFilenameExtension(&extension)?;
 
         Some(ut_type.identifier().to_string())
     }
 
-    pub fn get_icon(&self, path: impl AsRef<Path>) -> Option<T> {
-        let path = path.as_ref();
-        let context = self.context.as_ref().unwrap();
+    pub fn get_file_icon(&self, path: &Path, size: u16, options: &Options) -> Result<Icon, Error> {
         let file_path = path_to_nsstring(path)?;
-        let image = self.shared_workspace.iconForFile(&file_path);
+        let image = NSWorkspace::sharedWorkspace().iconForFile(&file_path);
+        let size = options.scaled_size(size);
+        let mut canvas = self.canvas.lock().unwrap_or_else(PoisonError::into_inner);
 
-        Some((self.converter)(Icon {
-            width: self.icon_size,
-            height: self.icon_size,
-            pixels: get_pixels(&image, context, &self.bitmap_representation, self.icon_size)?,
-        }))
+        // The canvas is replaced when the size or the scale changes.
+        if canvas.size != size {
+            *canvas = Canvas::new(size)?;
+        }
+
+        draw_icon(
+            &image,
+            &canvas.context,
+            &canvas.bitmap_representation,
+            u32::from(size),
+            options,
+        )
     }
 }
 
-fn create_bitmap_representation(icon_size: u16) -> Option<Retained<NSBitmapImageRep>> {
+fn create_bitmap_representation(icon_size: u16) -> Result<Retained<NSBitmapImageRep>, Error> {
     let color_space_name = NSString::from_str("NSDeviceRGBColorSpace");
-    let icon_size = isize::try_from(icon_size).ok()?;
+    let icon_size = isize::try_from(icon_size).map_err(Error::failed)?;
     let bitmap_representation = unsafe { NSBitmapImageRep::initWithBitmapDataPlanes_pixelsWide_pixelsHigh_bitsPerSample_samplesPerPixel_hasAlpha_isPlanar_colorSpaceName_bytesPerRow_bitsPerPixel(
         NSBitmapImageRep::alloc(),
         std::ptr::null_mut(),
@@ -113,35 +479,31 @@ fn create_bitmap_representation(icon_size: u16) -> Option<Retained<NSBitmapImage
         32,
     ) };
 
-    if bitmap_representation.is_none() {
-        error!("Failed to create NSBitmapImageRep");
-    }
-
-    bitmap_representation
+    bitmap_representation.ok_or_else(|| {
+        debug!("NSBitmapImageRep could not be created for {icon_size}px icons");
+        Error::failed("Failed to create NSBitmapImageRep")
+    })
 }
 
 fn create_context(
     bitmap_representation: &Retained<NSBitmapImageRep>,
-) -> Option<Retained<NSGraphicsContext>> {
-    if let Some(context) = NSGraphicsContext::graphicsContextWithBitmapImageRep(bitmap_representation) {
-        Some(context)
-    } else {
-        error!("Failed to create graphics context");
-        None
-    }
+) -> Result<Retained<NSGraphicsContext>, Error> {
+    NSGraphicsContext::graphicsContextWithBitmapImageRep(bitmap_representation)
+        .ok_or_else(|| Error::failed("Failed to create graphics context"))
 }
 
-fn get_pixels(
+/// Draws `image` into the bitmap and returns its pixels, with the dimensions of the bitmap.
+fn draw_icon(
     image: &NSImage,
     context: &NSGraphicsContext,
     bitmap_representation: &NSBitmapImageRep,
     icon_size: u32,
-) -> Option<Vec<u8>> {
+    options: &Options,
+) -> Result<Icon, Error> {
     let image_size = image.size();
 
     if image_size.width < 1.0 || image_size.height < 1.0 {
-        error!("Invalid image size");
-        return None;
+        return Err(Error::failed("Invalid image size"));
     }
     let icon_size = f64::from(icon_size);
     let desired_size = NSSize {
@@ -149,46 +511,111 @@ fn get_pixels(
         height: icon_size,
     };
 
-    Some(unsafe {
+    let width = u32::try_from(bitmap_representation.pixelsWide()).map_err(Error::failed)?;
+    let height = u32::try_from(bitmap_representation.pixelsHigh()).map_err(Error::failed)?;
+    let bytes_per_row =
+        usize::try_from(bitmap_representation.bytesPerRow()).map_err(Error::failed)?;
+    let row_len = width as usize * 4;
+
+    // The requested stride is a hint, AppKit can pad the rows to align them.
+    if bytes_per_row < row_len {
+        debug!("NSBitmapImageRep has {bytes_per_row} bytes per row for {width}px wide icons");
+        return Err(Error::failed("Invalid bitmap stride"));
+    }
+
+    let pixels = unsafe {
         context.saveGraphicsState();
         NSGraphicsContext::setCurrentContext(Some(context));
         image.setSize(desired_size);
-        image.drawAtPoint_fromRect_operation_fraction(
-            NSPoint::ZERO,
-            NSRect::new(NSPoint::ZERO, desired_size),
-            NSCompositingOperation::Copy,
-            1.0,
-        );
+
+        let draw = || {
+            image.drawAtPoint_fromRect_operation_fraction(
+                NSPoint::ZERO,
+                NSRect::new(NSPoint::ZERO, desired_size),
+                NSCompositingOperation::Copy,
+                1.0,
+            );
+        };
+
+        // The drawing appearance selects the variant of the icon matching the color scheme.
+        match appearance(options.color_scheme) {
+            Some(appearance) => {
+                appearance.performAsCurrentDrawingAppearance(&StackBlock::new(draw));
+            }
+            None => draw(),
+        }
+
         context.flushGraphics();
         context.restoreGraphicsState();
 
-        let bytes_per_plane = usize::try_from(bitmap_representation.bytesPerPlane()).ok()?;
-
         std::slice::from_raw_parts(
             bitmap_representation.bitmapData(),
-            bytes_per_plane,
+            bytes_per_row * height as usize,
         )
-        .to_vec()
-    })
+    };
+    // The rows keep the padding AppKit adds to align them, the icon reports the real stride.
+    let mut icon = Icon {
+        width,
+        height,
+        pixels: pixels.to_vec(),
+        format: PixelFormat::Rgba8,
+        stride: (bytes_per_row != row_len).then_some(bytes_per_row),
+    };
+
+    // Bitmap graphics contexts only support premultiplied alpha.
+    if !options.premultiplied {
+        icon.unpremultiply_alpha();
+    }
+
+    Ok(icon)
 }
 
-fn path_to_nsstring(path: impl AsRef<Path>) -> Option<Retained<NSString>> {
-    let path = match path.as_ref().canonicalize() {
-        Ok(path) => path,
-        Err(error) => {
-            error!(
-                "Failed to canonicalize path '{}': {}",
-                path.as_ref().display(),
-                error
-            );
-            return None;
-        }
-    };
+/// Extensions of the bundles whose icon is cached, see [`bundle_identifier`].
+const BUNDLE_EXTENSIONS: [&str; 3] = ["app", "framework", "bundle"];
 
-    if let Some(path) = path.to_str() {
-        Some(NSString::from_str(path))
-    } else {
-        error!("Path '{}' is not valid unicode", path.display());
-        None
+/// Returns the identifier of an application, framework or plug-in bundle, like `com.apple.finder`.
+///
+/// The icons of these bundles are expensive to render, they are cached by identifier
+/// because every copy of a bundle has the same icon.
+fn bundle_identifier(path: &Path) -> Option<String> {
+    let extension = path.extension()?.to_str()?;
+
+    if !BUNDLE_EXTENSIONS
+        .iter()
+        .any(|bundle_extension| extension.eq_ignore_ascii_case(bundle_extension))
+    {
+        return None;
     }
+
+    let bundle = NSBundle::bundleWithPath(&path_to_nsstring(path).ok()?)?;
+
+    Some(bundle.bundleIdentifier()?.to_string())
+}
+
+/// Returns the appearance of a color scheme, or `None` to keep the appearance of the system.
+fn appearance(color_scheme: ColorScheme) -> Option<Retained<NSAppearance>> {
+    let name = match color_scheme {
+        ColorScheme::System => return None,
+        ColorScheme::Light => unsafe { NSAppearanceNameAqua },
+        ColorScheme::Dark => unsafe { NSAppearanceNameDarkAqua },
+    };
+
+    NSAppearance::appearanceNamed(name)
+}
+
+/// Converts a path to the string given to `AppKit`.
+///
+/// The string is created from the file system representation of the path, so paths which are
+/// not valid unicode are supported.
+fn path_to_nsstring(path: impl AsRef<Path>) -> Result<Retained<NSString>, Error> {
+    let path = path.as_ref().canonicalize().map_err(Error::failed)?;
+    let bytes = path.as_os_str().as_bytes();
+
+    // The bytes don't need to be null terminated because the length is given.
+    Ok(unsafe {
+        NSFileManager::defaultManager().stringWithFileSystemRepresentation_length(
+            NonNull::from(bytes).cast::<c_char>(),
+            bytes.len(),
+        )
+    })
 }
@@ -0,0 +1,301 @@
+//! Backend returning solid color icons without calling the platform, selected with the `stub` feature.
+//!
+//! The color of an icon is derived from the extension of the file, so tests can tell file types apart.
+//! Folders and files without extension have their own colors.
+
+use std::{ffi::OsStr, path::Path};
+
+use crate::{
+    Error, FileTypeInfo, Icon, IconSource, KnownFolder, Options, PixelFormat,
+    alpha::emblem_overlay, parallel::get_icons_in_chunks,
+};
+
+/// Key of the color of the folders, it can't be an extension.
+const FOLDER_KEY: &str = "/";
+
+/// Key of the color of the volumes, it can't be an extension.
+const VOLUME_KEY: &str = "/volume";
+
+/// Key of the color of the symbolic link emblem, it can't be an extension.
+const LINK_KEY: &str = "/link";
+
+pub(crate) fn get_file_icon(
+    path: impl AsRef<Path>,
+    size: u16,
+    _options: &Options,
+) -> Result<Icon, Error> {
+    let path = path.as_ref();
+
+    if !path.exists() {
+        return Err(Error::PathDoesNotExist);
+    }
+
+    Ok(solid_icon(&path_key(path), size))
+}
+
+pub(crate) fn get_file_thumbnail(
+    _path: impl AsRef<Path>,
+    _size: u16,
+    _options: &Options,
+) -> Result<Icon, Error> {
+    Err(Error::Unsupported)
+}
+
+pub(crate) fn get_file_icons(
+    paths: &[&Path],
+    size: u16,
+    options: &Options,
+) -> Vec<Result<Icon, Error>> {
+    paths
+        .iter()
+        .map(|path| get_file_icon(path, size, options))
+        .collect()
+}
+
+pub(crate) fn get_file_icons_parallel(
+    paths: &[&Path],
+    size: u16,
+    threads: usize,
+    options: &Options,
+) -> Vec<Result<Icon, Error>> {
+    get_icons_in_chunks(paths, threads, |paths| get_file_icons(paths, size, options))
+}
+
+pub(crate) fn get_file_icon_sizes(
+    path: &Path,
+    sizes: &[u16],
+    options: &Options,
+) -> Result<Vec<Icon>, Error> {
+    sizes
+        .iter()
+        .map(|size| get_file_icon(path, *size, options))
+        .collect()
+}
+
+#[cfg(target_os = "windows")]
+pub(crate) fn get_file_icon_sta(
+    path: impl AsRef<Path>,
+    size: u16,
+    options: &Options,
+) -> Result<Icon, Error> {
+    get_file_icon(path, size, options)
+}
+
+#[cfg(target_os = "windows")]
+pub(crate) fn shutdown_icon_worker() {}
+
+#[allow(clippy::unnecessary_wraps)]
+pub(crate) fn get_icon_for_extension(
+    extension: &str,
+    size: u16,
+    _options: &Options,
+) -> Result<Icon, Error> {
+    Ok(solid_icon(&extension.to_lowercase(), size))
+}
+
+pub(crate) fn get_icon_for_name(
+    file_name: &str,
+    size: u16,
+    options: &Options,
+) -> Result<Icon, Error> {
+    match Path::new(file_name).extension().and_then(OsStr::to_str) {
+        Some(extension) => get_icon_for_extension(extension, size, options),
+        None => get_default_file_icon(size, options),
+    }
+}
+
+pub(crate) fn get_icon_for_mime_type(
+    mime_type: &str,
+    size: u16,
+    _options: &Options,
+) -> Result<Icon, Error> {
+    // There is no MIME database, the color is derived from the MIME type itself.
+    match mime_type.split_once('/') {
+        Some((media, subtype))
+            if !media.is_empty() && !subtype.is_empty() && !mime_type.contains(' ') =>
+        {
+            Ok(solid_icon(&mime_type.to_lowercase(), size))
+        }
+        _ => Err(Error::failed(format!("Unknown MIME type '{mime_type}'"))),
+    }
+}
+
+#[allow(clippy::unnecessary_wraps)]
+pub(crate) fn get_folder_icon(size: u16, _options: &Options) -> Result<Icon, Error> {
+    Ok(solid_icon(FOLDER_KEY, size))
+}
+
+#[allow(clippy::unnecessary_wraps)]
+pub(crate) fn get_default_file_icon(size: u16, _options: &Options) -> Result<Icon, Error> {
+    Ok(solid_icon("", size))
+}
+
+/// Each folder has its own color, its key starts with `/` so it can't be an extension.
+#[allow(clippy::unnecessary_wraps)]
+pub(crate) fn get_known_folder_icon(
+    folder: KnownFolder,
+    size: u16,
+    _options: &Options,
+) -> Result<Icon, Error> {
+    Ok(solid_icon(&format!("/{folder:?}"), size))
+}
+
+#[allow(clippy::unnecessary_wraps)]
+pub(crate) fn get_volume_icon(_path: &Path, size: u16, _options: &Options) -> Result<Icon, Error> {
+    Ok(solid_icon(VOLUME_KEY, size))
+}
+
+pub(crate) fn get_application_icon(
+    executable: &Path,
+    size: u16,
+    options: &Options,
+) -> Result<Icon, Error> {
+    get_file_icon(executable, size, options)
+}
+
+pub(crate) fn get_icon_from_resource(
+    _path: &Path,
+    _index: i32,
+    _size: u16,
+    _options: &Options,
+) -> Result<Icon, Error> {
+    Err(Error::Unsupported)
+}
+
+#[allow(clippy::unnecessary_wraps)]
+pub(crate) fn get_link_overlay(size: u16, _options: &Options) -> Result<Icon, Error> {
+    Ok(emblem_overlay(
+        &solid_icon(LINK_KEY, (size / 2).max(1)),
+        size,
+    ))
+}
+
+/// The identifier of the type is the key of the color of its icon.
+#[allow(clippy::unnecessary_wraps)]
+pub(crate) fn get_file_type_info(
+    path: &Path,
+    size: u16,
+    options: &Options,
+) -> Result<FileTypeInfo, Error> {
+    let identifier = path_key(path);
+    let description = match identifier.as_str() {
+        FOLDER_KEY => "Folder".to_owned(),
+        "" => "File".to_owned(),
+        extension => format!("{} file", extension.to_uppercase()),
+    };
+
+    Ok(FileTypeInfo {
+        identifier,
+        description,
+        icon: get_file_icon(path, size, options).ok(),
+    })
+}
+
+/// The icons are solid colors, they are native at every size so none is listed.
+#[allow(clippy::unnecessary_wraps)]
+pub(crate) fn get_available_sizes(_path: &Path, _options: &Options) -> Result<Vec<u16>, Error> {
+    Ok(Vec::new())
+}
+
+pub(crate) fn get_icon_source(path: &Path) -> IconSource {
+    if path_key(path).is_empty() {
+        IconSource::Generic
+    } else {
+        IconSource::TypeAssociation
+    }
+}
+
+/// Returns the key the color of the icon of `path` is derived from.
+fn path_key(path: &Path) -> String {
+    if path.is_dir() {
+        return FOLDER_KEY.to_owned();
+    }
+
+    path.extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+fn solid_icon(key: &str, size: u16) -> Icon {
+    let size = u32::from(size);
+    let pixel_count = usize::try_from(size * size).unwrap_or_default();
+
+    Icon {
+        width: size,
+        height: size,
+        pixels: color(key).repeat(pixel_count),
+        format: PixelFormat::Rgba8,
+        stride: None,
+    }
+}
+
+/// Returns an opaque color derived from the FNV-1a hash of `key`, it is the same on every platform.
+fn color(key: &str) -> [u8; 4] {
+    let hash = key.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    });
+    let [red, green, blue, ..] = hash.to_le_bytes();
+
+    [red, green, blue, u8::MAX]
+}
+
+/// The stub icons never change.
+#[allow(clippy::unnecessary_wraps)]
+pub(crate) fn watch_theme() -> Result<(), Error> {
+    Ok(())
+}
+
+pub(crate) fn unwatch_theme() {}
+
+pub(crate) fn refresh_theme() {}
+
+pub(crate) struct Provider;
+
+impl Provider {
+    #[allow(clippy::unnecessary_wraps)]
+    pub fn new(_icon_size: u16) -> Result<Self, Error> {
+        Ok(Self)
+    }
+
+    /// Returns the key used to cache the icon of `path`, or `None` if its icon must not be cached.
+    #[allow(clippy::unused_self, clippy::unnecessary_wraps)]
+    pub fn cache_key(&self, path: &Path) -> Option<String> {
+        Some(path_key(path))
+    }
+
+    #[allow(clippy::unused_self)]
+    pub fn get_file_icon(&self, path: &Path, size: u16, options: &Options) -> Result<Icon, Error> {
+        get_file_icon(path, options.scaled_size(size), options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Options;
+
+    use super::{FOLDER_KEY, color, get_icon_for_extension, get_icon_for_name};
+
+    #[test]
+    fn test_color_is_deterministic() {
+        assert_eq!(color("txt"), color("txt"));
+        assert_eq!(color(""), [0x25, 0x23, 0x22, 0xff]);
+        assert_ne!(color("txt"), color("png"));
+        assert_ne!(color("txt"), color(FOLDER_KEY));
+    }
+
+    #[test]
+    fn test_icons_are_solid() {
+        let icon = get_icon_for_extension("TXT", 4, &Options::default()).expect("get icon");
+
+        assert_eq!((icon.width, icon.height), (4, 4));
+        assert!(
+            icon.pixels
+                .chunks_exact(4)
+                .all(|pixel| pixel == color("txt"))
+        );
+        assert_eq!(
+            icon,
+            get_icon_for_name("notes.txt", 4, &Options::default()).expect("get icon")
+        );
+    }
+}
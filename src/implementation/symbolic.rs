@@ -0,0 +1,95 @@
+//! Symbolic icons, shared by the Linux backends.
+
+use crate::{Error, Icon, Options, logging::debug};
+
+/// Loads the first icon of `names` with `load`.
+///
+/// The symbolic variants of the icons are tried first if requested by `options`,
+/// and painted with its foreground color. If none is found, the full-color icon is loaded.
+pub(crate) fn get_named_icon(
+    names: &[&str],
+    size: u16,
+    options: &Options,
+    load: impl Fn(&[&str], u16, &Options) -> Result<Icon, Error>,
+) -> Result<Icon, Error> {
+    if let Some(foreground) = options.symbolic {
+        let symbolic_names = symbolic_names(names);
+        let symbolic_names: Vec<&str> = symbolic_names.iter().map(String::as_str).collect();
+
+        match load(&symbolic_names, size, options) {
+            Ok(mut icon) => {
+                icon.recolor(foreground);
+                return Ok(icon);
+            }
+            Err(Error::Cancelled) => return Err(Error::Cancelled),
+            Err(error) => debug!("Using the full-color icon instead of the symbolic one: {error}"),
+        }
+    }
+
+    load(names, size, options)
+}
+
+/// Returns the names of the symbolic variants of the icons `names`, like "folder-symbolic".
+fn symbolic_names(names: &[&str]) -> Vec<String> {
+    names
+        .iter()
+        .map(|name| {
+            if name.ends_with("-symbolic") {
+                (*name).to_owned()
+            } else {
+                format!("{name}-symbolic")
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::assert_matches;
+
+    use crate::{Error, Icon, Options, PixelFormat};
+
+    use super::{get_named_icon, symbolic_names};
+
+    fn load(names: &[&str], _size: u16, _options: &Options) -> Result<Icon, Error> {
+        match names {
+            ["folder-symbolic" | "text-x-generic"] => Ok(Icon {
+                width: 2,
+                height: 1,
+                pixels: vec![10, 20, 30, 255, 40, 50, 60, 0],
+                format: PixelFormat::Rgba8,
+                stride: None,
+            }),
+            ["cancelled-symbolic"] => Err(Error::Cancelled),
+            _ => Err(Error::failed("No icon")),
+        }
+    }
+
+    #[test]
+    fn test_symbolic_names() {
+        assert_eq!(
+            symbolic_names(&["text-x-generic", "folder-symbolic"]),
+            ["text-x-generic-symbolic", "folder-symbolic"]
+        );
+    }
+
+    #[test]
+    fn test_get_named_icon() {
+        let options = Options {
+            symbolic: Some([255, 0, 0, 255]),
+            ..Options::default()
+        };
+        let symbolic = get_named_icon(&["folder"], 16, &options, load).expect("symbolic icon");
+        let fallback = get_named_icon(&["text-x-generic"], 16, &options, load).expect("icon");
+        let full_color =
+            get_named_icon(&["folder-symbolic"], 16, &Options::default(), load).expect("icon");
+
+        assert_eq!(symbolic.pixels, [255, 0, 0, 255, 255, 0, 0, 0]);
+        assert_eq!(fallback.pixels, [10, 20, 30, 255, 40, 50, 60, 0]);
+        assert_eq!(full_color.pixels, [10, 20, 30, 255, 40, 50, 60, 0]);
+        assert_matches!(
+            get_named_icon(&["cancelled"], 16, &options, load),
+            Err(Error::Cancelled)
+        );
+    }
+}
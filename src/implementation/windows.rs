@@ -1,114 +1,497 @@
 use std::{
-    cell::RefCell,
-    collections::BTreeMap,
-    ffi::{OsStr, c_void},
-    path::Path,
+    ffi::{OsStr, OsString, c_void},
+    os::windows::ffi::OsStrExt,
+    path::{Component, Path, PathBuf, Prefix},
     sync::{
-        LazyLock,
-        mpsc::{Sender, channel},
+        Arc, Mutex, PoisonError, Weak,
+        mpsc::{Receiver, RecvTimeoutError, Sender, channel},
     },
+    thread::JoinHandle,
+    time::{Duration, Instant},
 };
 
 use scopeguard::defer;
 use windows::{
     Win32::{
-        Foundation::SIZE,
+        Foundation::{
+            ERROR_CLASS_ALREADY_EXISTS, HINSTANCE, HWND, LPARAM, LRESULT, MAX_PATH, SIZE, WPARAM,
+        },
         Graphics::Gdi::{
             BI_RGB, BITMAP, BITMAPINFO, BITMAPINFOHEADER, CreateCompatibleDC, DIB_RGB_COLORS, DeleteDC, DeleteObject, GetDIBits, GetObjectW, HBITMAP, HDC
         },
-        System::Com::{CoInitialize, CoUninitialize},
-        UI::Shell::{
-            IShellItemImageFactory, SHCreateItemFromParsingName, SIIGBF_ICONONLY, SIIGBF_SCALEUP,
+        Storage::FileSystem::{
+            FILE_ATTRIBUTE_DIRECTORY, FILE_ATTRIBUTE_NORMAL, FILE_FLAGS_AND_ATTRIBUTES,
+        },
+        System::{
+            Com::{
+                APTTYPE, APTTYPE_MAINSTA, APTTYPE_STA, APTTYPEQUALIFIER, CoGetApartmentType,
+                CoInitialize, CoUninitialize,
+            },
+            LibraryLoader::GetModuleHandleW,
+            Registry::{HKEY_CLASSES_ROOT, RRF_RT_ANY, RRF_RT_REG_SZ, RegGetValueW},
+        },
+        UI::{
+            Controls::{IImageList, ILD_TRANSPARENT},
+            HiDpi::{DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2, SetThreadDpiAwarenessContext},
+            Shell::{
+                ASSOCF_INIT_IGNOREUNKNOWN, ASSOCSTR_FRIENDLYDOCNAME, AssocQueryStringW,
+                FOLDERID_ComputerFolder, FOLDERID_Desktop, FOLDERID_Documents, FOLDERID_Downloads,
+                FOLDERID_Music, FOLDERID_Pictures, FOLDERID_Profile, FOLDERID_RecycleBinFolder,
+                FOLDERID_Videos, IDO_SHGIOI_LINK, ILFree, IShellItemImageFactory, KF_FLAG_DEFAULT,
+                SHCreateItemFromIDList, SHCreateItemFromParsingName, SHFILEINFOW,
+                SHGFI_SYSICONINDEX, SHGFI_USEFILEATTRIBUTES, SHGetFileInfoW,
+                SHGetIconOverlayIndexW, SHGetImageList, SHGetKnownFolderIDList, SHIL_EXTRALARGE,
+                SHIL_JUMBO, SHIL_LARGE, SHIL_SMALL, SIIGBF, SIIGBF_ICONONLY, SIIGBF_RESIZETOFIT,
+                SIIGBF_SCALEUP, SIIGBF_THUMBNAILONLY,
+            },
+            WindowsAndMessaging::{
+                CreateWindowExW, DefWindowProcW, DestroyIcon, DispatchMessageW, GetIconInfo,
+                GetMessageW, HICON, ICONINFO, MSG, PostMessageW, PostQuitMessage,
+                PrivateExtractIconsW, RegisterClassW, WINDOW_EX_STYLE, WINDOW_STYLE, WM_CLOSE,
+                WM_DESTROY, WM_SETTINGCHANGE, WM_THEMECHANGED, WNDCLASSW,
+            },
         },
     },
-    core::HSTRING,
+    core::{GUID, HSTRING, PCWSTR, PWSTR, w},
 };
 
-use crate::Icon;
-
-use log::{debug, error};
+use crate::{
+    CancellationToken, Error, FileTypeInfo, Icon, IconSource, KnownFolder, Options, PixelFormat,
+    ResizeFilter, ScaleMode,
+    logging::{debug, error},
+    parallel::get_icons_in_chunks,
+    theme::notify_theme_changed,
+};
 
+#[allow(clippy::enum_variant_names)]
 enum ImageFactoryRequest {
     RequestImage {
         path: HSTRING,
         size: u16,
-        reply: Sender<ImageFactoryReply>,
+        /// The request is skipped if it is cancelled before the factory thread handles it.
+        cancellation: Option<CancellationToken>,
+        dpi_aware: bool,
+        scale_mode: ScaleMode,
+        reply: Reply,
+    },
+    /// Requests the thumbnail of a file, it fails if the file has no thumbnail.
+    RequestThumbnail {
+        path: HSTRING,
+        size: u16,
+        reply: Reply,
+    },
+    /// Requests the icon of a file type, the file does not have to exist.
+    RequestTypeImage {
+        name: HSTRING,
+        attributes: FILE_FLAGS_AND_ATTRIBUTES,
+        size: u16,
+        reply: Reply,
+    },
+    /// Requests the overlay of the shortcuts and symbolic links.
+    RequestLinkOverlay { size: u16, reply: Reply },
+    /// Requests the icon of a known folder, which may be a virtual item of the shell.
+    RequestKnownFolderImage {
+        folder: GUID,
+        size: u16,
+        reply: Reply,
     },
 }
 
-enum ImageFactoryReply {
-    Success(Icon),
-    Failure,
+impl ImageFactoryRequest {
+    fn reply(&self) -> &Reply {
+        match self {
+            ImageFactoryRequest::RequestImage { reply, .. }
+            | ImageFactoryRequest::RequestThumbnail { reply, .. }
+            | ImageFactoryRequest::RequestTypeImage { reply, .. }
+            | ImageFactoryRequest::RequestLinkOverlay { reply, .. }
+            | ImageFactoryRequest::RequestKnownFolderImage { reply, .. } => reply,
+        }
+    }
 }
 
-static IMAGE_FACTORY_REQUEST_SENDER: LazyLock<Sender<ImageFactoryRequest>> =
-    LazyLock::new(start_image_factory_thread);
+type ImageFactoryReply = Result<Icon, Error>;
 
-fn start_image_factory_thread() -> Sender<ImageFactoryRequest> {
-    let (sender, receiver) = channel();
+/// Channel answering a request of the image factory thread.
+#[derive(Clone)]
+struct Reply {
+    sender: Sender<ImageFactoryReply>,
+    /// Dropped by the caller when it stops waiting, after a timeout.
+    waiting: Weak<()>,
+}
+
+impl Reply {
+    /// Creates a reply and the receiver of the caller.
+    ///
+    /// The requests answered by this reply become stale once the returned `Arc` is dropped.
+    fn channel() -> (Self, Receiver<ImageFactoryReply>, Arc<()>) {
+        let (sender, receiver) = channel();
+        let waiting = Arc::new(());
+        let reply = Self {
+            sender,
+            waiting: Arc::downgrade(&waiting),
+        };
+
+        (reply, receiver, waiting)
+    }
+
+    /// Returns `true` if nobody waits for the reply anymore, the request can be skipped.
+    fn is_stale(&self) -> bool {
+        self.waiting.strong_count() == 0
+    }
+
+    fn send(&self, reply: ImageFactoryReply) {
+        let _ = self.sender.send(reply);
+    }
+}
+
+struct ImageFactoryThread {
+    sender: Sender<ImageFactoryRequest>,
+    /// Disconnected once the thread stopped, even if it panicked.
+    stopped: Receiver<()>,
+    handle: JoinHandle<()>,
+}
+
+/// How long [`shutdown_icon_worker`] waits for the image factory thread before detaching it.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The image factory thread, started by the first request after the start or a shutdown.
+static IMAGE_FACTORY_THREAD: Mutex<Option<ImageFactoryThread>> = Mutex::new(None);
+
+/// Sends a request to the image factory thread, starting it if needed.
+fn send_request(request: ImageFactoryRequest) -> Result<(), Error> {
+    let mut thread = IMAGE_FACTORY_THREAD
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+
+    if thread
+        .get_or_insert_with(start_image_factory_thread)
+        .sender
+        .send(request)
+        .is_err()
+    {
+        // The thread stopped unexpectedly, a new one is started by the next request.
+        *thread = None;
+
+        return Err(Error::failed("The image factory thread stopped"));
+    }
+
+    Ok(())
+}
+
+/// Stops the image factory thread and waits for it to answer the pending requests.
+///
+/// The thread is detached if it did not stop after [`SHUTDOWN_TIMEOUT`], for example if the shell hangs.
+/// COM is initialized for each request, so nothing is left initialized once the thread stopped.
+pub(crate) fn shutdown_icon_worker() {
+    let thread = IMAGE_FACTORY_THREAD
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take();
+
+    if let Some(ImageFactoryThread {
+        sender,
+        stopped,
+        handle,
+    }) = thread
+    {
+        // The thread stops once the channel is closed and empty.
+        drop(sender);
+
+        if let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(SHUTDOWN_TIMEOUT) {
+            error!("The image factory thread did not stop, it is detached");
+            return;
+        }
+
+        if handle.join().is_err() {
+            error!("The image factory thread panicked");
+        }
+    }
+}
+
+fn start_image_factory_thread() -> ImageFactoryThread {
+    let (sender, receiver) = channel::<ImageFactoryRequest>();
+    let (stopping, stopped) = channel();
+    let handle = std::thread::spawn(move || {
+        // Dropped when the thread stops, even by a panic.
+        let _stopping = stopping;
 
-    std::thread::spawn(move || {
         debug!("Start Image Factory thread");
-        for request in receiver.iter() {
+        for request in &receiver {
+            // The caller stopped waiting for this request, the shell is not called.
+            if request.reply().is_stale() {
+                debug!("Skip stale request");
+                continue;
+            }
+
+            // Every request must be answered, otherwise the caller waits until the timeout.
             match request {
-                ImageFactoryRequest::RequestImage { path, size, reply } => {
-                    if let Err(error) = unsafe { CoInitialize(None).ok() } {
-                        error!("Failed to initialize COM: {error}");
-                        let _ = reply.send(ImageFactoryReply::Failure);
-                        continue;
-                    }
-
-                    defer!(unsafe { CoUninitialize() });
-
-                    let factory: Result<IShellItemImageFactory, _> =
-                        unsafe { SHCreateItemFromParsingName(&path, None) };
-                    match factory {
-                        Ok(factory) => {
-                            let hbitmap = unsafe {
-                                let image_size = i32::from(size);
-                                factory.GetImage(
-                                    SIZE {
-                                        cx: image_size,
-                                        cy: image_size,
-                                    },
-                                    SIIGBF_ICONONLY | SIIGBF_SCALEUP,
-                                )
-                            };
-
-                            match hbitmap {
-                                Ok(hbitmap) => {
-                                    match get_hbitmap_pixels(hbitmap) {
-                                        Some(pixels) => {
-                                            let size = u32::from(size);
-                                            let _ = reply.send(ImageFactoryReply::Success(Icon {
-                                                width: size,
-                                                height: size,
-                                                pixels,
-                                            }));
-                                        }
-                                        None => {
-                                            let _ = reply.send(ImageFactoryReply::Failure);
-                                        }
-                                    }                                   
-                                }
-                                Err(error) => {
-                                    error!("Failed to get image from factory: {error}");
-                                    let _ = reply.send(ImageFactoryReply::Failure);
-                                }
-                            }
-                        }
-                        Err(error) => {
-                            error!("Failed to create IShellItemImageFactory: {error}");
-                            let _ = reply.send(ImageFactoryReply::Failure);
-                        }
-                    }
+                ImageFactoryRequest::RequestImage {
+                    path,
+                    size,
+                    cancellation,
+                    dpi_aware,
+                    scale_mode,
+                    reply,
+                } => {
+                    reply.send(match cancellation {
+                        Some(token) if token.is_cancelled() => Err(Error::Cancelled),
+                        _ => with_com(|| {
+                            with_dpi_awareness(dpi_aware, || get_image(&path, size, scale_mode))
+                        }),
+                    });
+                }
+                ImageFactoryRequest::RequestThumbnail { path, size, reply } => {
+                    reply.send(with_com(|| {
+                        get_factory_image(&path, size, SIIGBF_THUMBNAILONLY | SIIGBF_SCALEUP)
+                    }));
+                }
+                ImageFactoryRequest::RequestTypeImage {
+                    name,
+                    attributes,
+                    size,
+                    reply,
+                } => {
+                    reply.send(with_com(|| get_type_image(&name, attributes, size)));
+                }
+                ImageFactoryRequest::RequestLinkOverlay { size, reply } => {
+                    reply.send(with_com(|| get_link_overlay_image(size)));
+                }
+                ImageFactoryRequest::RequestKnownFolderImage {
+                    folder,
+                    size,
+                    reply,
+                } => {
+                    reply.send(with_com(|| get_known_folder_image(&folder, size)));
                 }
             }
         }
         debug!("Image Factory thread stopped");
     });
 
-    sender
+    ImageFactoryThread {
+        sender,
+        stopped,
+        handle,
+    }
+}
+
+/// Returns a function logging the failure of `function` of the Windows API and converting its error.
+fn api_error(function: &'static str) -> impl FnOnce(windows::core::Error) -> Error {
+    move |error| {
+        debug!("{function} failed: {error}");
+        Error::failed(error)
+    }
+}
+
+fn with_com(f: impl FnOnce() -> Result<Icon, Error>) -> Result<Icon, Error> {
+    unsafe { CoInitialize(None).ok() }.map_err(api_error("CoInitialize"))?;
+
+    defer!(unsafe { CoUninitialize() });
+
+    f()
+}
+
+/// Makes the current thread per-monitor DPI aware while `f` runs, if `dpi_aware` is set.
+///
+/// The shell returns 96 DPI bitmaps to DPI unaware threads, they are blurry once upscaled.
+fn with_dpi_awareness(
+    dpi_aware: bool,
+    f: impl FnOnce() -> Result<Icon, Error>,
+) -> Result<Icon, Error> {
+    if !dpi_aware {
+        return f();
+    }
+
+    let previous =
+        unsafe { SetThreadDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2) };
+
+    // The context is not changed on Windows versions older than 10 1703.
+    if previous.is_invalid() {
+        debug!("Failed to make the thread per-monitor DPI aware");
+        return f();
+    }
+
+    defer!({
+        unsafe { SetThreadDpiAwarenessContext(previous) };
+    });
+
+    f()
+}
+
+/// Size of the icons of the jumbo system image list.
+const JUMBO_SIZE: u16 = 256;
+
+fn get_image(path: &HSTRING, size: u16, scale_mode: ScaleMode) -> Result<Icon, Error> {
+    // The jumbo image list has sharper large icons than the downscaled images of the factory,
+    // but it pads the smaller native icons to its size, so the factory returns the native ones.
+    if size >= JUMBO_SIZE && scale_mode == ScaleMode::ScaleUp {
+        match get_system_image_index(path, None).and_then(|index| get_system_image(index, size)) {
+            Ok(icon) => return Ok(icon),
+            Err(error) => debug!("Failed to get jumbo icon of '{path}': {error}"),
+        }
+    }
+
+    get_factory_image(path, size, SIIGBF_ICONONLY | scale_flags(scale_mode))
+}
+
+/// Returns the flag of `IShellItemImageFactory::GetImage()` for the icons smaller than the requested size.
+fn scale_flags(scale_mode: ScaleMode) -> SIIGBF {
+    match scale_mode {
+        ScaleMode::ScaleUp => SIIGBF_SCALEUP,
+        // Without the flag the images are only shrunk, the small ones keep their native size.
+        ScaleMode::Native => SIIGBF_RESIZETOFIT,
+    }
+}
+
+fn get_factory_image(path: &HSTRING, size: u16, flags: SIIGBF) -> Result<Icon, Error> {
+    let factory: IShellItemImageFactory = unsafe { SHCreateItemFromParsingName(path, None) }
+        .map_err(api_error("SHCreateItemFromParsingName"))?;
+
+    get_item_image(&factory, size, flags)
+}
+
+/// Gets the icon of a known folder from its item identifier list, it works for the virtual folders too.
+fn get_known_folder_image(folder: &GUID, size: u16) -> Result<Icon, Error> {
+    let flags = u32::try_from(KF_FLAG_DEFAULT.0).map_err(Error::failed)?;
+    let id_list = unsafe { SHGetKnownFolderIDList(folder, flags, None) }
+        .map_err(api_error("SHGetKnownFolderIDList"))?;
+
+    defer!(unsafe { ILFree(Some(id_list.cast_const())) });
+
+    let factory: IShellItemImageFactory =
+        unsafe { SHCreateItemFromIDList(id_list) }.map_err(api_error("SHCreateItemFromIDList"))?;
+
+    get_item_image(&factory, size, SIIGBF_ICONONLY | SIIGBF_SCALEUP)
+}
+
+fn get_item_image(
+    factory: &IShellItemImageFactory,
+    size: u16,
+    flags: SIIGBF,
+) -> Result<Icon, Error> {
+    let hbitmap = unsafe {
+        let image_size = i32::from(size);
+        factory.GetImage(
+            SIZE {
+                cx: image_size,
+                cy: image_size,
+            },
+            flags,
+        )
+    }
+    .map_err(api_error("IShellItemImageFactory::GetImage"))?;
+
+    // The bitmap is not always of the requested size, its real dimensions are used.
+    get_hbitmap_icon(hbitmap)
+}
+
+fn get_type_image(
+    name: &HSTRING,
+    attributes: FILE_FLAGS_AND_ATTRIBUTES,
+    size: u16,
+) -> Result<Icon, Error> {
+    let index = get_system_image_index(name, Some(attributes))?;
+
+    get_system_image(index, size)
+}
+
+/// Gets the index of the icon of `name` in the system image lists.
+///
+/// If `attributes` is set, `name` does not have to exist, only its extension and the
+/// attributes are used.
+fn get_system_image_index(
+    name: &HSTRING,
+    attributes: Option<FILE_FLAGS_AND_ATTRIBUTES>,
+) -> Result<i32, Error> {
+    let mut file_info = SHFILEINFOW::default();
+    let flags = match attributes {
+        Some(_) => SHGFI_USEFILEATTRIBUTES | SHGFI_SYSICONINDEX,
+        None => SHGFI_SYSICONINDEX,
+    };
+
+    if unsafe {
+        SHGetFileInfoW(
+            name,
+            attributes.unwrap_or_default(),
+            Some(&raw mut file_info),
+            size_of_u32::<SHFILEINFOW>(),
+            flags,
+        )
+    } == 0
+    {
+        debug!("SHGetFileInfoW failed for '{name}'");
+        return Err(Error::failed(format!(
+            "Failed to get file info of '{name}'"
+        )));
+    }
+
+    Ok(file_info.iIcon)
+}
+
+/// Gets an image of the system image list.
+///
+/// The system image lists only have a few sizes (16, 32, 48 and 256 pixels),
+/// the smallest one larger or equal to `size` is used.
+/// Above 256 pixels, the icons of the largest list are upscaled to `size`.
+fn get_system_image(index: i32, size: u16) -> Result<Icon, Error> {
+    let image_list = get_system_image_list(size)?;
+    let hicon = unsafe { image_list.GetIcon(index, ILD_TRANSPARENT.0) }
+        .map_err(api_error("IImageList::GetIcon"))?;
+    let icon = get_hicon_icon(hicon)?;
+
+    if size > JUMBO_SIZE {
+        return Ok(icon.resize_with_filter(
+            u32::from(size),
+            u32::from(size),
+            ResizeFilter::Triangle,
+        ));
+    }
+
+    Ok(icon)
+}
+
+/// Gets the system image list of the smallest size larger or equal to `size`.
+fn get_system_image_list(size: u16) -> Result<IImageList, Error> {
+    let image_list = [(SHIL_SMALL, 16), (SHIL_LARGE, 32), (SHIL_EXTRALARGE, 48)]
+        .into_iter()
+        .find(|(_, image_list_size)| *image_list_size >= size)
+        .map_or(SHIL_JUMBO, |(image_list, _)| image_list);
+    let image_list = i32::try_from(image_list).map_err(Error::failed)?;
+
+    unsafe { SHGetImageList(image_list) }.map_err(api_error("SHGetImageList"))
+}
+
+/// Gets the arrow the shell draws over the icons of shortcuts and symbolic links.
+///
+/// The overlay has the size of the icons of the system image list, the arrow is in its bottom left corner.
+fn get_link_overlay_image(size: u16) -> Result<Icon, Error> {
+    let link = i32::try_from(IDO_SHGIOI_LINK).map_err(Error::failed)?;
+    let overlay = unsafe { SHGetIconOverlayIndexW(PCWSTR::null(), link) };
+
+    if overlay < 0 {
+        return Err(Error::failed("No overlay for shortcuts"));
+    }
+
+    let image_list = get_system_image_list(size)?;
+    let index = unsafe { image_list.GetOverlayImage(overlay) }
+        .map_err(api_error("IImageList::GetOverlayImage"))?;
+
+    get_system_image(index, size)
+}
+
+fn get_hicon_icon(hicon: HICON) -> Result<Icon, Error> {
+    defer!({
+        let _ = unsafe { DestroyIcon(hicon) };
+    });
+
+    let mut icon_info = ICONINFO::default();
+
+    unsafe { GetIconInfo(hicon, &raw mut icon_info) }.map_err(api_error("GetIconInfo"))?;
+
+    // Shell icons have an alpha channel, the mask is not needed.
+    let _ = unsafe { DeleteObject(icon_info.hbmMask.into()) };
+
+    get_hbitmap_icon(icon_info.hbmColor)
 }
 
 /// Just `std::mem::size_of::<T>()` casted to `i32`
@@ -123,8 +506,16 @@ fn size_of_u32<T>() -> u32 {
     u32::try_from(std::mem::size_of::<T>()).unwrap()
 }
 
-fn get_hbitmap_pixels(hbitmap: HBITMAP) -> Option<Vec<u8>> {
-    let pixels = unsafe {
+/// Bits per pixel of the DIBs read with `GetDIBits()`.
+const DIB_BIT_COUNT: u16 = 32;
+
+/// Returns the number of bytes of a DIB row, rows are padded to 4-byte boundaries.
+fn dib_stride(width: usize) -> usize {
+    (width * usize::from(DIB_BIT_COUNT)).div_ceil(32) * 4
+}
+
+fn get_hbitmap_icon(hbitmap: HBITMAP) -> Result<Icon, Error> {
+    let icon = unsafe {
         defer!({
             let _ = DeleteObject(hbitmap.into());
         });
@@ -137,8 +528,7 @@ fn get_hbitmap_pixels(hbitmap: HBITMAP) -> Option<Vec<u8>> {
             Some((&raw mut bmp).cast::<c_void>()),
         ) == 0
         {
-            error!("Failed to get HBITMAP data");
-            return None
+            return Err(Error::failed("Failed to get HBITMAP data"));
         }
 
         let mut bi: BITMAPINFO = std::mem::zeroed();
@@ -146,112 +536,773 @@ fn get_hbitmap_pixels(hbitmap: HBITMAP) -> Option<Vec<u8>> {
         bi.bmiHeader.biWidth = bmp.bmWidth;
         bi.bmiHeader.biHeight = -bmp.bmHeight;
         bi.bmiHeader.biPlanes = 1;
-        bi.bmiHeader.biBitCount = 32;
+        bi.bmiHeader.biBitCount = DIB_BIT_COUNT;
         bi.bmiHeader.biCompression = BI_RGB.0;
 
         let Ok(bmp_width) = usize::try_from(bmp.bmWidth) else {
-            error!("Negative bitmap width: {}", bmp.bmWidth);
-            return None
+            return Err(Error::failed(format!(
+                "Negative bitmap width: {}",
+                bmp.bmWidth
+            )));
         };
         let Ok(bmp_height) = usize::try_from(bmp.bmHeight) else {
-            error!("Negative bitmap height: {}", bmp.bmHeight);
-            return None
+            return Err(Error::failed(format!(
+                "Negative bitmap height: {}",
+                bmp.bmHeight
+            )));
+        };
+        let Ok(width) = u32::try_from(bmp_width) else {
+            return Err(Error::failed(format!(
+                "Out of bound bitmap width: {bmp_width}"
+            )));
         };
         let Ok(clines) = u32::try_from(bmp_height) else {
-            error!("Out of bound bitmap height: {bmp_height}");
-            return None
+            return Err(Error::failed(format!(
+                "Out of bound bitmap height: {bmp_height}"
+            )));
         };
-        let mut pixels = vec![0u8; bmp_width * bmp_height * 4];
+        if bmp_width == 0 || bmp_height == 0 {
+            return Err(Error::failed(format!(
+                "Empty bitmap: {bmp_width}x{bmp_height}"
+            )));
+        }
+        let stride = dib_stride(bmp_width);
+        let mut buffer = vec![0u8; stride * bmp_height];
         let hdc: HDC = CreateCompatibleDC(None);
         if hdc.is_invalid() {
-            error!("Unable to create Device Context");
-            return None
+            return Err(Error::failed("Unable to create Device Context"));
         }
         let res = GetDIBits(
             hdc,
             hbitmap,
             0,
             clines,
-            Some(pixels.as_mut_ptr().cast()),
+            Some(buffer.as_mut_ptr().cast()),
             &raw mut bi,
             DIB_RGB_COLORS,
         );
         let _ = DeleteDC(hdc);
 
         if res == 0 {
-            error!("Failed to get HBITMAP bits");
-            return None
+            return Err(Error::failed("Failed to get HBITMAP bits"));
         }
 
-        // GetDIBits() returns BGRA pixels, converting to RGBA
-        for chunk in pixels.chunks_exact_mut(4) {
-            chunk.swap(0, 2);
+        // GetDIBits() returns padded rows of BGRA pixels, the padding is removed.
+        // The pixels are kept in BGRA, they are only reordered if another format is requested.
+        let mut pixels = Vec::with_capacity(bmp_width * bmp_height * 4);
+
+        for row in buffer.chunks_exact(stride) {
+            pixels.extend_from_slice(&row[..bmp_width * 4]);
         }
 
-        pixels
+        Icon {
+            width,
+            height: clines,
+            pixels,
+            format: PixelFormat::Bgra8,
+            stride: None,
+        }
     };
 
-    Some(pixels)
+    Ok(icon)
+}
+
+pub(crate) fn get_file_icon(
+    path: impl AsRef<Path>,
+    size: u16,
+    options: &Options,
+) -> Result<Icon, Error> {
+    let path = shell_path(path.as_ref());
+    let cancellation = options.cancellation.clone();
+    let dpi_aware = options.dpi_aware;
+    let scale_mode = options.scale_mode;
+
+    request_image(options, |reply| ImageFactoryRequest::RequestImage {
+        path,
+        size,
+        cancellation,
+        dpi_aware,
+        scale_mode,
+        reply,
+    })
+}
+
+pub(crate) fn get_file_icon_sta(
+    path: impl AsRef<Path>,
+    size: u16,
+    options: &Options,
+) -> Result<Icon, Error> {
+    if !is_single_threaded_apartment() {
+        return get_file_icon(path, size, options);
+    }
+
+    options.check_cancelled()?;
+
+    // COM is already initialized by the caller, the image is retrieved on this thread.
+    with_dpi_awareness(options.dpi_aware, || {
+        get_image(&shell_path(path.as_ref()), size, options.scale_mode)
+    })
+    .map(|icon| icon.into_format(options.format))
+}
+
+/// Returns `true` if COM is initialized on the current thread as a single-threaded apartment,
+/// which the shell functions require.
+fn is_single_threaded_apartment() -> bool {
+    let mut apartment_type = APTTYPE::default();
+    let mut qualifier = APTTYPEQUALIFIER::default();
+
+    unsafe { CoGetApartmentType(&raw mut apartment_type, &raw mut qualifier) }.is_ok()
+        && (apartment_type == APTTYPE_STA || apartment_type == APTTYPE_MAINSTA)
 }
 
-pub(crate) fn get_file_icon(path: impl AsRef<Path>, size: u16) -> Option<Icon> {
-    let path = HSTRING::from(path.as_ref());
-    let (reply_tx, reply_rx) = channel();
+pub(crate) fn get_file_thumbnail(
+    path: impl AsRef<Path>,
+    size: u16,
+    options: &Options,
+) -> Result<Icon, Error> {
+    let path = shell_path(path.as_ref());
 
-    match IMAGE_FACTORY_REQUEST_SENDER.send(ImageFactoryRequest::RequestImage {
+    request_image(options, |reply| ImageFactoryRequest::RequestThumbnail {
         path,
         size,
-        reply: reply_tx,
-    }) {
-        Ok(()) => {
-            let Ok(ImageFactoryReply::Success(icon)) = reply_rx.recv() else {
-                return None
-            };
-
-            Some(icon)
+        reply,
+    })
+}
+
+pub(crate) fn get_file_icons(
+    paths: &[&Path],
+    size: u16,
+    options: &Options,
+) -> Vec<Result<Icon, Error>> {
+    request_images(paths.iter().map(|path| (shell_path(path), size)), options)
+}
+
+pub(crate) fn get_file_icons_parallel(
+    paths: &[&Path],
+    size: u16,
+    threads: usize,
+    options: &Options,
+) -> Vec<Result<Icon, Error>> {
+    get_icons_in_chunks(paths, threads, |paths| {
+        get_file_icons_in_apartment(paths, size, options)
+    })
+}
+
+/// Gets the icons on the current thread, in its own single-threaded apartment.
+///
+/// Unlike the image factory thread, the shell is called directly: the timeout does not apply.
+fn get_file_icons_in_apartment(
+    paths: &[&Path],
+    size: u16,
+    options: &Options,
+) -> Vec<Result<Icon, Error>> {
+    paths
+        .iter()
+        .map(|path| {
+            options.check_cancelled()?;
+            with_com(|| {
+                with_dpi_awareness(options.dpi_aware, || {
+                    get_image(&shell_path(path), size, options.scale_mode)
+                })
+            })
+            .map(|icon| icon.into_format(options.format))
+        })
+        .collect()
+}
+
+pub(crate) fn get_file_icon_sizes(
+    path: &Path,
+    sizes: &[u16],
+    options: &Options,
+) -> Result<Vec<Icon>, Error> {
+    let path = shell_path(path);
+
+    request_images(sizes.iter().map(|size| (path.clone(), *size)), options)
+        .into_iter()
+        .collect()
+}
+
+pub(crate) fn get_icon_for_extension(
+    extension: &str,
+    size: u16,
+    options: &Options,
+) -> Result<Icon, Error> {
+    let name = HSTRING::from(format!(".{extension}"));
+
+    request_image(options, |reply| ImageFactoryRequest::RequestTypeImage {
+        name,
+        attributes: FILE_ATTRIBUTE_NORMAL,
+        size,
+        reply,
+    })
+}
+
+pub(crate) fn get_icon_for_name(
+    file_name: &str,
+    size: u16,
+    options: &Options,
+) -> Result<Icon, Error> {
+    // The shell only looks at the extension, names without extension get the generic document icon.
+    let name = HSTRING::from(file_name);
+
+    request_image(options, |reply| ImageFactoryRequest::RequestTypeImage {
+        name,
+        attributes: FILE_ATTRIBUTE_NORMAL,
+        size,
+        reply,
+    })
+}
+
+pub(crate) fn get_icon_for_mime_type(
+    mime_type: &str,
+    size: u16,
+    options: &Options,
+) -> Result<Icon, Error> {
+    if let Some(extension) = get_mime_type_extension(mime_type) {
+        return get_icon_for_extension(&extension, size, options);
+    }
+
+    get_default_file_icon(size, options)
+}
+
+pub(crate) fn get_default_file_icon(size: u16, options: &Options) -> Result<Icon, Error> {
+    // A name without extension gives the generic document icon.
+    let name = HSTRING::from("file");
+
+    request_image(options, |reply| ImageFactoryRequest::RequestTypeImage {
+        name,
+        attributes: FILE_ATTRIBUTE_NORMAL,
+        size,
+        reply,
+    })
+}
+
+pub(crate) fn get_folder_icon(size: u16, options: &Options) -> Result<Icon, Error> {
+    let name = HSTRING::from("folder");
+
+    request_image(options, |reply| ImageFactoryRequest::RequestTypeImage {
+        name,
+        attributes: FILE_ATTRIBUTE_DIRECTORY,
+        size,
+        reply,
+    })
+}
+
+pub(crate) fn get_known_folder_icon(
+    folder: KnownFolder,
+    size: u16,
+    options: &Options,
+) -> Result<Icon, Error> {
+    let folder = match folder {
+        KnownFolder::Home => FOLDERID_Profile,
+        KnownFolder::Desktop => FOLDERID_Desktop,
+        KnownFolder::Documents => FOLDERID_Documents,
+        KnownFolder::Downloads => FOLDERID_Downloads,
+        KnownFolder::Music => FOLDERID_Music,
+        KnownFolder::Pictures => FOLDERID_Pictures,
+        KnownFolder::Videos => FOLDERID_Videos,
+        KnownFolder::RecycleBin => FOLDERID_RecycleBinFolder,
+        KnownFolder::ThisPc => FOLDERID_ComputerFolder,
+    };
+
+    request_image(options, |reply| {
+        ImageFactoryRequest::RequestKnownFolderImage {
+            folder,
+            size,
+            reply,
+        }
+    })
+}
+
+pub(crate) fn get_volume_icon(path: &Path, size: u16, options: &Options) -> Result<Icon, Error> {
+    // The shell gives the icon of the drive for the root of a volume, like `C:\`.
+    get_file_icon(path, size, options)
+}
+
+pub(crate) fn get_application_icon(
+    executable: &Path,
+    size: u16,
+    options: &Options,
+) -> Result<Icon, Error> {
+    // The shell extracts the main icon resource of the executable.
+    get_file_icon(executable, size, options)
+}
+
+pub(crate) fn get_icon_from_resource(
+    path: &Path,
+    index: i32,
+    size: u16,
+    options: &Options,
+) -> Result<Icon, Error> {
+    let mut file_name = [0u16; MAX_PATH as usize];
+    let wide_path = shell_path(path);
+
+    // The buffer must end with a null character.
+    if wide_path.len() >= file_name.len() {
+        return Err(Error::failed(format!(
+            "The path '{}' is too long",
+            path.display()
+        )));
+    }
+
+    file_name[..wide_path.len()].copy_from_slice(&wide_path);
+
+    let mut hicons = [HICON::default()];
+    let size = i32::from(size);
+    // A negative index is the identifier of the resource instead of its position.
+    let count =
+        unsafe { PrivateExtractIconsW(&file_name, index, size, size, Some(&mut hicons), None, 0) };
+
+    // The count is u32::MAX if the file can't be read, and 0 if there is no icon at this index.
+    if count == 0 || count == u32::MAX || hicons[0].is_invalid() {
+        return Err(Error::failed(format!(
+            "No icon resource {index} in '{}'",
+            path.display()
+        )));
+    }
+
+    get_hicon_icon(hicons[0]).map(|icon| icon.into_format(options.format))
+}
+
+pub(crate) fn get_link_overlay(size: u16, options: &Options) -> Result<Icon, Error> {
+    request_image(options, |reply| ImageFactoryRequest::RequestLinkOverlay {
+        size,
+        reply,
+    })
+}
+
+#[allow(clippy::unnecessary_wraps)]
+pub(crate) fn get_file_type_info(
+    path: &Path,
+    size: u16,
+    options: &Options,
+) -> Result<FileTypeInfo, Error> {
+    let extension = path.extension().and_then(OsStr::to_str);
+    let identifier = match extension {
+        _ if path.is_dir() => "Folder".to_owned(),
+        Some(extension) => format!(".{extension}"),
+        None => String::new(),
+    };
+    // Explorer shows the same descriptions for the types without friendly name.
+    let description = friendly_type_name(&identifier).unwrap_or_else(|| match extension {
+        Some(extension) => format!("{} File", extension.to_uppercase()),
+        None => "File".to_owned(),
+    });
+    let icon = get_file_icon(path, size, options)
+        .inspect_err(|error| debug!("No icon for '{}': {error}", path.display()))
+        .ok();
+
+    Ok(FileTypeInfo {
+        identifier,
+        description,
+        icon,
+    })
+}
+
+/// The shell draws the icons of the system image lists, see [`get_system_image_list`].
+#[allow(clippy::unnecessary_wraps)]
+pub(crate) fn get_available_sizes(_path: &Path, _options: &Options) -> Result<Vec<u16>, Error> {
+    Ok(vec![16, 32, 48, JUMBO_SIZE])
+}
+
+pub(crate) fn get_icon_source(path: &Path) -> IconSource {
+    if path.is_dir() {
+        return IconSource::TypeAssociation;
+    }
+
+    match path.extension().and_then(OsStr::to_str) {
+        // Executables embed their icon, shortcuts and icon files point to their own icon.
+        Some(extension)
+            if ["exe", "lnk", "url", "ico"]
+                .iter()
+                .any(|specific| extension.eq_ignore_ascii_case(specific)) =>
+        {
+            IconSource::FileSpecific
         }
+        Some(extension) if is_extension_registered(extension) => IconSource::TypeAssociation,
+        _ => IconSource::Generic,
+    }
+}
+
+/// Returns `path` in the form parsed by the shell functions.
+///
+/// Paths shorter than `MAX_PATH` are passed without the extended-length prefix `\\?\` added by
+/// `std::fs::canonicalize`, as the shell displays them. Longer paths are made absolute and
+/// normalized with the prefix, which lifts the `MAX_PATH` limit.
+fn shell_path(path: &Path) -> HSTRING {
+    let path = without_verbatim_prefix(path);
+
+    if path.as_os_str().encode_wide().count() < MAX_PATH as usize {
+        return HSTRING::from(path.as_path());
+    }
+
+    match std::path::absolute(&path) {
+        Ok(absolute_path) => HSTRING::from(with_verbatim_prefix(&absolute_path).as_path()),
         Err(error) => {
+            debug!("Failed to make '{}' absolute: {error}", path.display());
+            HSTRING::from(path.as_path())
+        }
+    }
+}
+
+/// Removes the extended-length prefix: `\\?\C:\file` becomes `C:\file`
+/// and `\\?\UNC\server\share\file` becomes `\\server\share\file`.
+fn without_verbatim_prefix(path: &Path) -> PathBuf {
+    replace_prefix(path, |prefix| match prefix {
+        Prefix::VerbatimDisk(disk) => Some(OsString::from(format!("{}:", char::from(disk)))),
+        Prefix::VerbatimUNC(server, share) => Some(unc_prefix(r"\\", server, share)),
+        _ => None,
+    })
+}
+
+/// Adds the extended-length prefix to an absolute path, the opposite of [`without_verbatim_prefix`].
+fn with_verbatim_prefix(path: &Path) -> PathBuf {
+    replace_prefix(path, |prefix| match prefix {
+        Prefix::Disk(disk) => Some(OsString::from(format!(r"\\?\{}:", char::from(disk)))),
+        Prefix::UNC(server, share) => Some(unc_prefix(r"\\?\UNC\", server, share)),
+        _ => None,
+    })
+}
+
+fn unc_prefix(start: &str, server: &OsStr, share: &OsStr) -> OsString {
+    let mut prefix = OsString::from(start);
+
+    prefix.push(server);
+    prefix.push(r"\");
+    prefix.push(share);
+    prefix
+}
+
+/// Replaces the prefix of `path` by the one returned by `replace`, `path` is unchanged if it returns `None`.
+fn replace_prefix(path: &Path, replace: impl FnOnce(Prefix) -> Option<OsString>) -> PathBuf {
+    let mut components = path.components();
+    let Some(Component::Prefix(prefix)) = components.next() else {
+        return path.to_path_buf();
+    };
+    let Some(prefix) = replace(prefix.kind()) else {
+        return path.to_path_buf();
+    };
+    let mut replaced = PathBuf::from(prefix);
+
+    replaced.extend(components);
+    replaced
+}
+
+/// Returns the `FriendlyTypeName` of a file type in the registry, like "PDF Document".
+///
+/// The name is often a reference to a string resource, the shell loads it.
+fn friendly_type_name(association: &str) -> Option<String> {
+    if association.is_empty() {
+        return None;
+    }
+
+    let association = HSTRING::from(association);
+    let query = |buffer: Option<&mut [u16]>, length: &mut u32| unsafe {
+        AssocQueryStringW(
+            ASSOCF_INIT_IGNOREUNKNOWN,
+            ASSOCSTR_FRIENDLYDOCNAME,
+            &association,
+            PCWSTR::null(),
+            buffer.map(|buffer| PWSTR(buffer.as_mut_ptr())),
+            length,
+        )
+    };
+    let mut length = 0;
+
+    // The first call returns the length of the name, including the null character.
+    if let Err(error) = query(None, &mut length).ok() {
+        debug!("No friendly name for '{association}': {error}");
+        return None;
+    }
+
+    let mut buffer = vec![0u16; length as usize];
+
+    query(Some(&mut buffer), &mut length).ok().ok()?;
+
+    let length = buffer.iter().position(|c| *c == 0).unwrap_or(buffer.len());
+    let name = String::from_utf16(&buffer[..length]).ok()?;
+
+    (!name.is_empty()).then_some(name)
+}
+
+/// Returns `true` if a program identifier is associated with the extension in the registry,
+/// the shell takes the icon of the file type from it.
+fn is_extension_registered(extension: &str) -> bool {
+    let key = HSTRING::from(format!(".{extension}"));
+
+    unsafe { RegGetValueW(HKEY_CLASSES_ROOT, &key, None, RRF_RT_ANY, None, None, None) }.is_ok()
+}
+
+/// Reads the extension associated with a MIME type in the registry, for example "png" for "image/png".
+fn get_mime_type_extension(mime_type: &str) -> Option<String> {
+    let key = HSTRING::from(format!(r"MIME\Database\Content Type\{mime_type}"));
+    let mut buffer = [0u16; 64];
+    let mut buffer_size = size_of_u32::<[u16; 64]>();
+
+    if let Err(error) = unsafe {
+        RegGetValueW(
+            HKEY_CLASSES_ROOT,
+            &key,
+            w!("Extension"),
+            RRF_RT_REG_SZ,
+            None,
+            Some(buffer.as_mut_ptr().cast()),
+            Some(&raw mut buffer_size),
+        )
+    }
+    .ok()
+    {
+        debug!("No extension associated with MIME type '{mime_type}': {error}");
+        return None;
+    }
+
+    let length = buffer.iter().position(|c| *c == 0).unwrap_or(buffer.len());
+    let extension = String::from_utf16(&buffer[..length]).ok()?;
+
+    Some(extension.trim_start_matches('.').to_owned())
+}
+
+/// Requests the images of several `(path, size)` and waits for the replies.
+///
+/// The pixels of the images are reordered into `options.format`.
+/// The timeout of the options applies to the whole batch.
+fn request_images(
+    requests: impl ExactSizeIterator<Item = (HSTRING, u16)>,
+    options: &Options,
+) -> Vec<Result<Icon, Error>> {
+    let count = requests.len();
+    let (reply, receiver, _waiting) = Reply::channel();
+    let mut sent = 0;
+
+    // All the requests are sent before waiting for the first reply.
+    // The factory thread answers them in order through the same channel.
+    for (path, size) in requests {
+        let request = ImageFactoryRequest::RequestImage {
+            path,
+            size,
+            cancellation: None,
+            dpi_aware: options.dpi_aware,
+            scale_mode: options.scale_mode,
+            reply: reply.clone(),
+        };
+
+        if let Err(error) = send_request(request) {
             error!("Failed to send request: {error}");
-            None
+            break;
+        }
+
+        sent += 1;
+    }
+
+    drop(reply);
+
+    let deadline = Instant::now().checked_add(options.timeout);
+    let mut icons = Vec::with_capacity(count);
+    let mut stop_reason = RecvTimeoutError::Disconnected;
+
+    for _ in 0..sent {
+        let reply = match deadline {
+            Some(deadline) => {
+                receiver.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            }
+            None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+
+        match reply {
+            Ok(reply) => icons.push(reply.map(|icon| icon.into_format(options.format))),
+            Err(error) => {
+                stop_reason = error;
+                break;
+            }
         }
     }
+
+    icons.resize_with(count, || Err(receive_error(stop_reason)));
+    icons
 }
 
-pub(crate) struct Provider<T: Clone> {
-    icon_size: u16,
-    converter: fn(Icon) -> T,
-    icons_cache: RefCell<BTreeMap<String, T>>,
+/// Sends a request to the image factory thread and waits for the reply.
+///
+/// The pixels of the image are reordered into `options.format`.
+fn request_image(
+    options: &Options,
+    request: impl FnOnce(Reply) -> ImageFactoryRequest,
+) -> Result<Icon, Error> {
+    let (reply, receiver, _waiting) = Reply::channel();
+
+    send_request(request(reply))?;
+
+    receiver
+        .recv_timeout(options.timeout)
+        .map_err(receive_error)?
+        .map(|icon| icon.into_format(options.format))
 }
 
-impl<T: Clone> Provider<T> {
-    #[allow(clippy::unnecessary_wraps)]
-    pub fn new(icon_size: u16, converter: fn(Icon) -> T) -> Option<Self> {
-        Some(Self {
-            icon_size,
-            converter,
-            icons_cache: RefCell::new(BTreeMap::new()),
-        })
+fn receive_error(error: RecvTimeoutError) -> Error {
+    match error {
+        RecvTimeoutError::Timeout => Error::Timeout,
+        RecvTimeoutError::Disconnected => Error::failed("The image factory thread stopped"),
+    }
+}
+
+/// The hidden window receiving the changes of the settings of the system, set while they are watched.
+///
+/// The handle is stored as an integer, `HWND` can't be sent to other threads.
+static SETTINGS_WINDOW: Mutex<Option<isize>> = Mutex::new(None);
+
+/// Starts a thread whose hidden window receives the changes of the settings of the system.
+///
+/// `WM_SETTINGCHANGE` is only broadcast to top-level windows, a message-only window would not get it.
+pub(crate) fn watch_theme() -> Result<(), Error> {
+    let mut settings_window = SETTINGS_WINDOW
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+
+    if settings_window.is_some() {
+        return Ok(());
+    }
+
+    let (sender, receiver) = channel();
+
+    std::thread::spawn(move || {
+        let window = create_settings_window();
+        let created = window.is_ok();
+
+        let _ = sender.send(window.map(|window| window.0 as isize));
+
+        if created {
+            run_message_loop();
+        }
+    });
+
+    *settings_window = Some(
+        receiver
+            .recv()
+            .map_err(|_| Error::failed("The settings thread stopped"))??,
+    );
+
+    Ok(())
+}
+
+/// Closes the hidden window watching the settings, its thread stops once the window is destroyed.
+pub(crate) fn unwatch_theme() {
+    let window = SETTINGS_WINDOW
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take();
+
+    if let Some(window) = window {
+        let window = HWND(window as *mut c_void);
+
+        if let Err(error) = unsafe { PostMessageW(Some(window), WM_CLOSE, WPARAM(0), LPARAM(0)) } {
+            error!("Failed to close the settings window: {error}");
+        }
+    }
+}
+
+/// The icons of the shell are not cached by this crate, there is nothing to reload.
+pub(crate) fn refresh_theme() {}
+
+fn create_settings_window() -> Result<HWND, Error> {
+    let class_name = w!("file_icon_provider_settings");
+    let instance = HINSTANCE::from(
+        unsafe { GetModuleHandleW(PCWSTR::null()) }.map_err(api_error("GetModuleHandleW"))?,
+    );
+    let window_class = WNDCLASSW {
+        lpfnWndProc: Some(settings_window_procedure),
+        hInstance: instance,
+        lpszClassName: class_name,
+        ..Default::default()
+    };
+
+    // The class stays registered after the window of a previous watch was destroyed.
+    if unsafe { RegisterClassW(&raw const window_class) } == 0 {
+        let error = windows::core::Error::from_thread();
+
+        if error.code() != ERROR_CLASS_ALREADY_EXISTS.to_hresult() {
+            return Err(api_error("RegisterClassW")(error));
+        }
+    }
+
+    // The window is never shown, it only receives the broadcast messages.
+    unsafe {
+        CreateWindowExW(
+            WINDOW_EX_STYLE::default(),
+            class_name,
+            PCWSTR::null(),
+            WINDOW_STYLE::default(),
+            0,
+            0,
+            0,
+            0,
+            None,
+            None,
+            Some(instance),
+            None,
+        )
+    }
+    .map_err(api_error("CreateWindowExW"))
+}
+
+fn run_message_loop() {
+    let mut message = MSG::default();
+
+    while unsafe { GetMessageW(&raw mut message, None, 0, 0) }.as_bool() {
+        unsafe { DispatchMessageW(&raw const message) };
     }
+}
+
+extern "system" fn settings_window_procedure(
+    window: HWND,
+    message: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    match message {
+        WM_THEMECHANGED => notify_theme_changed(),
+        // The other settings, like the keyboard or the wallpaper, don't change the icons.
+        WM_SETTINGCHANGE if is_color_set_change(lparam) => notify_theme_changed(),
+        // The window is destroyed by `unwatch_theme`, this stops the message loop of its thread.
+        WM_DESTROY => unsafe { PostQuitMessage(0) },
+        _ => {}
+    }
+
+    unsafe { DefWindowProcW(window, message, wparam, lparam) }
+}
 
-    pub fn get_file_icon(&self, path: impl AsRef<Path>) -> Option<T> {
-        let path = path.as_ref();
+/// Returns `true` if a `WM_SETTINGCHANGE` notifies a change of the light or dark mode of the system.
+fn is_color_set_change(lparam: LPARAM) -> bool {
+    let area = PCWSTR(lparam.0 as *const u16);
 
+    !area.is_null() && unsafe { area.to_string() }.is_ok_and(|area| area == "ImmersiveColorSet")
+}
+
+pub(crate) struct Provider;
+
+impl Provider {
+    #[allow(clippy::unnecessary_wraps)]
+    pub fn new(_icon_size: u16) -> Result<Self, Error> {
+        Ok(Self)
+    }
+
+    /// Returns the key used to cache the icon of `path`, or `None` if its icon must not be cached.
+    #[allow(clippy::unused_self)]
+    pub fn cache_key(&self, path: &Path) -> Option<String> {
         match path.extension().and_then(OsStr::to_str) {
             // On Windows .exe and .lnk can have any icon so they are never cached.
-            Some(extension) if extension.eq_ignore_ascii_case("exe") || extension.eq_ignore_ascii_case("lnk") => {
-                get_file_icon(path, self.icon_size).map(self.converter)
+            Some(extension)
+                if extension.eq_ignore_ascii_case("exe")
+                    || extension.eq_ignore_ascii_case("lnk") =>
+            {
+                None
             }
-            None => get_file_icon(path, self.icon_size).map(self.converter),
-            Some(extension) => match self.icons_cache.borrow_mut().entry(extension.to_owned()) {
-                std::collections::btree_map::Entry::Vacant(vacant_entry) => Some(
-                    vacant_entry
-                        .insert(get_file_icon(path, self.icon_size).map(self.converter)?)
-                        .clone(),
-                ),
-                std::collections::btree_map::Entry::Occupied(occupied_entry) => {
-                    Some(occupied_entry.get().clone())
-                }
-            },
+            Some(extension) => Some(extension.to_owned()),
+            None => None,
         }
     }
+
+    #[allow(clippy::unused_self)]
+    pub fn get_file_icon(&self, path: &Path, size: u16, options: &Options) -> Result<Icon, Error> {
+        get_file_icon(path, options.scaled_size(size), options)
+    }
 }
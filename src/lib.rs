@@ -1,220 +1,5657 @@
 #![warn(clippy::pedantic)]
 
-use std::{fmt::Display, path::Path};
+use std::{
+    any::Any,
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    ffi::OsStr,
+    fmt::Display,
+    hash::{DefaultHasher, Hash, Hasher},
+    num::NonZeroU16,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex, MutexGuard, OnceLock, PoisonError, Weak,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use cache::Cache;
+use logging::{debug, warn};
+use theme::ThemeListener;
+
+pub use atlas::Rect;
+pub use directory::DirectoryIcons;
+#[cfg(feature = "global")]
+pub use global::{GlobalProvider, GlobalProviderConfig, global, set_global_provider_config};
+pub use path_ext::FileIconExt;
+pub use resize::ResizeFilter;
 
 /// Represents an icon with its dimensions and pixel data.
-#[derive(Debug)]
+///
+/// With the `image` feature, `Icon` can be converted into `image::RgbaImage` and `image::DynamicImage`
+/// using `TryFrom`.
+///
+/// With the `png` feature, `Icon::save_png` writes the icon to a PNG file.
+///
+/// With the `serde` feature, `Icon` implements `Serialize` and `Deserialize`.
+/// Deserialization fails if the length of the pixel buffer does not match the dimensions.
+///
+/// With the `terminal` feature, `Icon::to_sixel` and `Icon::to_kitty` encode the icon
+/// into escape sequences to print it in terminals.
+#[derive(Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "serialization::UncheckedIcon"))]
 pub struct Icon {
     /// The width of the icon in pixels.
     pub width: u32,
     /// The height of the icon in pixels.
     pub height: u32,
-    /// The pixel data of the icon in RGBA format.
+    /// The pixel data of the icon, in RGBA format unless `format` says otherwise.
+    ///
+    /// The alpha is straight on every platform: the colors are not premultiplied by the alpha,
+    /// unless the icon was requested with [`get_file_icon_premultiplied`].
+    ///
+    /// The rows can be padded at their end, see [`Icon::stride`].
     pub pixels: Vec<u8>,
+    /// The order of the channels of `pixels`.
+    pub format: PixelFormat,
+    /// The length of a row of `pixels` in bytes if the rows are padded, like the bitmaps of `AppKit`.
+    ///
+    /// `None` means the rows are tightly packed, `width * 4` bytes long.
+    /// See [`Icon::bytes_per_row`] and [`Icon::into_packed`].
+    pub stride: Option<usize>,
 }
 
-/// Represents an error
-#[derive(Debug)]
-pub enum Error {
-    /// Retrieving the icon failed
-    Failed,
-    /// The path does not exist
-    PathDoesNotExist,
-    /// The desired icon size is null
-    NullIconSize,
-}
+impl Icon {
+    /// Creates an icon from RGBA pixels with straight alpha, for example an icon loaded from a cache.
+    ///
+    /// It is the same as [`Icon::from_rgba`].
+    ///
+    /// # Errors
+    /// Fails with [`Error::InvalidBuffer`] if the length of `pixels` is not `width * height * 4`.
+    /// # Example
+    /// ```
+    /// use file_icon_provider::Icon;
+    ///
+    /// let icon = Icon::new(2, 2, vec![255; 16]).unwrap();
+    ///
+    /// assert_eq!(icon.into_pixels().len(), 16);
+    /// assert!(Icon::new(2, 2, vec![255; 15]).is_err());
+    /// ```
+    pub fn new(width: u32, height: u32, pixels: Vec<u8>) -> Result<Self, Error> {
+        Self::from_rgba(width, height, pixels)
+    }
 
-impl Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        match self {
-            Error::Failed => {
-                write!(f, "Failed to get icon")
-            }
-            Error::PathDoesNotExist => {
-                write!(f, "Path does not exist")
+    /// Creates an icon from RGBA pixels with straight alpha, like `image::RgbaImage::from_raw`.
+    ///
+    /// This rebuilds the icons stored field by field, for example in an icon cache on disk.
+    ///
+    /// # Errors
+    /// Fails with [`Error::InvalidBuffer`] if the length of `pixels` is not `width * height * 4`.
+    /// # Example
+    /// ```
+    /// use file_icon_provider::{Error, Icon};
+    ///
+    /// let icon = Icon::from_rgba(1, 2, vec![255; 8]).unwrap();
+    ///
+    /// assert_eq!((icon.width, icon.height), (1, 2));
+    /// assert!(matches!(
+    ///     Icon::from_rgba(1, 2, vec![255; 4]),
+    ///     Err(Error::InvalidBuffer { expected: 8, actual: 4 })
+    /// ));
+    /// ```
+    pub fn from_rgba(width: u32, height: u32, pixels: Vec<u8>) -> Result<Self, Error> {
+        let icon = Self {
+            width,
+            height,
+            pixels,
+            format: PixelFormat::Rgba8,
+            stride: None,
+        };
+
+        icon.check_buffer()?;
+
+        Ok(icon)
+    }
+
+    /// Returns the pixels in the order of [`Icon::format`], consuming the icon without copying them.
+    #[must_use]
+    pub fn into_pixels(self) -> Vec<u8> {
+        self.pixels
+    }
+
+    /// Returns the pixels in the order of [`Icon::format`].
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// Returns the length of a row of pixels in bytes, the stride of [`Icon::pixels`].
+    ///
+    /// It is [`Icon::stride`] if the rows are padded, `width * 4` otherwise.
+    ///
+    /// # Example
+    /// ```
+    /// use file_icon_provider::{Icon, PixelFormat};
+    ///
+    /// let icon = Icon {
+    ///     width: 1,
+    ///     height: 2,
+    ///     pixels: vec![255; 2 * 8],
+    ///     format: PixelFormat::Rgba8,
+    ///     stride: Some(8),
+    /// };
+    ///
+    /// assert_eq!(icon.bytes_per_row(), 8);
+    /// assert_eq!(icon.pixels.chunks_exact(icon.bytes_per_row()).count(), 2);
+    /// assert_eq!(icon.into_packed().bytes_per_row(), 4);
+    /// ```
+    #[must_use]
+    pub fn bytes_per_row(&self) -> usize {
+        self.stride.unwrap_or(self.width as usize * 4)
+    }
+
+    /// Returns the icon with its rows tightly packed, the padding at the end of the rows is removed.
+    ///
+    /// The pixels are moved within their buffer, they are not copied if the rows are already packed.
+    /// If the pixel buffer does not match the dimensions, the icon is returned unchanged.
+    ///
+    /// # Example
+    /// ```
+    /// use file_icon_provider::{Icon, PixelFormat};
+    ///
+    /// let icon = Icon {
+    ///     width: 1,
+    ///     height: 2,
+    ///     pixels: vec![1, 2, 3, 4, 0, 0, 5, 6, 7, 8, 0, 0],
+    ///     format: PixelFormat::Rgba8,
+    ///     stride: Some(6),
+    /// };
+    /// let packed = icon.into_packed();
+    ///
+    /// assert_eq!(packed.pixels, [1, 2, 3, 4, 5, 6, 7, 8]);
+    /// assert_eq!(packed.stride, None);
+    /// ```
+    #[must_use]
+    pub fn into_packed(mut self) -> Icon {
+        if self.stride.is_none() || !self.has_valid_buffer() {
+            return self;
+        }
+
+        let bytes_per_row = self.bytes_per_row();
+        let row_len = self.width as usize * 4;
+
+        for y in 1..self.height as usize {
+            self.pixels
+                .copy_within(y * bytes_per_row..y * bytes_per_row + row_len, y * row_len);
+        }
+        self.pixels.truncate(row_len * self.height as usize);
+        self.stride = None;
+        self
+    }
+
+    /// Returns the icon with its rows tightly packed, it is only copied if the rows are padded.
+    pub(crate) fn packed(&self) -> Cow<'_, Icon> {
+        if self.stride.is_none() {
+            Cow::Borrowed(self)
+        } else {
+            Cow::Owned(self.clone().into_packed())
+        }
+    }
+
+    /// Returns the rows of pixels, without their padding.
+    pub(crate) fn rows(&self) -> impl Iterator<Item = &[u8]> {
+        let row_len = self.width as usize * 4;
+
+        self.pixels
+            .chunks_exact(self.bytes_per_row().max(1))
+            .take(self.height as usize)
+            .map(move |row| &row[..row_len.min(row.len())])
+    }
+
+    /// Returns the rows of pixels, without their padding.
+    pub(crate) fn rows_mut(&mut self) -> impl Iterator<Item = &mut [u8]> {
+        let row_len = self.width as usize * 4;
+        let bytes_per_row = self.bytes_per_row().max(1);
+
+        self.pixels
+            .chunks_exact_mut(bytes_per_row)
+            .take(self.height as usize)
+            .map(move |row| {
+                let len = row_len.min(row.len());
+
+                &mut row[..len]
+            })
+    }
+
+    /// Returns the width divided by the height, or `0.0` if the icon has no height.
+    ///
+    /// # Example
+    /// ```
+    /// use file_icon_provider::Icon;
+    ///
+    /// let icon = Icon::new(4, 2, vec![255; 4 * 2 * 4]).unwrap();
+    ///
+    /// assert_eq!(icon.aspect_ratio(), 2.0);
+    /// assert!(!icon.is_square());
+    /// assert_eq!(icon.pixel_count(), 8);
+    /// ```
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn aspect_ratio(&self) -> f32 {
+        if self.height == 0 {
+            return 0.0;
+        }
+
+        self.width as f32 / self.height as f32
+    }
+
+    /// Returns `true` if the width and the height are equal.
+    #[must_use]
+    pub fn is_square(&self) -> bool {
+        self.width == self.height
+    }
+
+    /// Returns the number of pixels, `width * height`.
+    #[must_use]
+    pub fn pixel_count(&self) -> usize {
+        self.width as usize * self.height as usize
+    }
+
+    /// Returns the RGBA color of the pixel at `x`, `y`, whatever the format of the icon.
+    ///
+    /// Returns `None` if the pixel is outside the icon, or if the pixel buffer is too short.
+    ///
+    /// # Example
+    /// ```
+    /// use file_icon_provider::{Icon, PixelFormat};
+    ///
+    /// let icon = Icon {
+    ///     width: 2,
+    ///     height: 1,
+    ///     pixels: vec![0, 0, 0, 0, 3, 2, 1, 4],
+    ///     format: PixelFormat::Bgra8,
+    ///     stride: None,
+    /// };
+    ///
+    /// assert_eq!(icon.get_pixel(1, 0), Some([1, 2, 3, 4]));
+    /// assert_eq!(icon.get_pixel(2, 0), None);
+    /// ```
+    #[must_use]
+    pub fn get_pixel(&self, x: u32, y: u32) -> Option<[u8; 4]> {
+        let offset = self.pixel_offset(x, y)?;
+        let pixel: [u8; 4] = self.pixels.get(offset..offset + 4)?.try_into().ok()?;
+
+        Some(self.format.swizzle(pixel))
+    }
+
+    /// Sets the pixel at `x`, `y` to the RGBA color `rgba`, converted to the format of the icon.
+    ///
+    /// Returns `false` and leaves the icon unchanged if the pixel is outside the icon,
+    /// or if the pixel buffer is too short.
+    ///
+    /// # Example
+    /// ```
+    /// use file_icon_provider::Icon;
+    ///
+    /// let mut icon = Icon::new(2, 2, vec![0; 2 * 2 * 4]).unwrap();
+    ///
+    /// assert!(icon.set_pixel(1, 1, [255, 0, 0, 255]));
+    /// assert!(!icon.set_pixel(0, 2, [255, 0, 0, 255]));
+    /// assert_eq!(icon.get_pixel(1, 1), Some([255, 0, 0, 255]));
+    /// ```
+    pub fn set_pixel(&mut self, x: u32, y: u32, rgba: [u8; 4]) -> bool {
+        let pixel = self.format.swizzle(rgba);
+
+        match self
+            .pixel_offset(x, y)
+            .and_then(|offset| self.pixels.get_mut(offset..offset + 4))
+        {
+            Some(destination) => {
+                destination.copy_from_slice(&pixel);
+                true
             }
-            Error::NullIconSize => {
-                write!(f, "Null icon size")
+            None => false,
+        }
+    }
+
+    /// Returns the offset of the first byte of the pixel at `x`, `y`, or `None` if it is outside the icon.
+    fn pixel_offset(&self, x: u32, y: u32) -> Option<usize> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
+        Some(y as usize * self.bytes_per_row() + x as usize * 4)
+    }
+
+    /// Returns a hash of the dimensions, the format and the pixels, to tell cheaply if an icon changed.
+    ///
+    /// The hash is computed with [`DefaultHasher`], it is only stable within a process:
+    /// it must not be stored or compared with the hashes of another version of the program.
+    ///
+    /// # Example
+    /// ```
+    /// use file_icon_provider::Icon;
+    ///
+    /// let icon = Icon::new(1, 1, vec![1, 2, 3, 4]).unwrap();
+    /// let other = Icon::new(1, 1, vec![4, 3, 2, 1]).unwrap();
+    ///
+    /// assert_eq!(icon.content_hash(), icon.clone().content_hash());
+    /// assert_ne!(icon.content_hash(), other.content_hash());
+    /// ```
+    #[must_use]
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Returns the icon with its pixels in `format`, the pixels are only reordered if the format differs.
+    ///
+    /// # Example
+    /// ```
+    /// use file_icon_provider::{Icon, PixelFormat};
+    ///
+    /// let icon = Icon::new(1, 1, vec![1, 2, 3, 4]).unwrap().into_format(PixelFormat::Bgra8);
+    ///
+    /// assert_eq!(icon.pixels, [3, 2, 1, 4]);
+    /// ```
+    #[must_use]
+    pub fn into_format(mut self, format: PixelFormat) -> Icon {
+        if self.format != format {
+            // Swapping red and blue converts in both directions.
+            for pixel in self.rows_mut().flat_map(|row| row.chunks_exact_mut(4)) {
+                pixel.swap(0, 2);
             }
+            self.format = format;
+        }
+
+        self
+    }
+
+    /// Returns the tightly packed pixels in RGBA, they are only copied if the icon has another format
+    /// or padded rows.
+    #[cfg_attr(not(any(feature = "png", feature = "terminal")), allow(dead_code))]
+    pub(crate) fn rgba_pixels(&self) -> Cow<'_, [u8]> {
+        match (self.format, self.packed()) {
+            (PixelFormat::Rgba8, Cow::Borrowed(icon)) => Cow::Borrowed(&icon.pixels),
+            (_, icon) => Cow::Owned(icon.into_owned().into_format(PixelFormat::Rgba8).pixels),
+        }
+    }
+
+    /// Fails with [`Error::InvalidBuffer`] if the length of the pixel buffer does not match the dimensions.
+    pub(crate) fn check_buffer(&self) -> Result<(), Error> {
+        if self.has_valid_buffer() {
+            return Ok(());
         }
+
+        Err(Error::InvalidBuffer {
+            expected: self
+                .bytes_per_row()
+                .max(self.width as usize * 4)
+                .saturating_mul(self.height as usize),
+            actual: self.pixels.len(),
+        })
+    }
+
+    /// Returns `true` if the length of the pixel buffer matches the dimensions and the stride.
+    pub(crate) fn has_valid_buffer(&self) -> bool {
+        let (Ok(len), Ok(bytes_per_row)) = (
+            u64::try_from(self.pixels.len()),
+            u64::try_from(self.bytes_per_row()),
+        ) else {
+            return false;
+        };
+
+        bytes_per_row >= u64::from(self.width) * 4
+            && bytes_per_row.checked_mul(u64::from(self.height)) == Some(len)
+    }
+}
+
+impl std::fmt::Debug for Icon {
+    /// The pixels are not printed, only their count.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Icon")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("pixels", &format_args!("[u8; {}]", self.pixels.len()))
+            .field("format", &self.format)
+            .field("stride", &self.stride)
+            .finish()
     }
 }
 
-impl std::error::Error for Error {}
+impl From<Icon> for Vec<u8> {
+    fn from(icon: Icon) -> Self {
+        icon.into_pixels()
+    }
+}
 
-/// Retrieves the icon for a given file.
+/// The appearance icons are rendered for.
 ///
-/// # Parameters
-/// * `path` - A file path for which the icon is to be retrieved.
-/// * `size` - Desired icon size, must be greater than 0.
-/// # Returns
-/// * `Ok(Icon)` - If the icon is successfully retrieved.
-/// * `Err(Error)` - If the icon could not be retrieved.
+/// Only the `MacOS` backend renders icons differently depending on the color scheme,
+/// the other platforms ignore it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum ColorScheme {
+    /// The current appearance of the system.
+    #[default]
+    System,
+    /// The light appearance.
+    Light,
+    /// The dark appearance.
+    Dark,
+}
+
+/// The order of the channels of the pixels of an [`Icon`], 8 bits per channel.
 ///
-/// # Errors
-/// See [`Error`] for the reasons of failures.
+/// Icons are RGBA unless another format is requested, for example with [`get_file_icon_format`].
+/// BGRA is the native order of Windows bitmaps and of many GPU texture formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PixelFormat {
+    /// Red, green, blue, alpha.
+    #[default]
+    Rgba8,
+    /// Blue, green, red, alpha.
+    Bgra8,
+}
+
+impl PixelFormat {
+    /// Reorders the channels of a color between RGBA and this format, it is the same in both directions.
+    pub(crate) fn swizzle(self, [red, green, blue, alpha]: [u8; 4]) -> [u8; 4] {
+        match self {
+            PixelFormat::Rgba8 => [red, green, blue, alpha],
+            PixelFormat::Bgra8 => [blue, green, red, alpha],
+        }
+    }
+}
+
+/// How the icons smaller than the requested size are returned, see [`get_file_icon_with_scale_mode`].
+///
+/// Only the Windows backend honors the scale mode, the other platforms always return
+/// the icons at the requested size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum ScaleMode {
+    /// The icons are upscaled to the requested size, they can be blurry.
+    #[default]
+    ScaleUp,
+    /// The icons keep their native size when the file has no icon as large as requested,
+    /// the returned icon can be smaller than the requested size.
+    Native,
+}
+
+/// A corner of an icon, where [`Icon::overlay`] draws a badge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Corner {
+    /// The top left corner.
+    TopLeft,
+    /// The top right corner.
+    TopRight,
+    /// The bottom left corner.
+    BottomLeft,
+    /// The bottom right corner, where file managers usually draw emblems.
+    #[default]
+    BottomRight,
+}
+
+/// How a [`Provider`] marks the icons of the hidden files, see [`ProviderBuilder::hidden_files`].
+///
+/// The hidden files are the files whose name starts with a dot, or on Windows
+/// the files with the hidden attribute.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub enum HiddenFiles {
+    /// The icons of the hidden files are returned unchanged.
+    #[default]
+    Unchanged,
+    /// The alpha of the icons of the hidden files is multiplied by `opacity / 255`.
+    Dimmed(u8),
+    /// The badge is drawn in a corner of the icons of the hidden files, see [`Icon::overlay`].
+    Badge(Icon, Corner),
+}
+
+impl HiddenFiles {
+    /// Returns `icon` marked as the icon of a hidden file.
+    fn mark(&self, icon: Icon) -> Icon {
+        match self {
+            HiddenFiles::Unchanged => icon,
+            HiddenFiles::Dimmed(opacity) => {
+                let mut icon = icon;
+
+                icon.fade(*opacity);
+                icon
+            }
+            HiddenFiles::Badge(badge, corner) => icon.overlay(badge, *corner),
+        }
+    }
+}
+
+/// Returns `true` if `path` is hidden: its name starts with a dot, or on Windows it has the hidden attribute.
+fn is_hidden(path: &Path) -> bool {
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::fs::MetadataExt;
+
+        const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+
+        path.metadata()
+            .is_ok_and(|metadata| metadata.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0)
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        path.file_name()
+            .is_some_and(|name| name.as_encoded_bytes().starts_with(b"."))
+    }
+}
+
+/// An icon size in pixels that can't be null.
+///
+/// Functions taking an `IconSize`, like [`get_file_icon_with_size`] and [`Provider::with_size`],
+/// never fail with [`Error::NullIconSize`].
 ///
 /// # Example
 /// ```
-/// use file_icon_provider::get_file_icon;
+/// use file_icon_provider::IconSize;
 ///
-/// if let Ok(icon) = get_file_icon("path/to/file", 64) {
-///     println!("Icon dimensions: {}x{}", icon.width, icon.height);
-/// } else {
-///     println!("Failed to retrieve the icon.");
-/// }
+/// assert_eq!(IconSize::new(32).map(IconSize::get), Some(32));
+/// assert_eq!(IconSize::new(0), None);
 /// ```
-///
-/// # Caveats
-///
-/// On linux, this function must be called on the main thread.
-pub fn get_file_icon(path: impl AsRef<Path>, size: u16) -> Result<Icon, Error> {
-    // For consistency: on MacOS if the path does not exist None is returned
-    // but on Windows a default icon is returned.
-    if !path.as_ref().exists() {
-        return Err(Error::PathDoesNotExist);
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct IconSize(NonZeroU16);
+
+impl IconSize {
+    /// Returns `None` if `size` is 0.
+    #[must_use]
+    pub const fn new(size: u16) -> Option<Self> {
+        match NonZeroU16::new(size) {
+            Some(size) => Some(Self(size)),
+            None => None,
+        }
     }
 
-    if size == 0 {
-        return Err(Error::NullIconSize);
+    /// Returns the size in pixels.
+    #[must_use]
+    pub const fn get(self) -> u16 {
+        self.0.get()
+    }
+}
+
+impl From<NonZeroU16> for IconSize {
+    fn from(size: NonZeroU16) -> Self {
+        Self(size)
     }
+}
 
-    implementation::get_file_icon(path, size).ok_or(Error::Failed)
+impl From<IconSize> for u16 {
+    fn from(size: IconSize) -> Self {
+        size.get()
+    }
 }
 
-/// Provides icons.  
-/// # Type Parameters
-/// * `T` - The type of the final image to be displayed. It must be clonable. If your image type can't be cloned
-///   use a smart pointer (Rc).  
+impl TryFrom<u16> for IconSize {
+    type Error = Error;
+
+    /// Fails with [`Error::NullIconSize`] if `size` is 0.
+    fn try_from(size: u16) -> Result<Self, Self::Error> {
+        Self::new(size).ok_or(Error::NullIconSize)
+    }
+}
+
+/// Where the icon returned by [`get_file_icon_detailed`] comes from.
 ///
-/// Provider is interesting if you request a lot of icons with a fixed size. \
-/// It allocates internal buffers once and reuse them when the platform allows it (on `MacOS`).
-/// It caches icons reducing the CPU and memory usage.  
-pub struct Provider<T: Clone> {
-    implementation: implementation::Provider<T>,
+/// The source is inferred by each platform from the kind of file, it is a hint for debugging
+/// and user interfaces, not a guarantee.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IconSource {
+    /// The icon belongs to this file only, for example the icon embedded in an executable,
+    /// the icon of a shortcut or of an application bundle.
+    FileSpecific,
+    /// The icon is shared by the files of the same type, for example all the PNG images.
+    TypeAssociation,
+    /// The type of the file is unknown, the icon is the generic document icon.
+    Generic,
+    /// The icon is a preview of the content of the file, like the images of [`get_file_thumbnail`].
+    Thumbnail,
 }
 
-impl<T> Provider<T>
-where
-    T: Clone,
-{
-    /// Creates a new Provider
-    /// # Errors
-    /// See [`Error`] for the reasons of failures.
-    pub fn new(icon_size: u16, converter: fn(Icon) -> T) -> Result<Self, Error> {
-        if icon_size == 0 {
-            return Err(Error::NullIconSize);
+/// A special folder of the user or of the system, see [`get_known_folder_icon`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum KnownFolder {
+    /// The home folder of the user.
+    Home,
+    Desktop,
+    Documents,
+    Downloads,
+    Music,
+    Pictures,
+    Videos,
+    /// The Recycle Bin on Windows, the Trash on `MacOS` and Linux.
+    RecycleBin,
+    /// The virtual folder listing the drives, "This PC" on Windows and "Computer" on Linux.
+    ThisPc,
+}
+
+impl KnownFolder {
+    /// Names of the icon of the folder in the freedesktop icon naming specification, from the most specific.
+    #[cfg_attr(any(not(target_os = "linux"), feature = "stub"), allow(dead_code))]
+    pub(crate) fn icon_names(self) -> &'static [&'static str] {
+        match self {
+            KnownFolder::Home => &["user-home", "folder"],
+            KnownFolder::Desktop => &["user-desktop", "folder"],
+            KnownFolder::Documents => &["folder-documents", "folder"],
+            KnownFolder::Downloads => &["folder-download", "folder"],
+            KnownFolder::Music => &["folder-music", "folder"],
+            KnownFolder::Pictures => &["folder-pictures", "folder"],
+            KnownFolder::Videos => &["folder-videos", "folder"],
+            KnownFolder::RecycleBin => &["user-trash"],
+            KnownFolder::ThisPc => &["computer"],
+        }
+    }
+}
+
+/// The type of a file, returned by [`get_file_type_info`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileTypeInfo {
+    /// The identifier of the type: the uniform type identifier on `MacOS`, like "com.adobe.pdf",
+    /// the extension on Windows, like ".pdf", and the MIME type on Linux, like "application/pdf".
+    pub identifier: String,
+    /// The description of the type shown to the user, like "PDF Document".
+    pub description: String,
+    /// The icon of the file, `None` if the type is known but the icon can't be retrieved.
+    pub icon: Option<Icon>,
+}
+
+/// Settings forwarded to the platform implementation.
+#[derive(Debug, Clone)]
+pub(crate) struct Options {
+    pub(crate) color_scheme: ColorScheme,
+    /// Name of the icon theme to use instead of the default one.
+    #[cfg_attr(any(not(target_os = "linux"), feature = "stub"), allow(dead_code))]
+    pub(crate) theme: Option<String>,
+    /// Number of physical pixels per logical pixel, applied by the providers.
+    pub(crate) scale: f32,
+    /// Token checked by the implementation to stop the request early.
+    pub(crate) cancellation: Option<CancellationToken>,
+    /// Order of the channels of the icons, only the Windows backend returns it directly.
+    #[cfg_attr(any(not(target_os = "windows"), feature = "stub"), allow(dead_code))]
+    pub(crate) format: PixelFormat,
+    /// Maximum time to wait for the image factory thread, only used by the Windows backend.
+    #[cfg_attr(any(not(target_os = "windows"), feature = "stub"), allow(dead_code))]
+    pub(crate) timeout: Duration,
+    /// Foreground color of the symbolic icons to prefer, only used by the Linux backends.
+    #[cfg_attr(any(not(target_os = "linux"), feature = "stub"), allow(dead_code))]
+    pub(crate) symbolic: Option<[u8; 4]>,
+    /// Requests the icons as a per-monitor DPI aware application, only used by the Windows backend.
+    #[cfg_attr(any(not(target_os = "windows"), feature = "stub"), allow(dead_code))]
+    pub(crate) dpi_aware: bool,
+    /// Keeps the colors premultiplied by the alpha, only the macOS backend returns them directly.
+    #[cfg_attr(any(not(target_os = "macos"), feature = "stub"), allow(dead_code))]
+    pub(crate) premultiplied: bool,
+    /// Upscales the icons smaller than the requested size, only used by the Windows backend.
+    #[cfg_attr(any(not(target_os = "windows"), feature = "stub"), allow(dead_code))]
+    pub(crate) scale_mode: ScaleMode,
+}
+
+/// Default maximum time to wait for the system to return an icon, see [`get_file_icon_timeout`].
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            color_scheme: ColorScheme::default(),
+            theme: None,
+            scale: 1.0,
+            cancellation: None,
+            format: PixelFormat::default(),
+            timeout: DEFAULT_TIMEOUT,
+            symbolic: None,
+            dpi_aware: false,
+            premultiplied: false,
+            scale_mode: ScaleMode::default(),
         }
+    }
+}
+
+impl Options {
+    /// Returns the size in physical pixels of an icon of `size` logical pixels.
+    pub(crate) fn scaled_size(&self, size: u16) -> u16 {
+        scaled_size(size, self.scale)
+    }
+
+    /// Returns [`Error::Cancelled`] if the request was cancelled.
+    pub(crate) fn check_cancelled(&self) -> Result<(), Error> {
+        match &self.cancellation {
+            Some(token) if token.is_cancelled() => Err(Error::Cancelled),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Handle used to cancel icon requests that are no longer needed,
+/// for example the icons of files scrolled out of view.
+///
+/// Clones share the same state, cancelling one of them cancels all of them.
+/// See [`get_file_icon_cancellable`].
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    state: Arc<CancellationState>,
+}
+
+#[derive(Default)]
+struct CancellationState {
+    cancelled: AtomicBool,
+    /// Called once when the token is cancelled, to interrupt the platform calls in progress.
+    callbacks: Mutex<Vec<(u64, CancellationCallback)>>,
+    next_callback_id: AtomicU64,
+}
+
+type CancellationCallback = Box<dyn FnOnce() + Send>;
+
+impl CancellationToken {
+    /// Creates a token that is not cancelled.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels the requests using this token.
+    ///
+    /// Requests that are already finished are not affected.
+    pub fn cancel(&self) {
+        self.state.cancelled.store(true, Ordering::Release);
+
+        let callbacks = std::mem::take(&mut *self.callbacks());
+
+        for (_, callback) in callbacks {
+            callback();
+        }
+    }
+
+    /// Returns `true` if [`CancellationToken::cancel`] was called.
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.state.cancelled.load(Ordering::Acquire)
+    }
+
+    /// Calls `callback` when the token is cancelled, until the returned guard is dropped.
+    ///
+    /// `callback` is called immediately if the token is already cancelled.
+    #[cfg_attr(
+        not(all(
+            target_os = "linux",
+            not(feature = "linux-freedesktop"),
+            not(feature = "stub")
+        )),
+        allow(dead_code)
+    )]
+    pub(crate) fn on_cancel(&self, callback: impl FnOnce() + Send + 'static) -> OnCancelGuard<'_> {
+        let id = self.state.next_callback_id.fetch_add(1, Ordering::Relaxed);
+        let mut callbacks = self.callbacks();
+
+        if self.is_cancelled() {
+            drop(callbacks);
+            callback();
+        } else {
+            callbacks.push((id, Box::new(callback)));
+        }
+
+        OnCancelGuard { token: self, id }
+    }
+
+    fn callbacks(&self) -> MutexGuard<'_, Vec<(u64, CancellationCallback)>> {
+        self.state
+            .callbacks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl std::fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CancellationToken")
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
+
+/// Unregisters a callback of [`CancellationToken::on_cancel`] when dropped.
+pub(crate) struct OnCancelGuard<'a> {
+    token: &'a CancellationToken,
+    id: u64,
+}
+
+impl Drop for OnCancelGuard<'_> {
+    fn drop(&mut self) {
+        self.token.callbacks().retain(|(id, _)| *id != self.id);
+    }
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn scaled_size(size: u16, scale: f32) -> u16 {
+    (f32::from(size) * scale)
+        .round()
+        .clamp(1.0, f32::from(u16::MAX)) as u16
+}
+
+fn is_valid_scale(scale: f32) -> bool {
+    scale.is_finite() && scale > 0.0
+}
+
+/// Represents an error
+#[derive(Debug)]
+pub enum Error {
+    /// Retrieving the icon failed, the underlying platform error is available with
+    /// [`std::error::Error::source`]
+    Failed(Box<dyn std::error::Error + Send + Sync>),
+    /// The path does not exist
+    PathDoesNotExist,
+    /// The desired icon size is null
+    NullIconSize,
+    /// Converting the icon failed, for example in the converter of a [`Provider`]
+    Conversion(Box<dyn std::error::Error + Send + Sync>),
+    /// Writing or encoding a file failed
+    Io(std::io::Error),
+    /// The request was cancelled with a [`CancellationToken`]
+    Cancelled,
+    /// The operation is not available on this platform or with this backend,
+    /// for example the icon resources of [`get_icon_from_resource`] outside of Windows
+    Unsupported,
+    /// The system did not answer in time, see [`get_file_icon_timeout`]
+    Timeout,
+    /// The length of a pixel buffer does not match the dimensions of the icon, see [`Icon::from_rgba`]
+    InvalidBuffer {
+        /// The length matching the dimensions, `width * height * 4`.
+        expected: usize,
+        /// The length of the pixel buffer.
+        actual: usize,
+    },
+    /// The backend of the platform could not be initialized, with its message.
+    /// On Linux, GTK fails this way when no display is available
+    Backend(String),
+    /// The scale is not a positive number, see [`get_file_icon_with_scale`].
+    /// A DPI of 0 is reported as a scale of 0
+    InvalidScale(f32),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::Failed(error) => {
+                write!(f, "Failed to get icon: {error}")
+            }
+            Error::PathDoesNotExist => {
+                write!(f, "Path does not exist")
+            }
+            Error::NullIconSize => {
+                write!(f, "Null icon size")
+            }
+            Error::Conversion(error) => {
+                write!(f, "Failed to convert icon: {error}")
+            }
+            Error::Io(error) => {
+                write!(f, "I/O error: {error}")
+            }
+            Error::Cancelled => {
+                write!(f, "Cancelled")
+            }
+            Error::Unsupported => {
+                write!(f, "Operation not supported on this platform")
+            }
+            Error::Timeout => {
+                write!(f, "Timed out")
+            }
+            Error::InvalidBuffer { expected, actual } => {
+                write!(
+                    f,
+                    "Invalid pixel buffer length: expected {expected} bytes, got {actual}"
+                )
+            }
+            Error::Backend(message) => {
+                write!(f, "Failed to initialize the backend: {message}")
+            }
+            Error::InvalidScale(scale) => {
+                write!(f, "Invalid scale {scale}, it must be a positive number")
+            }
+        }
+    }
+}
+
+impl Error {
+    pub(crate) fn failed(error: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Self {
+        Error::Failed(error.into())
+    }
+
+    /// Returns a copy of the error for the requests sharing a lookup,
+    /// the underlying errors are replaced by their message.
+    pub(crate) fn duplicate(&self) -> Self {
+        match self {
+            Error::Failed(error) => Error::failed(error.to_string()),
+            Error::PathDoesNotExist => Error::PathDoesNotExist,
+            Error::NullIconSize => Error::NullIconSize,
+            Error::Conversion(error) => Error::Conversion(error.to_string().into()),
+            Error::Io(error) => Error::Io(std::io::Error::new(error.kind(), error.to_string())),
+            Error::Cancelled => Error::Cancelled,
+            Error::Unsupported => Error::Unsupported,
+            Error::Timeout => Error::Timeout,
+            Error::InvalidBuffer { expected, actual } => Error::InvalidBuffer {
+                expected: *expected,
+                actual: *actual,
+            },
+            Error::Backend(message) => Error::Backend(message.clone()),
+            Error::InvalidScale(scale) => Error::InvalidScale(*scale),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Failed(error) | Error::Conversion(error) => Some(error.as_ref()),
+            Error::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Retrieves the icon for a given file.
+///
+/// The pixels are RGBA with straight alpha, use [`get_file_icon_premultiplied`] to
+/// get colors premultiplied by their alpha.
+///
+/// # Parameters
+/// * `path` - A file path for which the icon is to be retrieved.
+/// * `size` - Desired icon size, must be greater than 0.
+/// # Returns
+/// * `Ok(Icon)` - If the icon is successfully retrieved.
+/// * `Err(Error)` - If the icon could not be retrieved.
+///
+/// # Errors
+/// See [`Error`] for the reasons of failures.
+///
+/// # Example
+/// ```
+/// use file_icon_provider::get_file_icon;
+///
+/// if let Ok(icon) = get_file_icon("path/to/file", 64) {
+///     println!("Icon dimensions: {}x{}", icon.width, icon.height);
+/// } else {
+///     println!("Failed to retrieve the icon.");
+/// }
+/// ```
+///
+/// # Caveats
+///
+/// On linux, this function must be called on the main thread.
+pub fn get_file_icon(path: impl AsRef<Path>, size: u16) -> Result<Icon, Error> {
+    get_file_icon_with_options(path.as_ref(), size, &Options::default())
+}
+
+/// Retrieves the icon for a given file without checking that it exists, the backend decides.
+///
+/// Unlike [`get_file_icon`], the existence of the path is not checked, which saves a system call.
+/// It accepts paths that are not in the file system:
+/// * Windows: the items of the shell namespace, like the files inside ZIP folders.
+/// * Linux: with the `linux-gtk` feature, the URIs of gio like `smb://server/share/file.txt`.
+///
+/// The result for a path that does not exist depends on the backend:
+/// Windows returns a generic icon for its extension, the other platforms fail.
+///
+/// # Errors
+/// See [`Error`] for the reasons of failures.
+///
+/// # Example
+/// ```
+/// use file_icon_provider::get_file_icon_unchecked;
+///
+/// if let Ok(icon) = get_file_icon_unchecked("smb://server/share/file.txt", 64) {
+///     println!("Icon dimensions: {}x{}", icon.width, icon.height);
+/// }
+/// ```
+///
+/// # Caveats
+///
+/// On linux, this function must be called on the main thread.
+pub fn get_file_icon_unchecked(path: impl AsRef<Path>, size: u16) -> Result<Icon, Error> {
+    if size == 0 {
+        return Err(Error::NullIconSize);
+    }
+
+    let path = path.as_ref();
+
+    implementation::get_file_icon(path, size, &Options::default())
+        .inspect_err(|error| log_failure(path, size, error))
+}
+
+/// Retrieves the icon for a given file, with a size that can't be null.
+///
+/// Like [`get_file_icon`], but it never fails with [`Error::NullIconSize`].
+///
+/// # Errors
+/// See [`Error`] for the reasons of failures.
+///
+/// # Example
+/// ```
+/// use file_icon_provider::{IconSize, get_file_icon_with_size};
+///
+/// const SIZE: IconSize = IconSize::new(64).unwrap();
+///
+/// if let Ok(icon) = get_file_icon_with_size("path/to/file", SIZE) {
+///     println!("Icon dimensions: {}x{}", icon.width, icon.height);
+/// }
+/// ```
+///
+/// # Caveats
+///
+/// On linux, this function must be called on the main thread.
+pub fn get_file_icon_with_size(path: impl AsRef<Path>, size: IconSize) -> Result<Icon, Error> {
+    get_file_icon_with_options(path.as_ref(), size.get(), &Options::default())
+}
+
+/// Retrieves the icon for a given file, rendered for a color scheme.
+///
+/// Only `MacOS` honors the color scheme, see [`ColorScheme`].
+///
+/// # Errors
+/// See [`Error`] for the reasons of failures.
+///
+/// # Example
+/// ```
+/// use file_icon_provider::{ColorScheme, get_file_icon_with_color_scheme};
+///
+/// if let Ok(icon) = get_file_icon_with_color_scheme("path/to/file", 64, ColorScheme::Dark) {
+///     println!("Icon dimensions: {}x{}", icon.width, icon.height);
+/// }
+/// ```
+///
+/// # Caveats
+///
+/// On linux, this function must be called on the main thread.
+pub fn get_file_icon_with_color_scheme(
+    path: impl AsRef<Path>,
+    size: u16,
+    color_scheme: ColorScheme,
+) -> Result<Icon, Error> {
+    let options = Options {
+        color_scheme,
+        ..Options::default()
+    };
+
+    get_file_icon_with_options(path.as_ref(), size, &options)
+}
+
+/// Retrieves the icon for a given file with its pixels in `format`.
+///
+/// The Windows backend gets BGRA pixels from the system, requesting [`PixelFormat::Bgra8`]
+/// there skips the conversion to RGBA. The other platforms reorder the pixels on demand.
+///
+/// # Errors
+/// See [`Error`] for the reasons of failures.
+///
+/// # Example
+/// ```
+/// use file_icon_provider::{PixelFormat, get_file_icon_format};
+///
+/// if let Ok(icon) = get_file_icon_format("path/to/file", 64, PixelFormat::Bgra8) {
+///     assert_eq!(icon.format, PixelFormat::Bgra8);
+/// }
+/// ```
+///
+/// # Caveats
+///
+/// On linux, this function must be called on the main thread.
+pub fn get_file_icon_format(
+    path: impl AsRef<Path>,
+    size: u16,
+    format: PixelFormat,
+) -> Result<Icon, Error> {
+    let options = Options {
+        format,
+        ..Options::default()
+    };
+
+    get_file_icon_with_options(path.as_ref(), size, &options).map(|icon| icon.into_format(format))
+}
+
+/// Retrieves the icon for a given file with its colors premultiplied by their alpha,
+/// as expected by most GPU compositors.
+///
+/// The macOS backend draws premultiplied pixels, requesting them there skips the conversion
+/// to straight alpha. The other platforms premultiply the pixels on demand,
+/// like [`Icon::premultiplied`].
+///
+/// # Errors
+/// See [`Error`] for the reasons of failures.
+///
+/// # Example
+/// ```
+/// use file_icon_provider::get_file_icon_premultiplied;
+///
+/// if let Ok(icon) = get_file_icon_premultiplied("path/to/file", 64) {
+///     // The colors are never brighter than the alpha.
+///     assert!(icon.pixels.chunks(4).all(|pixel| pixel[..3].iter().all(|c| *c <= pixel[3])));
+/// }
+/// ```
+///
+/// # Caveats
+///
+/// On linux, this function must be called on the main thread.
+pub fn get_file_icon_premultiplied(path: impl AsRef<Path>, size: u16) -> Result<Icon, Error> {
+    let options = Options {
+        premultiplied: true,
+        ..Options::default()
+    };
+    let icon = get_file_icon_with_options(path.as_ref(), size, &options)?;
+
+    if cfg!(all(target_os = "macos", not(feature = "stub"))) {
+        Ok(icon)
+    } else {
+        Ok(icon.premultiplied())
+    }
+}
+
+/// Retrieves the icon for a given file, waiting at most `timeout` for the system.
+///
+/// Only the Windows backend honors the timeout: the icons are retrieved by a worker thread which
+/// can be blocked by a slow network drive or an unresponsive shell extension.
+/// On Windows, the other functions wait at most 5 seconds.
+///
+/// # Errors
+/// See [`Error`] for the reasons of failures. \
+/// [`Error::Timeout`] is returned if the icon was not retrieved in time.
+///
+/// # Example
+/// ```
+/// use file_icon_provider::get_file_icon_timeout;
+/// use std::time::Duration;
+///
+/// if let Ok(icon) = get_file_icon_timeout("path/to/file", 64, Duration::from_secs(1)) {
+///     println!("Icon dimensions: {}x{}", icon.width, icon.height);
+/// }
+/// ```
+///
+/// # Caveats
+///
+/// On linux, this function must be called on the main thread.
+pub fn get_file_icon_timeout(
+    path: impl AsRef<Path>,
+    size: u16,
+    timeout: Duration,
+) -> Result<Icon, Error> {
+    let options = Options {
+        timeout,
+        ..Options::default()
+    };
+
+    get_file_icon_with_options(path.as_ref(), size, &options)
+}
+
+/// Retrieves the icon for a given file from the icon theme named `theme_name`.
+///
+/// Only the Linux backend honors the theme, other platforms ignore it.
+/// If the theme can't be loaded or has no icon for the file, the default theme is used.
+///
+/// # Errors
+/// See [`Error`] for the reasons of failures.
+///
+/// # Example
+/// ```
+/// use file_icon_provider::get_file_icon_with_theme;
+///
+/// if let Ok(icon) = get_file_icon_with_theme("path/to/file", 64, "Adwaita") {
+///     println!("Icon dimensions: {}x{}", icon.width, icon.height);
+/// }
+/// ```
+///
+/// # Caveats
+///
+/// On linux, this function must be called on the main thread.
+pub fn get_file_icon_with_theme(
+    path: impl AsRef<Path>,
+    size: u16,
+    theme_name: &str,
+) -> Result<Icon, Error> {
+    let options = Options {
+        theme: Some(theme_name.to_owned()),
+        ..Options::default()
+    };
+
+    get_file_icon_with_options(path.as_ref(), size, &options)
+}
+
+/// Retrieves the symbolic icon for a given file, painted with `rgba_foreground`.
+///
+/// Symbolic icons are monochrome variants of the icons, like "text-x-generic-symbolic",
+/// meant to be tinted with the color of the text.
+/// Only the Linux backends honor this, other platforms return the normal icon.
+/// If the icon theme has no symbolic variant, the normal icon is returned.
+///
+/// # Errors
+/// See [`Error`] for the reasons of failures.
+///
+/// # Example
+/// ```
+/// use file_icon_provider::get_file_icon_symbolic;
+///
+/// if let Ok(icon) = get_file_icon_symbolic("path/to/file", 16, [255, 255, 255, 255]) {
+///     println!("Icon dimensions: {}x{}", icon.width, icon.height);
+/// }
+/// ```
+///
+/// # Caveats
+///
+/// On linux, this function must be called on the main thread.
+pub fn get_file_icon_symbolic(
+    path: impl AsRef<Path>,
+    size: u16,
+    rgba_foreground: [u8; 4],
+) -> Result<Icon, Error> {
+    let options = Options {
+        symbolic: Some(rgba_foreground),
+        ..Options::default()
+    };
+
+    get_file_icon_with_options(path.as_ref(), size, &options)
+}
+
+/// Retrieves the icon of a file at several sizes, for example to build a multi-resolution asset.
+///
+/// # Parameters
+/// * `path` - A file path for which the icons are to be retrieved.
+/// * `sizes` - Desired icon sizes, all must be greater than 0.
+/// # Returns
+/// * `Ok(Vec<Icon>)` - The icons, in the same order as `sizes`.
+/// * `Err(Error)` - If one of the icons could not be retrieved.
+///
+/// # Errors
+/// See [`Error`] for the reasons of failures.
+///
+/// # Example
+/// ```
+/// use file_icon_provider::get_file_icon_sizes;
+///
+/// if let Ok(icons) = get_file_icon_sizes("path/to/file", &[16, 32, 48, 256]) {
+///     for icon in icons {
+///         println!("Icon dimensions: {}x{}", icon.width, icon.height);
+///     }
+/// }
+/// ```
+///
+/// # Caveats
+///
+/// On linux, this function must be called on the main thread. \
+/// On `MacOS`, the icon is drawn at the largest size and downscaled to the other sizes.
+pub fn get_file_icon_sizes(path: impl AsRef<Path>, sizes: &[u16]) -> Result<Vec<Icon>, Error> {
+    let path = path.as_ref();
+
+    if !path.exists() {
+        return Err(Error::PathDoesNotExist);
+    }
+
+    if sizes.contains(&0) {
+        return Err(Error::NullIconSize);
+    }
+
+    implementation::get_file_icon_sizes(path, sizes, &Options::default())
+}
+
+/// Retrieves a thumbnail showing the content of a file, for example a preview of a photo.
+///
+/// # Parameters
+/// * `path` - A file path for which the thumbnail is to be retrieved.
+/// * `size` - Desired thumbnail size, must be greater than 0.
+/// * `fallback_to_icon` - If true, the icon of the file is returned when it has no thumbnail.
+/// # Returns
+/// * `Ok(Icon)` - If the thumbnail, or the icon, is successfully retrieved.
+/// * `Err(Error)` - If the thumbnail could not be retrieved.
+///
+/// # Errors
+/// See [`Error`] for the reasons of failures. \
+/// [`Error::Failed`] is returned if the file has no thumbnail and `fallback_to_icon` is false. \
+/// [`Error::Unsupported`] is returned by the `linux-freedesktop` backend, which has no thumbnails,
+/// if `fallback_to_icon` is false.
+///
+/// # Example
+/// ```
+/// use file_icon_provider::get_file_thumbnail;
+///
+/// if let Ok(thumbnail) = get_file_thumbnail("path/to/photo.jpg", 128, true) {
+///     println!("Thumbnail dimensions: {}x{}", thumbnail.width, thumbnail.height);
+/// }
+/// ```
+///
+/// # Caveats
+///
+/// On linux, this function must be called on the main thread and only the thumbnails
+/// already cached by the file managers are found. \
+/// On `MacOS`, the thumbnail is stretched to a square of `size` pixels.
+pub fn get_file_thumbnail(
+    path: impl AsRef<Path>,
+    size: u16,
+    fallback_to_icon: bool,
+) -> Result<Icon, Error> {
+    let path = path.as_ref();
+
+    if !path.exists() {
+        return Err(Error::PathDoesNotExist);
+    }
+
+    if size == 0 {
+        return Err(Error::NullIconSize);
+    }
+
+    let options = Options::default();
+
+    match implementation::get_file_thumbnail(path, size, &options) {
+        Err(error @ (Error::Failed(_) | Error::Unsupported)) if fallback_to_icon => {
+            debug!("No thumbnail for '{}': {error}", path.display());
+            implementation::get_file_icon(path, size, &options)
+        }
+        result => result,
+    }
+}
+
+/// Retrieves the icon for a given file, for a display with `scale` physical pixels per logical pixel.
+///
+/// `size` is in logical pixels, the returned icon is `size * scale` pixels large so it is crisp on
+/// high density displays. Use the scale factor of the display, for example 2.0 on a Retina display.
+///
+/// # Errors
+/// See [`Error`] for the reasons of failures. \
+/// [`Error::InvalidScale`] is returned if `scale` is not a positive number.
+///
+/// # Example
+/// ```
+/// use file_icon_provider::get_file_icon_with_scale;
+///
+/// if let Ok(icon) = get_file_icon_with_scale("path/to/file", 16, 2.0) {
+///     println!("Icon dimensions: {}x{}", icon.width, icon.height);
+/// }
+/// ```
+///
+/// # Caveats
+///
+/// On linux, this function must be called on the main thread.
+pub fn get_file_icon_with_scale(
+    path: impl AsRef<Path>,
+    size: u16,
+    scale: f32,
+) -> Result<Icon, Error> {
+    if !is_valid_scale(scale) {
+        return Err(Error::InvalidScale(scale));
+    }
+
+    // The size is checked before scaling, a null size must not be rounded up.
+    if size == 0 {
+        return Err(Error::NullIconSize);
+    }
+
+    get_file_icon_with_options(path.as_ref(), scaled_size(size, scale), &Options::default())
+}
+
+/// Retrieves the icon for a given file, for a display of `dpi` dots per inch.
+///
+/// `size` is in logical pixels at 96 DPI, the returned icon is `size * dpi / 96` pixels large.
+/// Use the DPI of the monitor showing the icon, for example 144 at 150% or 192 at 200%.
+///
+/// On Windows, the icon is requested as a per-monitor DPI aware application, so the shell returns
+/// the bitmaps made for this density instead of upscaling the 96 DPI ones.
+/// Other platforms behave like [`get_file_icon_with_scale`].
+///
+/// # Errors
+/// See [`Error`] for the reasons of failures. \
+/// [`Error::InvalidScale`] is returned if `dpi` is 0.
+///
+/// # Example
+/// ```
+/// use file_icon_provider::get_file_icon_with_dpi;
+///
+/// if let Ok(icon) = get_file_icon_with_dpi("path/to/file", 32, 144) {
+///     println!("Icon dimensions: {}x{}", icon.width, icon.height);
+/// }
+/// ```
+///
+/// # Caveats
+///
+/// On linux, this function must be called on the main thread.
+pub fn get_file_icon_with_dpi(path: impl AsRef<Path>, size: u16, dpi: u32) -> Result<Icon, Error> {
+    if dpi == 0 {
+        return Err(Error::InvalidScale(0.0));
+    }
+
+    // The size is checked before scaling, a null size must not be rounded up.
+    if size == 0 {
+        return Err(Error::NullIconSize);
+    }
+
+    let options = Options {
+        dpi_aware: true,
+        ..Options::default()
+    };
+    let size = (u64::from(size) * u64::from(dpi) + 48) / 96;
+
+    get_file_icon_with_options(
+        path.as_ref(),
+        u16::try_from(size).unwrap_or(u16::MAX).max(1),
+        &options,
+    )
+}
+
+/// Retrieves the icon for a given file, choosing whether the icons smaller than `size` are upscaled.
+///
+/// On Windows, with [`ScaleMode::Native`] the shell returns the largest native icon of the file
+/// when it has none of the requested size, instead of an upscaled and blurry one.
+/// The returned icon has its real dimensions, which can be smaller than `size`.
+/// Other platforms behave like [`get_file_icon`].
+///
+/// # Errors
+/// See [`Error`] for the reasons of failures.
+///
+/// # Example
+/// ```
+/// use file_icon_provider::{ScaleMode, get_file_icon_with_scale_mode};
+///
+/// if let Ok(icon) = get_file_icon_with_scale_mode("path/to/file", 256, ScaleMode::Native) {
+///     assert!(icon.width <= 256 && icon.height <= 256);
+/// }
+/// ```
+///
+/// # Caveats
+///
+/// On linux, this function must be called on the main thread.
+pub fn get_file_icon_with_scale_mode(
+    path: impl AsRef<Path>,
+    size: u16,
+    scale_mode: ScaleMode,
+) -> Result<Icon, Error> {
+    let options = Options {
+        scale_mode,
+        ..Options::default()
+    };
+
+    get_file_icon_with_options(path.as_ref(), size, &options)
+}
+
+/// Retrieves the icon for a given file, unless the request is cancelled before the icon is ready.
+///
+/// The request stops as early as possible once `cancellation` is cancelled,
+/// which avoids wasting time on icons that are no longer needed.
+///
+/// # Errors
+/// See [`Error`] for the reasons of failures. \
+/// [`Error::Cancelled`] is returned if the request was cancelled.
+///
+/// # Example
+/// ```
+/// use file_icon_provider::{CancellationToken, Error, get_file_icon_cancellable};
+///
+/// let cancellation = CancellationToken::new();
+///
+/// // Usually called from another thread, when the icon is not needed anymore.
+/// cancellation.cancel();
+///
+/// assert!(matches!(
+///     get_file_icon_cancellable("Cargo.toml", 64, &cancellation),
+///     Err(Error::Cancelled)
+/// ));
+/// ```
+///
+/// # Caveats
+///
+/// On linux, this function must be called on the main thread.
+pub fn get_file_icon_cancellable(
+    path: impl AsRef<Path>,
+    size: u16,
+    cancellation: &CancellationToken,
+) -> Result<Icon, Error> {
+    let options = Options {
+        cancellation: Some(cancellation.clone()),
+        ..Options::default()
+    };
+
+    get_file_icon_with_options(path.as_ref(), size, &options)
+}
+
+/// Retrieves the icon for a given file, along with where the icon comes from.
+///
+/// See [`IconSource`] for the possible sources.
+///
+/// # Errors
+/// See [`Error`] for the reasons of failures.
+///
+/// # Example
+/// ```
+/// use file_icon_provider::{IconSource, get_file_icon_detailed};
+///
+/// if let Ok((icon, source)) = get_file_icon_detailed("path/to/file", 64) {
+///     if source == IconSource::Generic {
+///         println!("Unknown file type");
+///     }
+///     println!("Icon dimensions: {}x{}", icon.width, icon.height);
+/// }
+/// ```
+///
+/// # Caveats
+///
+/// On linux, this function must be called on the main thread.
+pub fn get_file_icon_detailed(
+    path: impl AsRef<Path>,
+    size: u16,
+) -> Result<(Icon, IconSource), Error> {
+    let path = path.as_ref();
+    let icon = get_file_icon_with_options(path, size, &Options::default())?;
+
+    Ok((icon, implementation::get_icon_source(path)))
+}
+
+/// Returns the sizes at which the icon of a file exists natively, in ascending order.
+///
+/// Requesting one of these sizes avoids the artifacts of scaling the icon.
+/// Scalable icons can be drawn at any size without artifacts, they are not listed:
+/// the list is empty if the icon only exists as a scalable image.
+///
+/// The sizes are:
+/// * `MacOS`: the pixel widths of the representations of the icon.
+/// * Windows: the sizes of the system image lists, 16, 32, 48 and 256.
+/// * Linux: the sizes of the icon in the icon theme.
+///
+/// # Errors
+/// See [`Error`] for the reasons of failures.
+///
+/// # Example
+/// ```
+/// use file_icon_provider::{available_sizes, get_file_icon};
+///
+/// if let Ok(sizes) = available_sizes("path/to/file") {
+///     // The smallest native size larger than 40 pixels, to downscale it.
+///     let size = sizes.into_iter().find(|size| *size >= 40).unwrap_or(40);
+///
+///     if let Ok(icon) = get_file_icon("path/to/file", size) {
+///         println!("Icon dimensions: {}x{}", icon.width, icon.height);
+///     }
+/// }
+/// ```
+///
+/// # Caveats
+///
+/// On linux, this function must be called on the main thread.
+pub fn available_sizes(path: impl AsRef<Path>) -> Result<Vec<u16>, Error> {
+    let path = path.as_ref();
+
+    if !path.exists() {
+        return Err(Error::PathDoesNotExist);
+    }
+
+    implementation::get_available_sizes(path, &Options::default())
+}
+
+/// Retrieves the icon of a file downscaled from its nearest native size, for crisp results.
+///
+/// The native size is chosen from [`available_sizes`]:
+/// the smallest size greater than or equal to `requested_size`, else the largest size available.
+/// The icon is then scaled with bilinear filtering so its largest side is `requested_size`.
+/// If the icon has no native size, because it is scalable, it is requested at `requested_size`.
+///
+/// Downscaling a larger icon gives sharper results than letting the system upscale a smaller one.
+///
+/// # Errors
+/// See [`Error`] for the reasons of failures.
+///
+/// # Example
+/// ```
+/// use file_icon_provider::get_file_icon_nearest;
+///
+/// if let Ok(icon) = get_file_icon_nearest("path/to/file", 40) {
+///     println!("Icon dimensions: {}x{}", icon.width, icon.height);
+/// }
+/// ```
+///
+/// # Caveats
+///
+/// On linux, this function must be called on the main thread.
+pub fn get_file_icon_nearest(path: impl AsRef<Path>, requested_size: u16) -> Result<Icon, Error> {
+    let path = path.as_ref();
+
+    if requested_size == 0 {
+        return Err(Error::NullIconSize);
+    }
+
+    let native_size = nearest_size(&available_sizes(path)?, requested_size);
+    let icon = get_file_icon_with_options(path, native_size, &Options::default())?;
+
+    if icon.width.max(icon.height) == u32::from(requested_size) {
+        return Ok(icon);
+    }
+
+    let requested_size = u32::from(requested_size);
+    let (width, height) = alpha::fit_within(&icon, requested_size, requested_size);
+
+    Ok(icon.resize_with_filter(width, height, ResizeFilter::Triangle))
+}
+
+/// Returns the smallest of the ascending `sizes` greater than or equal to `requested_size`,
+/// else the largest one, or `requested_size` if there is none.
+fn nearest_size(sizes: &[u16], requested_size: u16) -> u16 {
+    sizes
+        .iter()
+        .find(|size| **size >= requested_size)
+        .or(sizes.last())
+        .copied()
+        .unwrap_or(requested_size)
+}
+
+/// Retrieves the type of a file with its description and icon, for example to fill a details pane.
+///
+/// The type is looked up once for both the description and the icon.
+/// On Windows, the description is the friendly name of the file type registered by its application.
+///
+/// # Errors
+/// See [`Error`] for the reasons of failures.
+///
+/// # Example
+/// ```
+/// use file_icon_provider::get_file_type_info;
+///
+/// if let Ok(info) = get_file_type_info("path/to/file", 64) {
+///     println!("{} ({})", info.description, info.identifier);
+/// }
+/// ```
+///
+/// # Caveats
+///
+/// On linux, this function must be called on the main thread.
+pub fn get_file_type_info(path: impl AsRef<Path>, size: u16) -> Result<FileTypeInfo, Error> {
+    let path = path.as_ref();
+
+    if !path.exists() {
+        return Err(Error::PathDoesNotExist);
+    }
+
+    if size == 0 {
+        return Err(Error::NullIconSize);
+    }
+
+    implementation::get_file_type_info(path, size, &Options::default())
+}
+
+/// Retrieves the icon of a symbolic link: the icon of its target with the link emblem of the platform.
+///
+/// [`get_file_icon`] follows symbolic links, it returns the icon of the target without emblem.
+/// The emblem is the arrow of the shortcuts on Windows, the alias badge on `MacOS` and
+/// the "emblem-symbolic-link" icon of the icon theme on Linux.
+/// Broken links have the generic document icon with the emblem.
+///
+/// If `path` is not a symbolic link, the icon is the same as with [`get_file_icon`].
+///
+/// # Errors
+/// See [`Error`] for the reasons of failures.
+///
+/// # Example
+/// ```
+/// use file_icon_provider::get_symlink_icon;
+///
+/// if let Ok(icon) = get_symlink_icon("path/to/link", 64) {
+///     println!("Icon dimensions: {}x{}", icon.width, icon.height);
+/// }
+/// ```
+///
+/// # Caveats
+///
+/// On linux, this function must be called on the main thread.
+pub fn get_symlink_icon(path: impl AsRef<Path>, size: u16) -> Result<Icon, Error> {
+    let path = path.as_ref();
+    let options = Options::default();
+
+    if !path.is_symlink() {
+        return get_file_icon_with_options(path, size, &options);
+    }
+
+    if size == 0 {
+        return Err(Error::NullIconSize);
+    }
+
+    let mut icon = if path.exists() {
+        implementation::get_file_icon(path, size, &options)?
+    } else {
+        implementation::get_default_file_icon(size, &options)?
+    };
+    let overlay = implementation::get_link_overlay(size, &options)?;
+
+    icon.draw(
+        &overlay.resize_with_filter(icon.width, icon.height, ResizeFilter::Triangle),
+        0,
+        0,
+    );
+
+    Ok(icon)
+}
+
+/// Retrieves an icon resource of a Windows executable or library, like `C:\foo.dll,-5`
+/// in the registry or in shortcuts.
+///
+/// A positive or null `index` is the position of the icon among the icons of the file,
+/// a negative `index` is the identifier of the icon resource, `-5` is the resource 5.
+///
+/// # Errors
+/// See [`Error`] for the reasons of failures.
+/// Fails with [`Error::Unsupported`] on the other platforms, they have no icon resources.
+///
+/// # Example
+/// ```no_run
+/// use file_icon_provider::get_icon_from_resource;
+///
+/// let icon = get_icon_from_resource(r"C:\Windows\System32\shell32.dll", -4, 32)?;
+///
+/// println!("Icon dimensions: {}x{}", icon.width, icon.height);
+/// # Ok::<(), file_icon_provider::Error>(())
+/// ```
+pub fn get_icon_from_resource(
+    path: impl AsRef<Path>,
+    index: i32,
+    size: u16,
+) -> Result<Icon, Error> {
+    let path = path.as_ref();
+
+    if !path.exists() {
+        return Err(Error::PathDoesNotExist);
+    }
+
+    if size == 0 {
+        return Err(Error::NullIconSize);
+    }
+
+    implementation::get_icon_from_resource(path, index, size, &Options::default())
+}
+
+/// Retrieves the icon for a given file on the calling thread, without going through
+/// the image factory thread, if the calling thread is a COM single-threaded apartment.
+///
+/// Use it from threads that already initialized COM as single-threaded apartment,
+/// for example the UI thread of an application, to avoid handing the request over to another thread.
+/// On the other threads, it behaves like [`get_file_icon`].
+///
+/// # Errors
+/// See [`Error`] for the reasons of failures.
+///
+/// # Example
+/// ```no_run
+/// use file_icon_provider::get_file_icon_sta;
+///
+/// // COM is initialized by the application, for example by its windowing library.
+/// if let Ok(icon) = get_file_icon_sta("path/to/file", 64) {
+///     println!("Icon dimensions: {}x{}", icon.width, icon.height);
+/// }
+/// ```
+#[cfg(target_os = "windows")]
+pub fn get_file_icon_sta(path: impl AsRef<Path>, size: u16) -> Result<Icon, Error> {
+    let path = path.as_ref();
+
+    if !path.exists() {
+        return Err(Error::PathDoesNotExist);
+    }
+
+    if size == 0 {
+        return Err(Error::NullIconSize);
+    }
+
+    implementation::get_file_icon_sta(path, size, &Options::default())
+}
+
+/// Retrieves the icon of an item of the shell namespace, which may not exist in the file system.
+///
+/// The existence of the path is not checked, the shell resolves it as a parsing name instead.
+/// This gives the icons of the files inside ZIP folders, like `C:\stuff.zip\inner.txt`,
+/// and of the items of other shell extensions, like `::{26EE0668-A00A-44D7-9371-BEB064C98683}`
+/// for the control panel.
+///
+/// It is [`get_file_icon_unchecked`], under a name telling what it accepts on Windows.
+///
+/// # Errors
+/// Fails with [`Error::Failed`] if the shell can't resolve the parsing name,
+/// see [`Error`] for the other reasons of failures.
+///
+/// # Example
+/// ```no_run
+/// use file_icon_provider::get_shell_item_icon;
+///
+/// if let Ok(icon) = get_shell_item_icon(r"C:\stuff.zip\inner.txt", 64) {
+///     println!("Icon dimensions: {}x{}", icon.width, icon.height);
+/// }
+/// ```
+#[cfg(target_os = "windows")]
+pub fn get_shell_item_icon(parsing_name: impl AsRef<Path>, size: u16) -> Result<Icon, Error> {
+    get_file_icon_unchecked(parsing_name, size)
+}
+
+/// Stops the thread retrieving the icons on Windows, after it answered the pending requests.
+///
+/// The thread lives until the end of the process otherwise, which can be reported by leak
+/// detectors and prevents unloading a library using this crate.
+/// The thread is started again by the next request, so calling this function is always safe.
+/// The thread watching the settings of the system stops once the providers listening to it
+/// with [`Provider::on_theme_changed`] are dropped.
+///
+/// It waits a few seconds at most for the thread to stop, the thread is detached after that.
+///
+/// # Caveats
+///
+/// It must not be called while the loader lock is held, for example from `DllMain`:
+/// the thread can't exit until the lock is released, so the whole wait is spent
+/// and the thread is detached.
+///
+/// # Example
+/// ```no_run
+/// use file_icon_provider::{get_file_icon, shutdown_icon_worker};
+///
+/// let icon = get_file_icon("path/to/file", 64);
+///
+/// shutdown_icon_worker();
+/// ```
+#[cfg(target_os = "windows")]
+pub fn shutdown_icon_worker() {
+    implementation::shutdown_icon_worker();
+}
+
+/// Retrieves the icon of the file open with the descriptor `fd`, for example a [`std::fs::File`]
+/// or a descriptor received from another process.
+///
+/// The current path of the file is resolved from `/proc/self/fd`, then the icon is looked up
+/// from this path like with [`get_file_icon`]. If the file is renamed or replaced meanwhile,
+/// the icon can be the one of another file.
+///
+/// # Errors
+/// See [`Error`] for the reasons of failures. \
+/// [`Error::Failed`] is returned if `fd` is not the descriptor of a file. \
+/// [`Error::PathDoesNotExist`] is returned if the file was deleted after being opened.
+///
+/// # Example
+/// ```no_run
+/// use file_icon_provider::get_file_icon_from_fd;
+/// use std::fs::File;
+///
+/// let file = File::open("path/to/file")?;
+/// let icon = get_file_icon_from_fd(&file, 64)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+///
+/// # Caveats
+///
+/// On linux, this function must be called on the main thread.
+#[cfg(target_os = "linux")]
+pub fn get_file_icon_from_fd(fd: impl std::os::fd::AsFd, size: u16) -> Result<Icon, Error> {
+    get_file_icon(descriptor::fd_path(fd.as_fd())?, size)
+}
+
+/// Retrieves the icon of the file open with `handle`, for example a [`std::fs::File`]
+/// or a handle received from another process.
+///
+/// The current path of the file is resolved with `GetFinalPathNameByHandleW`, then the icon is
+/// looked up from this path like with [`get_file_icon`]. If the file is renamed or replaced
+/// meanwhile, the icon can be the one of another file.
+///
+/// # Errors
+/// See [`Error`] for the reasons of failures. \
+/// [`Error::Failed`] is returned if `handle` is not the handle of a file.
+///
+/// # Example
+/// ```no_run
+/// use file_icon_provider::get_file_icon_from_handle;
+/// use std::fs::File;
+///
+/// let file = File::open("path/to/file")?;
+/// let icon = get_file_icon_from_handle(&file, 64)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[cfg(target_os = "windows")]
+pub fn get_file_icon_from_handle(
+    handle: impl std::os::windows::io::AsHandle,
+    size: u16,
+) -> Result<Icon, Error> {
+    get_file_icon(descriptor::handle_path(handle.as_handle())?, size)
+}
+
+fn get_file_icon_with_options(path: &Path, size: u16, options: &Options) -> Result<Icon, Error> {
+    // For consistency: on MacOS if the path does not exist None is returned
+    // but on Windows a default icon is returned.
+    if !path.exists() {
+        return Err(Error::PathDoesNotExist);
+    }
+
+    if size == 0 {
+        return Err(Error::NullIconSize);
+    }
+
+    options.check_cancelled()?;
+
+    let icon = implementation::get_file_icon(path, size, options)
+        .inspect_err(|error| log_failure(path, size, error))?;
+
+    debug_assert!(
+        icon.has_valid_buffer(),
+        "{}x{} icon with {} bytes of pixels",
+        icon.width,
+        icon.height,
+        icon.pixels.len()
+    );
+
+    Ok(icon)
+}
+
+/// Logs the failure of a backend to retrieve the icon of `path`.
+///
+/// The failures of the system are warnings, the expected ones like cancellations are only debug messages.
+fn log_failure(path: &Path, size: u16, error: &Error) {
+    match error {
+        Error::Failed(_) | Error::Timeout | Error::Backend(_) => {
+            warn!(
+                "Failed to get the {size}px icon of '{}': {error}",
+                path.display()
+            );
+        }
+        _ => debug!("No {size}px icon for '{}': {error}", path.display()),
+    }
+}
+
+/// Retrieves the icons for many files at once.
+///
+/// This is faster than calling [`get_file_icon`] for each file because the platform
+/// context is set up once for the whole batch.
+///
+/// # Parameters
+/// * `paths` - The file paths for which the icons are to be retrieved.
+/// * `size` - Desired icon size, must be greater than 0.
+/// # Returns
+/// A vector with one result per path, in the same order as `paths`.
+/// Each result is an error if the icon of this path could not be retrieved,
+/// see [`Error`] for the reasons of failures.
+///
+/// # Example
+/// ```
+/// use file_icon_provider::get_file_icons;
+///
+/// for icon in get_file_icons(&["Cargo.toml", "src/lib.rs"], 64) {
+///     match icon {
+///         Ok(icon) => println!("Icon dimensions: {}x{}", icon.width, icon.height),
+///         Err(error) => println!("Failed to retrieve the icon: {error}"),
+///     }
+/// }
+/// ```
+///
+/// # Caveats
+///
+/// On linux, this function must be called on the main thread.
+pub fn get_file_icons(paths: &[impl AsRef<Path>], size: u16) -> Vec<Result<Icon, Error>> {
+    get_existing_file_icons(paths, size, |paths| {
+        implementation::get_file_icons(paths, size, &Options::default())
+    })
+}
+
+/// Retrieves the icons for many files at once, using up to `threads` threads.
+///
+/// This is meant for large one-shot batches, like filling a cache with thousands of icons.
+/// The paths are split into one chunk per thread, a `threads` of 0 is treated as 1.
+///
+/// The parallelism depends on the platform:
+/// - On Windows, each thread asks the shell directly in its own COM apartment, without the worker
+///   thread of the other functions. The 5 seconds timeout does not apply: a slow network drive
+///   or an unresponsive shell extension blocks its thread until it answers.
+/// - On `MacOS`, each thread draws the icons into its own bitmap.
+/// - On Linux with GTK, GTK is not thread-safe: the icons are retrieved one after the other
+///   on the calling thread, like [`get_file_icons`].
+/// - On Linux with the `linux-freedesktop` feature, the themes are shared by the threads.
+///
+/// # Parameters
+/// * `paths` - The file paths for which the icons are to be retrieved.
+/// * `size` - Desired icon size, must be greater than 0.
+/// * `threads` - The maximum number of threads retrieving the icons.
+/// # Returns
+/// A vector with one result per path, in the same order as `paths`.
+/// Each result is an error if the icon of this path could not be retrieved,
+/// see [`Error`] for the reasons of failures.
+///
+/// # Example
+/// ```
+/// use file_icon_provider::get_file_icons_parallel;
+///
+/// let icons = get_file_icons_parallel(&["Cargo.toml", "src/lib.rs"], 64, 4);
+///
+/// assert_eq!(icons.len(), 2);
+/// ```
+///
+/// # Caveats
+///
+/// On linux with GTK, this function must be called on the main thread.
+pub fn get_file_icons_parallel(
+    paths: &[impl AsRef<Path>],
+    size: u16,
+    threads: usize,
+) -> Vec<Result<Icon, Error>> {
+    get_existing_file_icons(paths, size, |paths| {
+        implementation::get_file_icons_parallel(paths, size, threads.max(1), &Options::default())
+    })
+}
+
+/// Calls `get_icons` with the paths which exist, and returns one result per path of `paths`.
+fn get_existing_file_icons(
+    paths: &[impl AsRef<Path>],
+    size: u16,
+    get_icons: impl FnOnce(&[&Path]) -> Vec<Result<Icon, Error>>,
+) -> Vec<Result<Icon, Error>> {
+    let existing: Vec<bool> = paths.iter().map(|path| path.as_ref().exists()).collect();
+    let mut icons = if size == 0 {
+        Vec::new()
+    } else {
+        let existing_paths: Vec<&Path> = paths
+            .iter()
+            .zip(&existing)
+            .filter(|(_, exists)| **exists)
+            .map(|(path, _)| path.as_ref())
+            .collect();
+
+        get_icons(&existing_paths)
+    }
+    .into_iter();
+
+    existing
+        .into_iter()
+        .map(|exists| {
+            if !exists {
+                Err(Error::PathDoesNotExist)
+            } else if size == 0 {
+                Err(Error::NullIconSize)
+            } else {
+                icons
+                    .next()
+                    .unwrap_or_else(|| Err(Error::failed("No icon returned")))
+            }
+        })
+        .collect()
+}
+
+/// Iterates over the entries of a directory with their icons.
+///
+/// The icons are retrieved one by one as the iterator advances, so a file manager can display
+/// the listing progressively. Retrieving an icon can fail without stopping the iteration,
+/// the error is returned with the path of the entry.
+///
+/// Hidden entries are skipped, use [`DirectoryIcons::include_hidden`] to include them.
+///
+/// # Errors
+/// [`Error::PathDoesNotExist`] is returned if `dir` does not exist, [`Error::Io`] if it can't be read
+/// and [`Error::NullIconSize`] if `size` is 0.
+///
+/// # Example
+/// ```no_run
+/// use file_icon_provider::icons_in_dir;
+///
+/// for (path, icon) in icons_in_dir("path/to/directory", 32)? {
+///     match icon {
+///         Ok(icon) => println!("{}: {}x{}", path.display(), icon.width, icon.height),
+///         Err(error) => println!("{}: {error}", path.display()),
+///     }
+/// }
+/// # Ok::<(), file_icon_provider::Error>(())
+/// ```
+///
+/// # Caveats
+///
+/// On linux, the iterator must be advanced on the main thread.
+pub fn icons_in_dir(dir: impl AsRef<Path>, size: u16) -> Result<DirectoryIcons, Error> {
+    let dir = dir.as_ref();
+
+    if !dir.exists() {
+        return Err(Error::PathDoesNotExist);
+    }
+
+    if size == 0 {
+        return Err(Error::NullIconSize);
+    }
+
+    let entries = std::fs::read_dir(dir).map_err(Error::Io)?;
+
+    Ok(DirectoryIcons::new(entries, size))
+}
+
+/// Retrieves the icon associated with a file extension.
+///
+/// The file does not have to exist, this is useful to display the icon of a file type.
+///
+/// # Parameters
+/// * `extension` - The file extension, with or without the leading dot (both "pdf" and ".pdf" work).
+/// * `size` - Desired icon size, must be greater than 0.
+/// # Returns
+/// * `Ok(Icon)` - If the icon is successfully retrieved.
+/// * `Err(Error)` - If the icon could not be retrieved.
+///
+/// # Errors
+/// See [`Error`] for the reasons of failures.
+///
+/// # Example
+/// ```
+/// use file_icon_provider::get_icon_for_extension;
+///
+/// if let Ok(icon) = get_icon_for_extension("pdf", 64) {
+///     println!("Icon dimensions: {}x{}", icon.width, icon.height);
+/// } else {
+///     println!("Failed to retrieve the icon.");
+/// }
+/// ```
+///
+/// # Caveats
+///
+/// On linux, this function must be called on the main thread. \
+/// On Windows, the icon comes from the system image lists which only provide a few sizes
+/// (16, 32, 48 and 256 pixels): the smallest size larger or equal to `size` is returned.
+/// Above 256 pixels, the icon of 256 pixels is upscaled to `size`.
+pub fn get_icon_for_extension(extension: &str, size: u16) -> Result<Icon, Error> {
+    if size == 0 {
+        return Err(Error::NullIconSize);
+    }
+
+    let extension = extension.strip_prefix('.').unwrap_or(extension);
+
+    if extension.is_empty() {
+        return Err(Error::failed("Empty extension"));
+    }
+
+    implementation::get_icon_for_extension(extension, size, &Options::default())
+}
+
+/// Retrieves the icon associated with a file name, for example "report.final.pdf" or "Makefile".
+///
+/// The file does not have to exist, this is useful when the content of a file is only available
+/// in memory. Unlike [`get_icon_for_extension`], names without extension like "Makefile" or ".gitignore"
+/// are recognized when the system knows them.
+///
+/// # Parameters
+/// * `file_name` - The name of the file, only its last component is used.
+/// * `size` - Desired icon size, must be greater than 0.
+/// # Returns
+/// * `Ok(Icon)` - If the icon is successfully retrieved.
+/// * `Err(Error)` - If the icon could not be retrieved.
+///
+/// # Errors
+/// See [`Error`] for the reasons of failures. \
+/// [`Error::Failed`] is returned if `file_name` is empty or is not a file name, like "..".
+///
+/// # Example
+/// ```
+/// use file_icon_provider::get_icon_for_name;
+///
+/// if let Ok(icon) = get_icon_for_name("Makefile", 64) {
+///     println!("Icon dimensions: {}x{}", icon.width, icon.height);
+/// } else {
+///     println!("Failed to retrieve the icon.");
+/// }
+/// ```
+///
+/// # Caveats
+///
+/// On linux, this function must be called on the main thread. \
+/// On macOS and Windows, the icon only depends on the extension: names without extension
+/// get the generic document icon.
+pub fn get_icon_for_name(file_name: &str, size: u16) -> Result<Icon, Error> {
+    if size == 0 {
+        return Err(Error::NullIconSize);
+    }
+
+    let Some(name) = Path::new(file_name).file_name().and_then(OsStr::to_str) else {
+        return Err(Error::failed(format!("Invalid file name '{file_name}'")));
+    };
+
+    implementation::get_icon_for_name(name, size, &Options::default())
+}
+
+/// Retrieves the icon associated with a MIME type, for example "image/png".
+///
+/// # Parameters
+/// * `mime_type` - The MIME type, in the form "type/subtype".
+/// * `size` - Desired icon size, must be greater than 0.
+/// # Returns
+/// * `Ok(Icon)` - If the icon is successfully retrieved.
+/// * `Err(Error)` - If the icon could not be retrieved.
+///
+/// # Errors
+/// See [`Error`] for the reasons of failures. \
+/// [`Error::Failed`] is returned if the MIME type is not recognized.
+///
+/// # Example
+/// ```
+/// use file_icon_provider::get_icon_for_mime_type;
+///
+/// if let Ok(icon) = get_icon_for_mime_type("image/png", 64) {
+///     println!("Icon dimensions: {}x{}", icon.width, icon.height);
+/// } else {
+///     println!("Failed to retrieve the icon.");
+/// }
+/// ```
+///
+/// # Caveats
+///
+/// On linux, this function must be called on the main thread. \
+/// On Windows, the generic document icon is returned when no extension is associated with the MIME type.
+/// The icon sizes are limited the same way as [`get_icon_for_extension`].
+pub fn get_icon_for_mime_type(mime_type: &str, size: u16) -> Result<Icon, Error> {
+    if size == 0 {
+        return Err(Error::NullIconSize);
+    }
+
+    let is_valid = mime_type
+        .split_once('/')
+        .is_some_and(|(type_, subtype)| !type_.is_empty() && !subtype.is_empty());
+
+    if !is_valid {
+        return Err(Error::failed(format!("Invalid MIME type '{mime_type}'")));
+    }
+
+    implementation::get_icon_for_mime_type(mime_type, size, &Options::default())
+}
+
+/// Retrieves the generic folder icon of the platform.
+///
+/// No directory is needed, this is useful to display folders in tree views or breadcrumbs.
+///
+/// # Parameters
+/// * `size` - Desired icon size, must be greater than 0.
+/// # Returns
+/// * `Ok(Icon)` - If the icon is successfully retrieved.
+/// * `Err(Error)` - If the icon could not be retrieved.
+///
+/// # Errors
+/// See [`Error`] for the reasons of failures.
+///
+/// # Example
+/// ```
+/// use file_icon_provider::get_folder_icon;
+///
+/// if let Ok(icon) = get_folder_icon(64) {
+///     println!("Icon dimensions: {}x{}", icon.width, icon.height);
+/// } else {
+///     println!("Failed to retrieve the icon.");
+/// }
+/// ```
+///
+/// # Caveats
+///
+/// On linux, this function must be called on the main thread. \
+/// The icon sizes are limited on Windows the same way as [`get_icon_for_extension`].
+pub fn get_folder_icon(size: u16) -> Result<Icon, Error> {
+    if size == 0 {
+        return Err(Error::NullIconSize);
+    }
+
+    implementation::get_folder_icon(size, &Options::default())
+}
+
+/// Retrieves the generic document icon of the platform.
+///
+/// This is the icon to display when the icon of a file can't be retrieved,
+/// for example when [`get_file_icon`] returns [`Error::Failed`].
+///
+/// # Parameters
+/// * `size` - Desired icon size, must be greater than 0.
+/// # Returns
+/// * `Ok(Icon)` - If the icon is successfully retrieved.
+/// * `Err(Error)` - If the icon could not be retrieved.
+///
+/// # Errors
+/// See [`Error`] for the reasons of failures.
+///
+/// # Example
+/// ```
+/// use file_icon_provider::{get_default_file_icon, get_file_icon};
+///
+/// let icon = get_file_icon("path/to/file", 64).or_else(|_| get_default_file_icon(64));
+///
+/// if let Ok(icon) = icon {
+///     println!("Icon dimensions: {}x{}", icon.width, icon.height);
+/// }
+/// ```
+///
+/// # Caveats
+///
+/// On linux, this function must be called on the main thread. \
+/// The icon sizes are limited on Windows the same way as [`get_icon_for_extension`].
+pub fn get_default_file_icon(size: u16) -> Result<Icon, Error> {
+    if size == 0 {
+        return Err(Error::NullIconSize);
+    }
+
+    implementation::get_default_file_icon(size, &Options::default())
+}
+
+/// Retrieves the icon of a special folder, like the desktop or the recycle bin.
+///
+/// Some of these folders are virtual items of the shell that have no path, like "This PC" on Windows,
+/// and the other ones have a dedicated icon that [`get_folder_icon`] does not give.
+///
+/// # Errors
+/// Fails with [`Error::Unsupported`] if the platform has no such folder,
+/// like [`KnownFolder::ThisPc`] on `MacOS`, see [`Error`] for the other reasons of failures.
+///
+/// # Example
+/// ```
+/// use file_icon_provider::{KnownFolder, get_known_folder_icon};
+///
+/// if let Ok(icon) = get_known_folder_icon(KnownFolder::RecycleBin, 32) {
+///     println!("Icon dimensions: {}x{}", icon.width, icon.height);
+/// }
+/// ```
+///
+/// # Caveats
+///
+/// On linux, this function must be called on the main thread.
+pub fn get_known_folder_icon(folder: KnownFolder, size: u16) -> Result<Icon, Error> {
+    if size == 0 {
+        return Err(Error::NullIconSize);
+    }
+
+    implementation::get_known_folder_icon(folder, size, &Options::default())
+}
+
+/// Retrieves the icon of the volume mounted at `mount_path`, like a hard disk or a USB drive.
+///
+/// On Windows and `MacOS` it is the icon the shell shows for the root of the volume, like `C:\`
+/// or `/Volumes/USB`. On Linux with GTK it is the icon of the mount of gio containing `mount_path`,
+/// the volumes that gio does not list, like the root partition, have the hard disk icon.
+/// The `linux-freedesktop` backend can't identify the devices, the volumes mounted in `/media`
+/// and `/run/media` have the removable media icon and the other ones the hard disk icon.
+///
+/// # Errors
+/// See [`Error`] for the reasons of failures.
+///
+/// # Example
+/// ```
+/// use file_icon_provider::get_volume_icon;
+///
+/// if let Ok(icon) = get_volume_icon("/Volumes/USB", 32) {
+///     println!("Icon dimensions: {}x{}", icon.width, icon.height);
+/// }
+/// ```
+///
+/// # Caveats
+///
+/// On linux, this function must be called on the main thread.
+pub fn get_volume_icon(mount_path: impl AsRef<Path>, size: u16) -> Result<Icon, Error> {
+    let mount_path = mount_path.as_ref();
+
+    if !mount_path.exists() {
+        return Err(Error::PathDoesNotExist);
+    }
+
+    if size == 0 {
+        return Err(Error::NullIconSize);
+    }
+
+    implementation::get_volume_icon(mount_path, size, &Options::default())
+}
+
+/// Retrieves the icon of the running application, for example for an About dialog.
+///
+/// The icon is the one of [`std::env::current_exe`]: on Windows the icon of the executable,
+/// and on `MacOS` the icon of the `.app` bundle containing it, or the generic executable icon
+/// if the program is not bundled. On Linux, executables have no icon: the icon of the desktop
+/// entry launching a program with the same file name is used, or the generic executable icon
+/// if there is none.
+///
+/// # Errors
+/// Fails with [`Error::Io`] if the path of the executable can't be resolved.
+/// See [`Error`] for the other reasons of failures.
+///
+/// # Example
+/// ```
+/// use file_icon_provider::get_current_process_icon;
+///
+/// if let Ok(icon) = get_current_process_icon(64) {
+///     println!("Icon dimensions: {}x{}", icon.width, icon.height);
+/// }
+/// ```
+///
+/// # Caveats
+///
+/// On linux, this function must be called on the main thread.
+pub fn get_current_process_icon(size: u16) -> Result<Icon, Error> {
+    if size == 0 {
+        return Err(Error::NullIconSize);
+    }
+
+    let executable = std::env::current_exe().map_err(Error::Io)?;
+
+    implementation::get_application_icon(&executable, size, &Options::default())
+        .inspect_err(|error| log_failure(&executable, size, error))
+}
+
+/// Retrieves the icon for a given file without blocking the calling thread.
+///
+/// The icon is retrieved on a background thread shared by all the asynchronous requests,
+/// except with the default `linux-gtk` feature, see the caveats.
+/// Dropping the returned future is safe: if the request did not start yet it is skipped,
+/// otherwise its result is discarded.
+///
+/// # Parameters
+/// * `path` - A file path for which the icon is to be retrieved.
+/// * `size` - Desired icon size, must be greater than 0.
+/// # Returns
+/// A future resolving to:
+/// * `Ok(Icon)` - If the icon is successfully retrieved.
+/// * `Err(Error)` - If the icon could not be retrieved.
+///
+/// # Errors
+/// See [`Error`] for the reasons of failures.
+///
+/// # Example
+/// ```
+/// use file_icon_provider::get_file_icon_async;
+///
+/// async fn print_icon_size() {
+///     if let Ok(icon) = get_file_icon_async("path/to/file", 64).await {
+///         println!("Icon dimensions: {}x{}", icon.width, icon.height);
+///     }
+/// }
+/// ```
+///
+/// # Caveats
+///
+/// On linux, GTK can only be used from the thread initializing it: with the default `linux-gtk`
+/// feature the icon is retrieved on the calling thread, which must be the main thread,
+/// and the returned future is ready immediately. Use the `linux-freedesktop` feature to retrieve
+/// the icons in the background.
+#[cfg(feature = "async")]
+pub fn get_file_icon_async(
+    path: impl AsRef<Path>,
+    size: u16,
+) -> impl Future<Output = Result<Icon, Error>> + Send + 'static {
+    let path = path.as_ref().to_path_buf();
+    let task = if USES_GTK {
+        worker::ready(get_file_icon(path, size))
+    } else {
+        worker::spawn(move || get_file_icon(path, size))
+    };
+
+    async move {
+        task.await
+            .unwrap_or_else(|| Err(Error::failed("The icon retrieval panicked")))
+    }
+}
+
+/// `true` if the icons are retrieved with GTK, which is bound to the first thread initializing it,
+/// so the background thread of the asynchronous functions must not use it.
+#[cfg(feature = "async")]
+const USES_GTK: bool = cfg!(all(
+    target_os = "linux",
+    not(feature = "linux-freedesktop"),
+    not(feature = "stub")
+));
+
+/// Provides icons.  
+/// # Type Parameters
+/// * `T` - The type of the final image to be displayed. It must be clonable. If your image type can't be cloned
+///   use a smart pointer (Rc).  
+///
+/// Provider is interesting if you request a lot of icons with a fixed size. \
+/// It allocates internal buffers once and reuse them when the platform allows it (on `MacOS`).
+/// It caches icons reducing the CPU and memory usage. \
+/// The cache is unbounded unless the provider is created with [`Provider::with_capacity`].
+/// The icons are cached before their conversion, they are only converted the first time
+/// a `T` is requested, see [`Provider::get_file_icon_owned`].
+/// Both are kept once converted, a [`Provider::weak`] keeps neither once the application dropped
+/// the converted icon.
+///
+/// `Provider<T>` is `Send` and `Sync` when `T` is `Send`, so it can be shared between threads using an `Arc`.
+/// When several threads request an icon that is not cached yet, only one of them retrieves it
+/// and the others wait for its result.
+pub struct Provider<T: Clone> {
+    implementation: implementation::Provider,
+    icon_size: u16,
+    options: Options,
+    converter: Converter<T>,
+    /// Set if the cache holds weak references to the converted icons, see [`Provider::weak`].
+    weak_references: Option<WeakReferences<T>>,
+    cache: Mutex<Cache<CacheKey, Arc<CachedIcon<T>>>>,
+    /// The icons being retrieved, concurrent requests of the same icon wait for the same lookup.
+    in_flight: Mutex<BTreeMap<CacheKey, Arc<Flight<T>>>>,
+    /// The messages of the failures, only filled if failures are cached.
+    failures: Mutex<BTreeMap<FailureKey, String>>,
+    /// The content hashes of the refreshed icons that are not cached, see [`Provider::refresh`].
+    refreshed: Mutex<BTreeMap<FailureKey, u64>>,
+    /// The cache keys of the paths whose icon was requested, see [`Provider::export_atlas`].
+    /// The paths whose entry left the cache are removed when the atlas is exported or the cache cleared.
+    paths: Mutex<BTreeMap<PathBuf, CacheKey>>,
+    cache_failures: bool,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    /// Set by [`Provider::on_theme_changed`].
+    theme_listener: Option<Arc<ThemeListener>>,
+    /// Set by [`Provider::on_evict`].
+    on_evict: Option<EvictionCallback<T>>,
+    /// Set by [`ProviderBuilder::hidden_files`].
+    hidden_files: HiddenFiles,
+}
+
+/// Statistics about the cache of a [`Provider`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CacheStats {
+    /// The number of icons served from the cache.
+    pub hits: u64,
+    /// The number of icons that had to be retrieved.
+    pub misses: u64,
+}
+
+type Converter<T> = Box<dyn Fn(Icon) -> Result<T, Error> + Send + Sync>;
+
+type EvictionCallback<T> = Box<dyn Fn(&T) + Send + Sync>;
+
+/// Result of a lookup shared by concurrent requests, set once by the first of them.
+type Flight<T> = OnceLock<Result<Arc<CachedIcon<T>>, Error>>;
+
+/// An icon of the cache, converted the first time it is requested.
+///
+/// When both are locked, `converted` is locked first.
+struct CachedIcon<T> {
+    /// The icon before its conversion, a [`Provider::weak`] releases it once converted.
+    icon: Mutex<Option<Icon>>,
+    /// A `Mutex` instead of a `OnceLock`, so the provider is `Sync` even if `T` is not.
+    converted: Mutex<Option<Converted<T>>>,
+}
+
+/// The converted icon of a cache entry.
+enum Converted<T> {
+    Strong(T),
+    /// A `Weak<U>` of a `T` that is an `Arc<U>`, see [`WeakReferences`].
+    Weak(Box<dyn Any + Send + Sync>),
+}
+
+/// Converts between `T` and a weak reference to it, `T` is an `Arc<U>` but the provider can't name `U`.
+struct WeakReferences<T> {
+    downgrade: fn(&T) -> Box<dyn Any + Send + Sync>,
+    upgrade: fn(&(dyn Any + Send + Sync)) -> Option<T>,
+}
+
+fn downgrade<U: Send + Sync + 'static>(converted: &Arc<U>) -> Box<dyn Any + Send + Sync> {
+    Box::new(Arc::downgrade(converted))
+}
+
+fn upgrade<U: Send + Sync + 'static>(weak: &(dyn Any + Send + Sync)) -> Option<Arc<U>> {
+    weak.downcast_ref::<Weak<U>>()?.upgrade()
+}
+
+impl<T> CachedIcon<T> {
+    fn new(icon: Icon) -> Arc<Self> {
+        Arc::new(Self {
+            icon: Mutex::new(Some(icon)),
+            converted: Mutex::new(None),
+        })
+    }
+
+    fn icon(&self) -> MutexGuard<'_, Option<Icon>> {
+        self.icon.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn converted(&self) -> MutexGuard<'_, Option<Converted<T>>> {
+        self.converted
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct CacheKey {
+    file_type: String,
+    size: u16,
+    color_scheme: ColorScheme,
+    /// The bits of the scale, `f32` is not `Ord`.
+    scale: u32,
+    /// Set if the icon is marked as the icon of a hidden file, see [`HiddenFiles`].
+    hidden: bool,
+}
+
+/// Cache key of the generic folder icon, paths can't contain a null character
+/// so no file type can have this key.
+const FOLDER_CACHE_KEY: &str = "\0folder";
+
+/// Cache key of the generic document icon.
+const DEFAULT_FILE_CACHE_KEY: &str = "\0file";
+
+/// Prefix of the cache keys of the icons retrieved by [`Provider::get_file_icon_by_type`],
+/// followed by the name the type is resolved from.
+const TYPE_CACHE_KEY: &str = "\0type:";
+
+/// Failures and the hashes of [`Provider::refresh`] are kept per path, unlike icons which are cached
+/// per file type.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct FailureKey {
+    /// The canonical path, see [`failure_path`].
+    path: PathBuf,
+    size: u16,
+    color_scheme: ColorScheme,
+    scale: u32,
+}
+
+/// Returns the path failures are cached for, so the equivalent paths of a file share their entries.
+///
+/// The path is canonicalized, or used as is if it can't be, for example if it is virtual.
+fn failure_path(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+impl<T> Provider<T>
+where
+    T: Clone,
+{
+    /// Creates a new Provider
+    ///
+    /// The converter can be a closure capturing its environment, for example a handle to a renderer.
+    /// It is a shortcut for [`Provider::builder`] with only the icon size and the converter.
+    /// # Errors
+    /// See [`Error`] for the reasons of failures.
+    pub fn new<F>(icon_size: u16, converter: F) -> Result<Self, Error>
+    where
+        F: Fn(Icon) -> T + Send + Sync + 'static,
+    {
+        Self::builder()
+            .icon_size(icon_size)
+            .converter(converter)
+            .build()
+    }
+
+    /// Returns a builder to configure a Provider before creating it.
+    ///
+    /// # Example
+    /// ```
+    /// use file_icon_provider::{ColorScheme, Provider, Icon};
+    /// use std::rc::Rc;
+    ///
+    /// let provider: Provider<Rc<Icon>> = Provider::builder()
+    ///     .icon_size(32)
+    ///     .color_scheme(ColorScheme::Dark)
+    ///     .scale(2.0)
+    ///     .capacity(512)
+    ///     .converter(Rc::new)
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// assert_eq!(provider.capacity(), Some(512));
+    /// ```
+    #[must_use]
+    pub fn builder() -> ProviderBuilder<T> {
+        ProviderBuilder::new()
+    }
+
+    /// Creates a new Provider with an icon size that can't be null.
+    ///
+    /// Like [`Provider::new`], but it never fails with [`Error::NullIconSize`].
+    /// # Errors
+    /// See [`Error`] for the reasons of failures.
+    /// # Example
+    /// ```
+    /// use file_icon_provider::{IconSize, Provider, Icon};
+    /// use std::rc::Rc;
+    ///
+    /// let size = IconSize::new(64).unwrap();
+    /// let provider: Provider<Rc<Icon>> = Provider::with_size(size, Rc::new).unwrap();
+    /// ```
+    pub fn with_size<F>(icon_size: IconSize, converter: F) -> Result<Self, Error>
+    where
+        F: Fn(Icon) -> T + Send + Sync + 'static,
+    {
+        Self::new(icon_size.get(), converter)
+    }
+
+    /// Creates a new Provider caching at most `max_entries` icons.
+    ///
+    /// Once the cache is full, the least recently used icon is evicted.
+    /// # Errors
+    /// See [`Error`] for the reasons of failures.
+    /// # Example
+    /// ```
+    /// use file_icon_provider::{Provider, Icon};
+    /// use std::rc::Rc;
+    ///
+    /// let provider: Provider<Rc<Icon>> = Provider::with_capacity(64, Rc::new, 512).unwrap();
+    ///
+    /// assert_eq!(provider.capacity(), Some(512));
+    /// ```
+    pub fn with_capacity<F>(icon_size: u16, converter: F, max_entries: usize) -> Result<Self, Error>
+    where
+        F: Fn(Icon) -> T + Send + Sync + 'static,
+    {
+        Self::builder()
+            .icon_size(icon_size)
+            .capacity(max_entries)
+            .converter(converter)
+            .build()
+    }
+
+    /// Creates a new Provider with a converter that can fail.
+    ///
+    /// When the converter fails, [`Provider::get_file_icon`] returns [`Error::Conversion`]
+    /// and the conversion is attempted again on the next request.
+    /// # Errors
+    /// See [`Error`] for the reasons of failures.
+    /// # Example
+    /// ```
+    /// use file_icon_provider::{Provider, Icon};
+    ///
+    /// fn to_texture(icon: Icon) -> Result<Vec<u8>, String> {
+    ///     if icon.pixels.is_empty() {
+    ///         return Err("empty icon".to_string());
+    ///     }
+    ///
+    ///     Ok(icon.pixels)
+    /// }
+    ///
+    /// let provider = Provider::try_new(64, to_texture).unwrap();
+    /// ```
+    pub fn try_new<F, E>(icon_size: u16, converter: F) -> Result<Self, Error>
+    where
+        F: Fn(Icon) -> Result<T, E> + Send + Sync + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        Self::with_converter(
+            icon_size,
+            Box::new(move |icon| converter(icon).map_err(|error| Error::Conversion(error.into()))),
+            None,
+        )
+    }
+
+    fn with_converter(
+        icon_size: u16,
+        converter: Converter<T>,
+        capacity: Option<usize>,
+    ) -> Result<Self, Error> {
+        if icon_size == 0 {
+            return Err(Error::NullIconSize);
+        }
+
+        Ok(Self {
+            implementation: implementation::Provider::new(icon_size)?,
+            icon_size,
+            options: Options::default(),
+            converter,
+            weak_references: None,
+            cache: Mutex::new(Cache::new(capacity)),
+            in_flight: Mutex::new(BTreeMap::new()),
+            failures: Mutex::new(BTreeMap::new()),
+            refreshed: Mutex::new(BTreeMap::new()),
+            paths: Mutex::new(BTreeMap::new()),
+            cache_failures: false,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            theme_listener: None,
+            on_evict: None,
+            hidden_files: HiddenFiles::Unchanged,
+        })
+    }
+
+    /// Enables or disables the caching of failures, it is disabled by default.
+    ///
+    /// When enabled, a file whose icon could not be retrieved fails again immediately
+    /// with the same [`Error::Failed`] message, without asking the system again.
+    /// Failures are cached per file and size, [`Provider::invalidate`], [`Provider::invalidate_size`]
+    /// and [`Provider::clear`] remove them. The paths are canonicalized, so the relative and the absolute
+    /// paths of a file share their failures.
+    ///
+    /// Some failures are transient, for example while a file is being written,
+    /// invalidate the file once it changed.
+    ///
+    /// # Example
+    /// ```
+    /// use file_icon_provider::{Provider, Icon};
+    /// use std::rc::Rc;
+    ///
+    /// let provider: Provider<Rc<Icon>> = Provider::new(32, Rc::new).unwrap().cache_failures(true);
+    /// ```
+    #[must_use]
+    pub fn cache_failures(mut self, cache_failures: bool) -> Self {
+        self.cache_failures = cache_failures;
+        self
+    }
+
+    /// Sets a function called with the converted icons removed from the cache,
+    /// to release the resources they hold, like GPU textures.
+    ///
+    /// It is called when an icon is evicted because the cache is full, and when it is removed by
+    /// [`Provider::clear_older_than`], [`Provider::invalidate`], [`Provider::invalidate_size`],
+    /// [`Provider::refresh`] or [`Provider::clear`]. It is not called when the provider is dropped.
+    ///
+    /// Only the icons that were converted are passed to the function, and with [`Provider::weak`],
+    /// only the ones still referenced. The function runs once the cache is unlocked, so it can
+    /// use the provider, but other threads may still hold clones of the removed icons.
+    ///
+    /// # Example
+    /// ```
+    /// use file_icon_provider::{Provider, Icon};
+    /// use std::sync::Arc;
+    ///
+    /// let provider: Provider<Arc<Icon>> = Provider::with_capacity(32, Arc::new, 100)
+    ///     .unwrap()
+    ///     .on_evict(|icon| println!("Release the texture of a {}x{} icon", icon.width, icon.height));
+    /// ```
+    #[must_use]
+    pub fn on_evict<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&T) + Send + Sync + 'static,
+    {
+        self.on_evict = Some(Box::new(callback));
+        self
+    }
+
+    /// Returns the color scheme icons are rendered for.
+    pub fn color_scheme(&self) -> ColorScheme {
+        self.options.color_scheme
+    }
+
+    /// Sets the color scheme icons are rendered for.
+    ///
+    /// Icons are cached per color scheme, switching back to a previous color scheme reuses its cached icons.
+    /// Only `MacOS` honors the color scheme, see [`ColorScheme`].
+    pub fn set_color_scheme(&mut self, color_scheme: ColorScheme) {
+        self.options.color_scheme = color_scheme;
+    }
+
+    /// Returns the number of physical pixels per logical pixel icons are retrieved for.
+    pub fn scale(&self) -> f32 {
+        self.options.scale
+    }
+
+    /// Sets the number of physical pixels per logical pixel icons are retrieved for.
+    ///
+    /// The icons are `icon_size * scale` pixels large, so they are crisp on high density displays.
+    /// Icons are cached per scale. The default scale is 1.0.
+    ///
+    /// # Errors
+    /// Fails with [`Error::InvalidScale`] and keeps the current scale if `scale` is not a positive number.
+    pub fn set_scale(&mut self, scale: f32) -> Result<(), Error> {
+        if !is_valid_scale(scale) {
+            return Err(Error::InvalidScale(scale));
+        }
+
+        self.options.scale = scale;
+
+        Ok(())
+    }
+
+    /// Returns the number of icons in the cache.
+    pub fn len(&self) -> usize {
+        self.cache().len()
+    }
+
+    /// Returns `true` if the cache contains no icons.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the maximum number of icons in the cache, or `None` if the cache is unbounded.
+    pub fn capacity(&self) -> Option<usize> {
+        self.cache().capacity()
+    }
+
+    /// Retrieves the icon for a given file.
+    ///
+    /// # Parameters
+    /// * `path` - A file path for which the icon is to be retrieved.
+    /// # Returns
+    /// * `Ok(Icon)` - If the icon is successfully retrieved.
+    /// * `Err(Error)` - If the icon could not be retrieved.
+    /// # Errors
+    /// See [`Error`] for the reasons of failures.
+    /// # Example
+    /// ```
+    /// use file_icon_provider::{Provider, Icon};
+    /// use std::rc::Rc;
+    ///
+    /// let provider: Provider<Rc<Icon>> = Provider::new(64, Rc::new).unwrap();
+    ///
+    /// if let Ok(icon) = provider.get_file_icon("path/to/file") {
+    ///     println!("Icon dimensions: {}x{}", icon.width, icon.height);
+    /// } else {
+    ///     println!("Failed to retrieve the icon.");
+    /// }
+    /// ```
+    ///
+    /// # Caveats
+    ///
+    /// On linux, this function must be called on the main thread.
+    pub fn get_file_icon(&self, path: impl AsRef<Path>) -> Result<T, Error> {
+        self.get_file_icon_sized(path, self.icon_size)
+    }
+
+    /// Retrieves the icon for a given file at a size other than the size of the provider.
+    ///
+    /// Icons are cached per size, so one provider can serve several views,
+    /// for example a list with small icons and a grid with large icons.
+    /// # Errors
+    /// See [`Error`] for the reasons of failures.
+    /// # Example
+    /// ```
+    /// use file_icon_provider::{Provider, Icon};
+    /// use std::rc::Rc;
+    ///
+    /// let provider: Provider<Rc<Icon>> = Provider::new(16, Rc::new).unwrap();
+    ///
+    /// let small_icon = provider.get_file_icon("path/to/file");
+    /// let large_icon = provider.get_file_icon_sized("path/to/file", 64);
+    /// ```
+    ///
+    /// # Caveats
+    ///
+    /// On linux, this function must be called on the main thread.
+    pub fn get_file_icon_sized(&self, path: impl AsRef<Path>, size: u16) -> Result<T, Error> {
+        self.get_cached(path.as_ref(), size).map(|(icon, _)| icon)
+    }
+
+    /// Retrieves the icon of the type of a given file, it is cached once for all the files of this type.
+    ///
+    /// [`Provider::get_file_icon`] already caches the icons per file type, except for the files
+    /// that can have their own icon, like executables, shortcuts or symbolic links, which are
+    /// retrieved every time. This function caches these files by type too: their type is resolved
+    /// from their extension, or from their name if they have none, like with [`get_icon_for_name`].
+    /// Directories get the folder icon, see [`get_folder_icon`].
+    ///
+    /// The icons returned this way don't reflect the custom icons of the files,
+    /// use [`Provider::get_file_icon`] for those.
+    ///
+    /// # Errors
+    /// See [`Error`] for the reasons of failures.
+    /// # Example
+    /// ```
+    /// use file_icon_provider::{Provider, Icon};
+    /// use std::rc::Rc;
+    ///
+    /// let provider: Provider<Rc<Icon>> = Provider::new(32, Rc::new).unwrap();
+    ///
+    /// for path in ["path/to/first.exe", "path/to/second.exe"] {
+    ///     if let Ok(icon) = provider.get_file_icon_by_type(path, 32) {
+    ///         println!("Icon dimensions: {}x{}", icon.width, icon.height);
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// # Caveats
+    ///
+    /// On linux, this function must be called on the main thread.
+    pub fn get_file_icon_by_type(&self, path: impl AsRef<Path>, size: u16) -> Result<T, Error> {
+        let path = path.as_ref();
+
+        if self.implementation.cache_key(path).is_some() {
+            return self.get_file_icon_sized(path, size);
+        }
+
+        if !path.exists() {
+            return Err(Error::PathDoesNotExist);
+        }
+
+        if size == 0 {
+            return Err(Error::NullIconSize);
+        }
+
+        self.clear_if_theme_changed();
+
+        if path.is_dir() {
+            return self.get_generic_icon(true, size);
+        }
+
+        // The extension is given to the backends with a placeholder name, so the icon
+        // only depends on the key.
+        let name = match path.extension() {
+            Some(extension) => format!("file.{}", extension.to_string_lossy().to_lowercase()),
+            None => path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+        };
+        let hidden = self.marks_hidden(path);
+
+        self.get_keyed_icon(format!("{TYPE_CACHE_KEY}{name}"), size, hidden, |size| {
+            implementation::get_icon_for_name(&name, size, &self.options)
+        })
+    }
+
+    /// Retrieves the icon for a given file before its conversion.
+    ///
+    /// The icon is cached like with [`Provider::get_file_icon`] and they share the same cache entry,
+    /// but the converter does not run. This is useful to save an icon of a provider
+    /// whose converter uploads it to a renderer.
+    /// # Errors
+    /// See [`Error`] for the reasons of failures.
+    /// # Example
+    /// ```
+    /// use file_icon_provider::{Provider, Icon};
+    /// use std::rc::Rc;
+    ///
+    /// let provider: Provider<Rc<Icon>> = Provider::new(32, Rc::new).unwrap();
+    ///
+    /// if let Ok(icon) = provider.get_file_icon_owned("path/to/file") {
+    ///     println!("{} bytes", icon.into_pixels().len());
+    /// }
+    /// ```
+    ///
+    /// # Caveats
+    ///
+    /// On linux, this function must be called on the main thread.
+    pub fn get_file_icon_owned(&self, path: impl AsRef<Path>) -> Result<Icon, Error> {
+        self.get_owned(path.as_ref(), self.icon_size)
+    }
+
+    /// Retrieves the icon for a given file, or the generic icon if it can't be retrieved.
+    ///
+    /// The generic icon is the folder icon for directories, see [`get_folder_icon`],
+    /// and the generic document icon otherwise, see [`get_default_file_icon`].
+    /// It is converted and cached like the other icons, so the user interface always has an icon to show.
+    ///
+    /// # Errors
+    /// Fails only if the generic icon can't be retrieved either, see [`Error`] for the reasons of failures.
+    /// # Example
+    /// ```
+    /// use file_icon_provider::{Provider, Icon};
+    /// use std::rc::Rc;
+    ///
+    /// let provider: Provider<Rc<Icon>> = Provider::new(32, Rc::new).unwrap();
+    ///
+    /// if let Ok(icon) = provider.get_file_icon_or_default("path/to/file", 32) {
+    ///     println!("Icon dimensions: {}x{}", icon.width, icon.height);
+    /// }
+    /// ```
+    ///
+    /// # Caveats
+    ///
+    /// On linux, this function must be called on the main thread.
+    pub fn get_file_icon_or_default(&self, path: impl AsRef<Path>, size: u16) -> Result<T, Error> {
+        let path = path.as_ref();
+
+        match self.get_cached(path, size) {
+            Ok((icon, _)) => Ok(icon),
+            Err(error) => {
+                debug!("Using the generic icon for '{}': {error}", path.display());
+                self.get_generic_icon(path.is_dir(), size)
+            }
+        }
+    }
+
+    /// Retrieves the icon for a given file, and tells if it was served from the cache.
+    ///
+    /// # Returns
+    /// * `Ok((icon, true))` - If the icon was in the cache.
+    /// * `Ok((icon, false))` - If the icon had to be retrieved.
+    /// * `Err(Error)` - If the icon could not be retrieved.
+    /// # Errors
+    /// See [`Error`] for the reasons of failures.
+    /// # Caveats
+    ///
+    /// On linux, this function must be called on the main thread.
+    pub fn get_file_icon_cached(&self, path: impl AsRef<Path>) -> Result<(T, bool), Error> {
+        self.get_cached(path.as_ref(), self.icon_size)
+    }
+
+    /// Runs `f` with the converted icon of `path` at `size`, without cloning it.
+    ///
+    /// The icon is retrieved and cached first if it is not in the cache.
+    /// The cache entry stays locked while `f` runs, so `f` must not request the same icon from the provider.
+    /// The icons of a [`Provider::weak`] are cloned, they are `Arc`s.
+    /// # Errors
+    /// See [`Error`] for the reasons of failures.
+    /// # Example
+    /// ```
+    /// use file_icon_provider::{Provider, Icon};
+    ///
+    /// let provider: Provider<Icon> = Provider::new(32, |icon| icon).unwrap();
+    ///
+    /// if let Ok(len) = provider.with_icon("path/to/file", 32, |icon| icon.pixels.len()) {
+    ///     println!("{len} bytes");
+    /// }
+    /// ```
+    ///
+    /// # Caveats
+    ///
+    /// On linux, this function must be called on the main thread.
+    pub fn with_icon<R>(
+        &self,
+        path: impl AsRef<Path>,
+        size: u16,
+        f: impl FnOnce(&T) -> R,
+    ) -> Result<R, Error> {
+        loop {
+            let (cached_icon, _) = self.get_cached_entry(path.as_ref(), size)?;
+
+            if self.weak_references.is_none() {
+                // The entry is not locked during the conversion, like in `convert`.
+                if cached_icon.converted().is_none() {
+                    let icon = cached_icon.icon().clone();
+
+                    if let Some(icon) = icon {
+                        let converted = (self.converter)(icon)?;
+
+                        cached_icon
+                            .converted()
+                            .get_or_insert(Converted::Strong(converted));
+                    }
+                }
+
+                if let Some(Converted::Strong(converted)) = cached_icon.converted().as_ref() {
+                    return Ok(f(converted));
+                }
+            }
+
+            if let Some(converted) = self.convert(cached_icon) {
+                return converted.map(|converted| f(&converted));
+            }
+        }
+    }
+
+    fn get_cached(&self, path: &Path, size: u16) -> Result<(T, bool), Error> {
+        loop {
+            let (cached_icon, hit) = self.get_cached_entry(path, size)?;
+
+            if let Some(converted) = self.convert(cached_icon) {
+                return Ok((converted?, hit));
+            }
+        }
+    }
+
+    /// Returns the cache entry of the icon of `path`, and tells if it was in the cache.
+    ///
+    /// The icons that are never cached get an entry that is not in the cache.
+    fn get_cached_entry(
+        &self,
+        path: &Path,
+        size: u16,
+    ) -> Result<(Arc<CachedIcon<T>>, bool), Error> {
+        if !path.exists() {
+            return Err(Error::PathDoesNotExist);
+        }
+
+        if size == 0 {
+            return Err(Error::NullIconSize);
+        }
+
+        self.clear_if_theme_changed();
+
+        if !self.cache_failures {
+            return self.get_cached_icon(path, size);
+        }
+
+        let failure_key = self.failure_key(path, size);
+
+        if let Some(message) = self.failures().get(&failure_key) {
+            return Err(Error::failed(message.clone()));
+        }
+
+        let result = self.get_cached_icon(path, size);
+
+        if let Err(Error::Failed(error)) = &result {
+            self.failures().insert(failure_key, error.to_string());
+        }
+
+        result
+    }
+
+    fn failure_key(&self, path: &Path, size: u16) -> FailureKey {
+        FailureKey {
+            path: failure_path(path),
+            size,
+            color_scheme: self.options.color_scheme,
+            scale: self.options.scale.to_bits(),
+        }
+    }
+
+    /// Returns the key of the icon of `path` in the cache, or `None` if its icon must not be cached.
+    fn cache_key(&self, path: &Path, size: u16) -> Option<CacheKey> {
+        Some(CacheKey {
+            file_type: self.implementation.cache_key(path)?,
+            size,
+            color_scheme: self.options.color_scheme,
+            scale: self.options.scale.to_bits(),
+            hidden: self.marks_hidden(path),
+        })
+    }
+
+    /// Returns `true` if the icon of `path` must be marked as the icon of a hidden file.
+    fn marks_hidden(&self, path: &Path) -> bool {
+        self.hidden_files != HiddenFiles::Unchanged && is_hidden(path)
+    }
+
+    fn get_cached_icon(&self, path: &Path, size: u16) -> Result<(Arc<CachedIcon<T>>, bool), Error> {
+        let Some(key) = self.cache_key(path, size) else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+
+            return Ok((CachedIcon::new(self.get_icon(path, size)?), false));
+        };
+        let result = self.get_keyed_entry(path, size, &key);
+
+        if result.is_ok() {
+            self.paths().insert(path.to_path_buf(), key);
+        }
+
+        result
+    }
+
+    /// Returns the cache entry of `key`, the icon of `path` is retrieved and cached if it is missing.
+    fn get_keyed_entry(
+        &self,
+        path: &Path,
+        size: u16,
+        key: &CacheKey,
+    ) -> Result<(Arc<CachedIcon<T>>, bool), Error> {
+        if let Some(cached_icon) = self.cached(key) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+
+            return Ok((cached_icon, true));
+        }
+
+        // Concurrent requests of the same icon wait for the first one instead of retrieving it again.
+        let flight = Arc::clone(self.in_flight().entry(key.clone()).or_default());
+        let mut initialized = false;
+        let mut retrieved = None;
+        let shared = flight.get_or_init(|| {
+            initialized = true;
+
+            // The icon may have been cached since the cache was checked.
+            if let Some(cached_icon) = self.cached(key) {
+                return Ok(cached_icon);
+            }
+
+            // The cache is not locked while the icon is retrieved so other threads are not blocked.
+            let result = self.get_icon(path, size).map(|icon| {
+                self.update_cache(|cache| cache.insert(key.clone(), CachedIcon::new(icon)))
+            });
+            let shared = result.as_ref().map(Arc::clone).map_err(Error::duplicate);
+
+            retrieved = Some(result);
+            shared
+        });
+
+        if initialized {
+            self.in_flight().remove(key);
+        }
+
+        if let Some(result) = retrieved {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+
+            return result.map(|cached_icon| (cached_icon, false));
+        }
+
+        if shared.is_ok() {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        }
+
+        shared
+            .as_ref()
+            .map(|cached_icon| (Arc::clone(cached_icon), true))
+            .map_err(Error::duplicate)
+    }
+
+    fn get_generic_icon(&self, folder: bool, size: u16) -> Result<T, Error> {
+        if size == 0 {
+            return Err(Error::NullIconSize);
+        }
+
+        let file_type = if folder {
+            FOLDER_CACHE_KEY
+        } else {
+            DEFAULT_FILE_CACHE_KEY
+        };
+
+        self.get_keyed_icon(file_type.to_owned(), size, false, |size| {
+            if folder {
+                implementation::get_folder_icon(size, &self.options)
+            } else {
+                implementation::get_default_file_icon(size, &self.options)
+            }
+        })
+    }
+
+    /// Returns the icon cached for `file_type`, `retrieve` is called with the scaled size if it is not cached.
+    ///
+    /// The icon is marked as the icon of a hidden file if `hidden` is set, see [`HiddenFiles`].
+    fn get_keyed_icon(
+        &self,
+        file_type: String,
+        size: u16,
+        hidden: bool,
+        retrieve: impl FnOnce(u16) -> Result<Icon, Error>,
+    ) -> Result<T, Error> {
+        let key = CacheKey {
+            file_type,
+            size,
+            color_scheme: self.options.color_scheme,
+            scale: self.options.scale.to_bits(),
+            hidden,
+        };
+
+        // The converted icon of a weak provider may have been dropped, the request is then a miss.
+        if let Some(converted) = self
+            .cached(&key)
+            .and_then(|cached_icon| self.convert(cached_icon))
+        {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+
+            return converted;
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        let mut icon = retrieve(self.options.scaled_size(size))?.into_format(self.options.format);
+
+        if hidden {
+            icon = self.hidden_files.mark(icon);
+        }
+
+        let cached_icon = CachedIcon::new(icon);
+
+        match self.convert(self.update_cache(|cache| cache.insert(key, cached_icon))) {
+            Some(converted) => converted,
+            // Another thread cached an entry whose converted icon was dropped meanwhile.
+            None => Err(Error::failed("The icon was released during its conversion")),
+        }
+    }
+
+    /// Returns how many icons were served from the cache and how many had to be retrieved.
+    ///
+    /// Files that are never cached, like directories, are counted as misses.
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Removes the cached icon of a given file.
+    ///
+    /// The path is mapped to a cache entry the same way [`Provider::get_file_icon`] does.
+    /// Icons are cached per file type, so the icon is removed for every file sharing the type of `path`,
+    /// for all the sizes and color schemes.
+    /// Nothing happens if this icon is not cached.
+    pub fn invalidate(&self, path: impl AsRef<Path>) {
+        let path = path.as_ref();
+        let failure_path = failure_path(path);
+
+        self.failures().retain(|key, _| key.path != failure_path);
+        self.refreshed().retain(|key, _| key.path != failure_path);
+
+        if let Some(file_type) = self.implementation.cache_key(path) {
+            self.update_cache(|cache| cache.remove_if(|key| key.file_type == file_type));
+        }
+    }
+
+    /// Returns `true` if the icon of a given file at `size` is cached.
+    ///
+    /// The icon is not retrieved, and the cache is left untouched: the icon is not marked as
+    /// recently used and the statistics are not updated.
+    /// This helps to choose between drawing a placeholder and requesting the icon.
+    ///
+    /// # Example
+    /// ```
+    /// use file_icon_provider::{Provider, Icon};
+    /// use std::rc::Rc;
+    ///
+    /// let provider: Provider<Rc<Icon>> = Provider::new(32, Rc::new).unwrap();
+    ///
+    /// if !provider.contains("path/to/file", 32) {
+    ///     println!("Drawing a placeholder");
+    /// }
+    /// ```
+    ///
+    /// # Caveats
+    ///
+    /// On linux, this function must be called on the main thread.
+    pub fn contains(&self, path: impl AsRef<Path>, size: u16) -> bool {
+        let path = path.as_ref();
+
+        if !path.exists() || size == 0 {
+            return false;
+        }
+
+        self.clear_if_theme_changed();
+        self.cache_key(path, size)
+            .is_some_and(|key| self.cache().contains_key(&key))
+    }
+
+    /// Removes the cached icon of a given file for a specific size.
+    ///
+    /// See [`Provider::invalidate`].
+    pub fn invalidate_size(&self, path: impl AsRef<Path>, size: u16) {
+        let path = path.as_ref();
+        let failure_path = failure_path(path);
+
+        self.failures()
+            .retain(|key, _| key.path != failure_path || key.size != size);
+        self.refreshed()
+            .retain(|key, _| key.path != failure_path || key.size != size);
+
+        if let Some(file_type) = self.implementation.cache_key(path) {
+            self.update_cache(|cache| {
+                cache.remove_if(|key| key.file_type == file_type && key.size == size);
+            });
+        }
+    }
+
+    /// Retrieves the icon of a given file at `size` again, and replaces the cached icon if it changed.
+    ///
+    /// Call it when the icon of a watched file may have changed, for example after a shortcut
+    /// was retargeted. The converter runs again on the next request of a changed icon.
+    ///
+    /// # Returns
+    /// * `Ok(true)` - If the icon differs from the cached one, or was not cached.
+    /// * `Ok(false)` - If the icon did not change.
+    ///
+    /// The icons of some files are never cached, for example the shortcuts and executables on
+    /// Windows: their icon is compared to the one of the previous refresh of the same path instead.
+    /// * `Err(Error)` - If the icon could not be retrieved, the cached icon is kept.
+    /// # Errors
+    /// See [`Error`] for the reasons of failures.
+    /// # Example
+    /// ```
+    /// use file_icon_provider::{Provider, Icon};
+    /// use std::rc::Rc;
+    ///
+    /// let provider: Provider<Rc<Icon>> = Provider::new(32, Rc::new).unwrap();
+    ///
+    /// if let Ok(true) = provider.refresh("path/to/shortcut.lnk", 32) {
+    ///     println!("The icon changed");
+    /// }
+    /// ```
+    ///
+    /// # Caveats
+    ///
+    /// On linux, this function must be called on the main thread.
+    pub fn refresh(&self, path: impl AsRef<Path>, size: u16) -> Result<bool, Error> {
+        let path = path.as_ref();
+
+        if !path.exists() {
+            return Err(Error::PathDoesNotExist);
+        }
+
+        if size == 0 {
+            return Err(Error::NullIconSize);
+        }
+
+        let icon = self.get_icon(path, size)?;
+        let Some(key) = self.cache_key(path, size) else {
+            let content_hash = icon.content_hash();
+            let previous = self
+                .refreshed()
+                .insert(self.failure_key(path, size), content_hash);
+
+            return Ok(previous != Some(content_hash));
+        };
+
+        Ok(self.update_cache(|cache| {
+            let changed = cache
+                .get(&key)
+                .is_none_or(|cached_icon| cached_icon.icon().as_ref() != Some(&icon));
+
+            if changed {
+                cache.remove(&key);
+                cache.insert(key, CachedIcon::new(icon));
+            }
+
+            changed
+        }))
+    }
+
+    /// Removes all the cached icons.
+    ///
+    /// Call it when the icons of the system may have changed, for example after the icon theme
+    /// changed or after applications were installed.
+    pub fn clear(&self) {
+        self.update_cache(Cache::clear);
+        self.failures().clear();
+        self.refreshed().clear();
+        self.paths().clear();
+    }
+
+    /// Calls `callback` when the icons of the system change, the cache is cleared on the next request.
+    ///
+    /// The icons change when the user switches the icon theme on Linux, the appearance on `MacOS`,
+    /// or the settings of the system on Windows. Use the callback to redraw the icons.
+    /// It replaces the callback of a previous call, and runs on:
+    /// * Linux with GTK: the main thread, from the main loop of GTK.
+    /// * Linux with `linux-freedesktop`: a background thread, which polls the GTK settings files.
+    /// * `MacOS`: the main thread, from its run loop.
+    /// * Windows: a background thread, whose hidden window receives `WM_SETTINGCHANGE`.
+    ///
+    /// The background threads stop once all the providers listening to the changes are dropped.
+    ///
+    /// # Errors
+    /// Fails with [`Error::Failed`] if the notifications of the system can't be watched,
+    /// for example if GTK can't be initialized.
+    /// # Example
+    /// ```
+    /// use file_icon_provider::{Provider, Icon};
+    /// use std::sync::Arc;
+    ///
+    /// let mut provider: Provider<Arc<Icon>> = Provider::new(32, Arc::new).unwrap();
+    ///
+    /// provider.on_theme_changed(|| println!("The icons must be redrawn")).unwrap();
+    /// ```
+    ///
+    /// # Caveats
+    ///
+    /// On linux, this function must be called on the main thread.
+    pub fn on_theme_changed<F>(&mut self, callback: F) -> Result<(), Error>
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.theme_listener = Some(theme::listen(callback)?);
+
+        Ok(())
+    }
+
+    /// Reloads the icon theme of the system and clears the cache.
+    ///
+    /// The changes are detected automatically with [`Provider::on_theme_changed`],
+    /// call it when the application knows the icons changed, for example after installing an icon theme.
+    ///
+    /// # Caveats
+    ///
+    /// On linux, this function must be called on the main thread.
+    pub fn refresh_theme(&self) {
+        implementation::refresh_theme();
+        self.clear();
+    }
+
+    /// Clears the cache if the icons of the system changed, see [`Provider::on_theme_changed`].
+    fn clear_if_theme_changed(&self) {
+        if self
+            .theme_listener
+            .as_ref()
+            .is_some_and(|listener| listener.take_changed())
+        {
+            self.clear();
+        }
+    }
+
+    /// Removes the cached icons that were not requested for at least `age`.
+    ///
+    /// Long running applications can call it periodically, so the icons are refreshed after
+    /// a change of the icon theme without clearing the whole cache.
+    /// Every request of an icon, including the ones served from the cache, resets its age.
+    /// The failures cached with [`Provider::cache_failures`] are not removed.
+    pub fn clear_older_than(&self, age: Duration) {
+        // No icon can be older than the monotonic clock.
+        let Some(instant) = Instant::now().checked_sub(age) else {
+            return;
+        };
+
+        self.update_cache(|cache| cache.remove_accessed_before(instant));
+    }
+
+    /// Retrieves and caches the icons of `paths` at `size`, so they are ready when they are displayed.
+    ///
+    /// This function blocks until all the icons are cached, see `Provider::prefetch_async`
+    /// to prefetch them on a background thread.
+    /// Files whose icon is never cached, like directories, are skipped.
+    /// Failures are ignored, the icons that can't be retrieved are not cached.
+    ///
+    /// # Example
+    /// ```
+    /// use file_icon_provider::{Provider, Icon};
+    /// use std::rc::Rc;
+    ///
+    /// let provider: Provider<Rc<Icon>> = Provider::new(32, Rc::new).unwrap();
+    ///
+    /// provider.prefetch(&["path/to/file.txt", "path/to/file.pdf"], 32);
+    /// ```
+    ///
+    /// # Caveats
+    ///
+    /// On linux, this function must be called on the main thread.
+    pub fn prefetch(&self, paths: &[impl AsRef<Path>], size: u16) {
+        for path in paths {
+            let path = path.as_ref();
+
+            if self.implementation.cache_key(path).is_none() {
+                continue;
+            }
+
+            if let Err(error) = self.get_cached_entry(path, size) {
+                debug!(
+                    "Failed to prefetch the icon of '{}': {error}",
+                    path.display()
+                );
+            }
+        }
+    }
+
+    /// Packs the cached icons into a single icon, for renderers uploading one texture.
+    ///
+    /// Returns the atlas with the rectangle of the icon of each path requested from the provider,
+    /// the icon last requested for the path if it was requested at several sizes.
+    /// The files sharing a cache entry share their rectangle, and the files whose icon is not cached
+    /// anymore, or is never cached like directories, are not in the table.
+    /// The icons released by a [`Provider::weak`] once converted are not in the atlas either.
+    /// The atlas has the pixel format of the provider and is transparent between the icons,
+    /// which are packed on rows from the tallest to the shortest. It is empty if no icon is cached.
+    ///
+    /// # Example
+    /// ```
+    /// use file_icon_provider::{Provider, Icon};
+    /// use std::rc::Rc;
+    ///
+    /// let provider: Provider<Rc<Icon>> = Provider::new(32, Rc::new).unwrap();
+    ///
+    /// provider.prefetch(&["path/to/file.txt", "path/to/file.pdf"], 32);
+    ///
+    /// let (atlas, rects) = provider.export_atlas();
+    ///
+    /// for (path, rect) in &rects {
+    ///     println!("{}: {}x{} at {}, {}", path.display(), rect.width, rect.height, rect.x, rect.y);
+    /// }
+    /// ```
+    pub fn export_atlas(&self) -> (Icon, HashMap<PathBuf, Rect>) {
+        let mut icons: Vec<Icon> = Vec::new();
+        let mut key_indices: BTreeMap<CacheKey, usize> = BTreeMap::new();
+        let mut path_indices = Vec::new();
+
+        {
+            let cache = self.cache();
+
+            self.paths().retain(|path, key| {
+                let Some(cached_icon) = cache.peek(key) else {
+                    return false;
+                };
+                if !key_indices.contains_key(key) {
+                    let Some(icon) = cached_icon.icon().clone() else {
+                        return true;
+                    };
+
+                    icons.push(icon);
+                    key_indices.insert(key.clone(), icons.len() - 1);
+                }
+
+                path_indices.push((path.clone(), key_indices[key]));
+                true
+            });
+        }
+
+        let (atlas, rects) = atlas::pack(&icons.iter().collect::<Vec<_>>(), self.options.format);
+
+        (
+            atlas,
+            path_indices
+                .into_iter()
+                .map(|(path, index)| (path, rects[index]))
+                .collect(),
+        )
+    }
+
+    fn get_icon(&self, path: &Path, size: u16) -> Result<Icon, Error> {
+        let icon = self
+            .implementation
+            .get_file_icon(path, size, &self.options)
+            .inspect_err(|error| log_failure(path, size, error))?;
+        let icon = icon.into_format(self.options.format);
+
+        if self.marks_hidden(path) {
+            return Ok(self.hidden_files.mark(icon));
+        }
+
+        Ok(icon)
+    }
+
+    /// Returns the icon of `path` at `size` without converting it.
+    fn get_owned(&self, path: &Path, size: u16) -> Result<Icon, Error> {
+        let (cached_icon, _) = self.get_cached_entry(path, size)?;
+
+        match Arc::try_unwrap(cached_icon) {
+            // The entry is not in the cache anymore, the icon is returned without copy.
+            Ok(cached_icon) => cached_icon
+                .icon
+                .into_inner()
+                .unwrap_or_else(PoisonError::into_inner)
+                .map_or_else(|| self.get_icon(path, size), Ok),
+            Err(cached_icon) => self.raw_icon(&cached_icon, path, size),
+        }
+    }
+
+    /// Returns the icon of a cache entry before its conversion.
+    ///
+    /// The icons released by a [`Provider::weak`] are retrieved again, without being cached.
+    fn raw_icon(&self, cached_icon: &CachedIcon<T>, path: &Path, size: u16) -> Result<Icon, Error> {
+        let icon = cached_icon.icon().clone();
+
+        icon.map_or_else(|| self.get_icon(path, size), Ok)
+    }
+
+    /// Returns the converted icon of a cache entry, the icon is converted on the first call.
+    ///
+    /// A [`Provider::weak`] releases the icon once converted, so the entry is useless once
+    /// the converted icon is dropped: `None` is returned, and the icon must be retrieved again.
+    fn convert(&self, cached_icon: Arc<CachedIcon<T>>) -> Option<Result<T, Error>> {
+        if let Some(converted) = self.load(cached_icon.converted().as_ref()) {
+            return Some(Ok(converted));
+        }
+
+        match Arc::try_unwrap(cached_icon) {
+            // The entry is not in the cache, the icon is converted without copy.
+            Ok(cached_icon) => Some((self.converter)(
+                cached_icon
+                    .icon
+                    .into_inner()
+                    .unwrap_or_else(PoisonError::into_inner)?,
+            )),
+            Err(cached_icon) => {
+                let icon = cached_icon.icon().clone()?;
+                // The entry is not locked during the conversion, another thread may convert it too.
+                let converted = match (self.converter)(icon) {
+                    Ok(converted) => converted,
+                    Err(error) => return Some(Err(error)),
+                };
+                let mut stored = cached_icon.converted();
+
+                if let Some(converted) = self.load(stored.as_ref()) {
+                    return Some(Ok(converted));
+                }
+
+                *stored = Some(match &self.weak_references {
+                    Some(weak_references) => {
+                        cached_icon.icon().take();
+
+                        Converted::Weak((weak_references.downgrade)(&converted))
+                    }
+                    None => Converted::Strong(converted.clone()),
+                });
+
+                Some(Ok(converted))
+            }
+        }
+    }
+
+    /// Returns the cache entry of `key` and marks it as recently used.
+    ///
+    /// The entries released by a [`Provider::weak`] are removed, see [`Provider::convert`].
+    fn cached(&self, key: &CacheKey) -> Option<Arc<CachedIcon<T>>> {
+        self.update_cache(|cache| {
+            let cached_icon = cache.get(key)?;
+            let released = cached_icon.icon().is_none();
+
+            if released && self.load(cached_icon.converted().as_ref()).is_none() {
+                cache.remove(key);
+                return None;
+            }
+
+            Some(cached_icon)
+        })
+    }
+
+    /// Returns the converted icon of a cache entry, `None` if it is not converted or was dropped.
+    fn load(&self, converted: Option<&Converted<T>>) -> Option<T> {
+        match converted? {
+            Converted::Strong(converted) => Some(converted.clone()),
+            Converted::Weak(weak) => (self.weak_references.as_ref()?.upgrade)(weak.as_ref()),
+        }
+    }
+
+    fn cache(&self) -> MutexGuard<'_, Cache<CacheKey, Arc<CachedIcon<T>>>> {
+        self.cache.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Updates the cache, then passes the removed icons to the callback of [`Provider::on_evict`]
+    /// once the cache is unlocked.
+    fn update_cache<R>(
+        &self,
+        update: impl FnOnce(&mut Cache<CacheKey, Arc<CachedIcon<T>>>) -> R,
+    ) -> R {
+        let (result, removed) = {
+            let mut cache = self.cache();
+            let result = update(&mut cache);
+
+            (result, cache.take_removed())
+        };
+
+        if let Some(on_evict) = &self.on_evict {
+            for cached_icon in removed {
+                let converted = self.load(cached_icon.converted().as_ref());
+
+                if let Some(converted) = converted {
+                    on_evict(&converted);
+                }
+            }
+        }
+
+        result
+    }
+
+    fn in_flight(&self) -> MutexGuard<'_, BTreeMap<CacheKey, Arc<Flight<T>>>> {
+        self.in_flight
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn failures(&self) -> MutexGuard<'_, BTreeMap<FailureKey, String>> {
+        self.failures.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn refreshed(&self) -> MutexGuard<'_, BTreeMap<FailureKey, u64>> {
+        self.refreshed
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn paths(&self) -> MutexGuard<'_, BTreeMap<PathBuf, CacheKey>> {
+        self.paths.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<U> Provider<Arc<U>>
+where
+    U: Send + Sync + 'static,
+{
+    /// Creates a new Provider whose cache holds weak references to the converted icons.
+    ///
+    /// The requests of an icon share the same `Arc` while the application keeps it,
+    /// once every clone is dropped its memory is released and the next request retrieves the icon again.
+    /// The cache does not keep the pixels of the converted icons either, so
+    /// [`Provider::get_file_icon_owned`] retrieves them again and [`Provider::refresh`]
+    /// reports them as changed.
+    /// # Errors
+    /// See [`Error`] for the reasons of failures.
+    /// # Example
+    /// ```
+    /// use file_icon_provider::{Provider, Icon};
+    /// use std::sync::Arc;
+    ///
+    /// let provider: Provider<Arc<Icon>> = Provider::weak(32, Arc::new).unwrap();
+    ///
+    /// if let Ok(icon) = provider.get_file_icon("path/to/file") {
+    ///     // The cache does not keep the icon alive.
+    ///     assert_eq!(Arc::strong_count(&icon), 1);
+    /// }
+    /// ```
+    pub fn weak<F>(icon_size: u16, converter: F) -> Result<Self, Error>
+    where
+        F: Fn(Icon) -> Arc<U> + Send + Sync + 'static,
+    {
+        let mut provider = Self::new(icon_size, converter)?;
+
+        provider.weak_references = Some(WeakReferences {
+            downgrade: downgrade::<U>,
+            upgrade: upgrade::<U>,
+        });
+
+        Ok(provider)
+    }
+}
+
+/// Builder of a [`Provider`], created with [`Provider::builder`].
+///
+/// The options not honored by the current platform are stored but ignored,
+/// so the same code configures the provider everywhere.
+pub struct ProviderBuilder<T: Clone> {
+    icon_size: u16,
+    theme: Option<String>,
+    color_scheme: ColorScheme,
+    scale: f32,
+    capacity: Option<usize>,
+    format: PixelFormat,
+    timeout: Duration,
+    symbolic: Option<[u8; 4]>,
+    scale_mode: ScaleMode,
+    hidden_files: HiddenFiles,
+    converter: Option<Converter<T>>,
+}
+
+impl<T> ProviderBuilder<T>
+where
+    T: Clone,
+{
+    fn new() -> Self {
+        Self {
+            icon_size: 0,
+            theme: None,
+            color_scheme: ColorScheme::default(),
+            scale: 1.0,
+            capacity: None,
+            format: PixelFormat::default(),
+            timeout: DEFAULT_TIMEOUT,
+            symbolic: None,
+            scale_mode: ScaleMode::default(),
+            hidden_files: HiddenFiles::Unchanged,
+            converter: None,
+        }
+    }
+
+    /// Sets the size of the icons returned by [`Provider::get_file_icon`], it is required.
+    #[must_use]
+    pub fn icon_size(mut self, icon_size: u16) -> Self {
+        self.icon_size = icon_size;
+        self
+    }
+
+    /// Sets the name of the icon theme to use instead of the default one.
+    ///
+    /// Only the Linux backend honors the theme, see [`get_file_icon_with_theme`].
+    #[must_use]
+    pub fn theme(mut self, theme_name: impl Into<String>) -> Self {
+        self.theme = Some(theme_name.into());
+        self
+    }
+
+    /// Sets the color scheme icons are rendered for, see [`Provider::set_color_scheme`].
+    #[must_use]
+    pub fn color_scheme(mut self, color_scheme: ColorScheme) -> Self {
+        self.color_scheme = color_scheme;
+        self
+    }
+
+    /// Sets the number of physical pixels per logical pixel, see [`Provider::set_scale`].
+    #[must_use]
+    pub fn scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// Bounds the cache to `max_entries` icons, see [`Provider::with_capacity`].
+    ///
+    /// The cache is unbounded by default.
+    #[must_use]
+    pub fn capacity(mut self, max_entries: usize) -> Self {
+        self.capacity = Some(max_entries);
+        self
+    }
+
+    /// Sets the order of the channels of the icons passed to the converter, RGBA by default.
+    ///
+    /// See [`get_file_icon_format`].
+    #[must_use]
+    pub fn pixel_format(mut self, format: PixelFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Sets the maximum time to wait for the system to return an icon, 5 seconds by default.
+    ///
+    /// Only the Windows backend honors the timeout, see [`get_file_icon_timeout`].
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Prefers the symbolic icons, painted with `rgba_foreground`.
+    ///
+    /// Only the Linux backends honor this, see [`get_file_icon_symbolic`].
+    #[must_use]
+    pub fn symbolic(mut self, rgba_foreground: [u8; 4]) -> Self {
+        self.symbolic = Some(rgba_foreground);
+        self
+    }
+
+    /// Sets whether the icons smaller than the requested size are upscaled, they are by default.
+    ///
+    /// Only the Windows backend honors the scale mode, see [`get_file_icon_with_scale_mode`].
+    #[must_use]
+    pub fn scale_mode(mut self, scale_mode: ScaleMode) -> Self {
+        self.scale_mode = scale_mode;
+        self
+    }
+
+    /// Sets how the icons of the hidden files are marked, they are returned unchanged by default.
+    ///
+    /// The icons are marked after they are retrieved, on every platform. The marked icons
+    /// are cached separately from the icons of the visible files of the same type.
+    ///
+    /// # Example
+    /// ```
+    /// use file_icon_provider::{HiddenFiles, Icon, Provider};
+    /// use std::rc::Rc;
+    ///
+    /// let provider: Provider<Rc<Icon>> = Provider::builder()
+    ///     .icon_size(32)
+    ///     .hidden_files(HiddenFiles::Dimmed(128))
+    ///     .converter(Rc::new)
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    #[must_use]
+    pub fn hidden_files(mut self, hidden_files: HiddenFiles) -> Self {
+        self.hidden_files = hidden_files;
+        self
+    }
+
+    /// Sets the function converting the icons before they are cached, it is required.
+    #[must_use]
+    pub fn converter<F>(mut self, converter: F) -> Self
+    where
+        F: Fn(Icon) -> T + Send + Sync + 'static,
+    {
+        self.converter = Some(Box::new(move |icon| Ok(converter(icon))));
+        self
+    }
+
+    /// Creates the Provider.
+    ///
+    /// # Errors
+    /// Fails with [`Error::NullIconSize`] if the icon size is not set or null,
+    /// with [`Error::InvalidScale`] if the scale is not a positive number,
+    /// and with [`Error::Failed`] if the converter is not set.
+    /// See [`Error`] for the other reasons of failures.
+    pub fn build(self) -> Result<Provider<T>, Error> {
+        let Some(converter) = self.converter else {
+            return Err(Error::failed("The converter of the provider is not set"));
+        };
+
+        if !is_valid_scale(self.scale) {
+            return Err(Error::InvalidScale(self.scale));
+        }
+
+        let mut provider = Provider::with_converter(self.icon_size, converter, self.capacity)?;
+
+        provider.options.theme = self.theme;
+        provider.options.color_scheme = self.color_scheme;
+        provider.options.scale = self.scale;
+        provider.options.format = self.format;
+        provider.options.timeout = self.timeout;
+        provider.options.symbolic = self.symbolic;
+        provider.options.scale_mode = self.scale_mode;
+        provider.hidden_files = self.hidden_files;
+
+        Ok(provider)
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T> Provider<T>
+where
+    T: Clone + Send + 'static,
+{
+    /// Retrieves and caches the icons of `paths` at `size` on a background thread.
+    ///
+    /// Works like [`Provider::prefetch`], the returned future completes once all the icons are cached.
+    /// The prefetch is skipped if the future is dropped before the background thread starts it.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use file_icon_provider::{Provider, Icon};
+    /// use std::{path::PathBuf, sync::Arc};
+    ///
+    /// # async fn example() {
+    /// let provider: Arc<Provider<Arc<Icon>>> = Arc::new(Provider::new(32, Arc::new).unwrap());
+    ///
+    /// provider
+    ///     .prefetch_async(vec![PathBuf::from("path/to/file.txt")], 32)
+    ///     .await;
+    /// # }
+    /// ```
+    ///
+    /// # Caveats
+    ///
+    /// On linux, GTK can only be used from the main thread: with the default `linux-gtk` feature
+    /// this function does nothing, call [`Provider::prefetch`] from the main thread instead.
+    pub fn prefetch_async(
+        self: &Arc<Self>,
+        paths: Vec<PathBuf>,
+        size: u16,
+    ) -> impl Future<Output = ()> + Send + 'static {
+        let task = if USES_GTK {
+            None
+        } else {
+            let provider = Arc::clone(self);
+
+            Some(worker::spawn(move || provider.prefetch(&paths, size)))
+        };
+
+        async move {
+            if let Some(task) = task {
+                task.await;
+            }
+        }
+    }
+}
+
+mod alpha;
+mod atlas;
+mod blocks;
+mod cache;
+mod color;
+mod crop;
+mod directory;
+mod logging;
+mod parallel;
+mod path_ext;
+mod resize;
+mod theme;
+
+#[cfg(any(target_os = "linux", target_os = "windows"))]
+mod descriptor;
+
+#[cfg(feature = "egui")]
+mod egui_conversion;
+
+#[cfg(feature = "global")]
+mod global;
+
+#[cfg(feature = "image")]
+mod image_conversion;
+
+#[cfg(feature = "png")]
+mod png_export;
+
+#[cfg(feature = "serde")]
+mod serialization;
+
+#[cfg(feature = "slint")]
+mod slint_conversion;
+
+#[cfg(feature = "terminal")]
+mod terminal;
+
+#[cfg(feature = "winit")]
+mod winit_conversion;
+
+#[cfg(feature = "async")]
+mod worker;
+
+mod implementation {
+    #[cfg(all(target_os = "macos", not(feature = "stub")))]
+    mod macos;
+
+    #[cfg(all(target_os = "macos", not(feature = "stub")))]
+    pub(crate) use macos::{
+        get_application_icon, get_available_sizes, get_default_file_icon, get_file_icon,
+        get_file_icon_sizes, get_file_icons, get_file_icons_parallel, get_file_thumbnail,
+        get_file_type_info, get_folder_icon, get_icon_for_extension, get_icon_for_mime_type,
+        get_icon_for_name, get_icon_from_resource, get_icon_source, get_known_folder_icon,
+        get_link_overlay, get_volume_icon, refresh_theme, unwatch_theme, watch_theme,
+    };
+
+    #[cfg(all(target_os = "macos", not(feature = "stub")))]
+    pub(crate) use macos::Provider;
+
+    #[cfg(all(target_os = "windows", not(feature = "stub")))]
+    mod windows;
+
+    #[cfg(all(target_os = "windows", not(feature = "stub")))]
+    pub(crate) use windows::{
+        get_application_icon, get_available_sizes, get_default_file_icon, get_file_icon,
+        get_file_icon_sizes, get_file_icons, get_file_icons_parallel, get_file_thumbnail,
+        get_file_type_info, get_folder_icon, get_icon_for_extension, get_icon_for_mime_type,
+        get_icon_for_name, get_icon_from_resource, get_icon_source, get_known_folder_icon,
+        get_link_overlay, get_volume_icon, refresh_theme, unwatch_theme, watch_theme,
+    };
+
+    #[cfg(all(target_os = "windows", not(feature = "stub")))]
+    pub(crate) use windows::{Provider, get_file_icon_sta, shutdown_icon_worker};
+
+    #[cfg(all(
+        target_os = "linux",
+        not(feature = "linux-gtk"),
+        not(feature = "linux-freedesktop"),
+        not(feature = "stub")
+    ))]
+    compile_error!("On Linux, one of the features 'linux-gtk' or 'linux-freedesktop' is required");
+
+    #[cfg(all(
+        target_os = "linux",
+        not(feature = "linux-freedesktop"),
+        not(feature = "stub")
+    ))]
+    mod linux;
+
+    #[cfg(all(
+        target_os = "linux",
+        not(feature = "linux-freedesktop"),
+        not(feature = "stub")
+    ))]
+    pub(crate) use linux::{
+        get_application_icon, get_available_sizes, get_default_file_icon, get_file_icon,
+        get_file_icon_sizes, get_file_icons, get_file_icons_parallel, get_file_thumbnail,
+        get_file_type_info, get_folder_icon, get_icon_for_extension, get_icon_for_mime_type,
+        get_icon_for_name, get_icon_from_resource, get_icon_source, get_known_folder_icon,
+        get_link_overlay, get_volume_icon, refresh_theme, unwatch_theme, watch_theme,
+    };
+
+    #[cfg(all(
+        target_os = "linux",
+        not(feature = "linux-freedesktop"),
+        not(feature = "stub")
+    ))]
+    pub(crate) use linux::Provider;
+
+    #[cfg(all(target_os = "linux", not(feature = "stub")))]
+    mod symbolic;
+
+    #[cfg(all(
+        target_os = "linux",
+        feature = "linux-freedesktop",
+        not(feature = "stub")
+    ))]
+    mod freedesktop;
+
+    #[cfg(all(
+        target_os = "linux",
+        feature = "linux-freedesktop",
+        not(feature = "stub")
+    ))]
+    pub(crate) use freedesktop::{
+        get_application_icon, get_available_sizes, get_default_file_icon, get_file_icon,
+        get_file_icon_sizes, get_file_icons, get_file_icons_parallel, get_file_thumbnail,
+        get_file_type_info, get_folder_icon, get_icon_for_extension, get_icon_for_mime_type,
+        get_icon_for_name, get_icon_from_resource, get_icon_source, get_known_folder_icon,
+        get_link_overlay, get_volume_icon, refresh_theme, unwatch_theme, watch_theme,
+    };
+
+    #[cfg(all(
+        target_os = "linux",
+        feature = "linux-freedesktop",
+        not(feature = "stub")
+    ))]
+    pub(crate) use freedesktop::Provider;
+
+    #[cfg(all(
+        not(any(target_os = "macos", target_os = "windows", target_os = "linux")),
+        not(feature = "stub")
+    ))]
+    compile_error!("Unsupported platform, only the 'stub' feature works here");
+
+    #[cfg(feature = "stub")]
+    mod stub;
+
+    #[cfg(feature = "stub")]
+    pub(crate) use stub::{
+        Provider, get_application_icon, get_available_sizes, get_default_file_icon, get_file_icon,
+        get_file_icon_sizes, get_file_icons, get_file_icons_parallel, get_file_thumbnail,
+        get_file_type_info, get_folder_icon, get_icon_for_extension, get_icon_for_mime_type,
+        get_icon_for_name, get_icon_from_resource, get_icon_source, get_known_folder_icon,
+        get_link_overlay, get_volume_icon, refresh_theme, unwatch_theme, watch_theme,
+    };
+
+    #[cfg(all(target_os = "windows", feature = "stub"))]
+    pub(crate) use stub::{get_file_icon_sta, shutdown_icon_worker};
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        CacheStats, CancellationToken, ColorScheme, Error, Icon, IconSize, IconSource, KnownFolder,
+        PixelFormat, Provider, ScaleMode, available_sizes, get_current_process_icon,
+        get_default_file_icon, get_file_icon, get_file_icon_cancellable, get_file_icon_detailed,
+        get_file_icon_format, get_file_icon_nearest, get_file_icon_premultiplied,
+        get_file_icon_sizes, get_file_icon_symbolic, get_file_icon_timeout,
+        get_file_icon_unchecked, get_file_icon_with_dpi, get_file_icon_with_scale,
+        get_file_icon_with_scale_mode, get_file_icon_with_size, get_file_icon_with_theme,
+        get_file_icons, get_file_icons_parallel, get_file_thumbnail, get_file_type_info,
+        get_folder_icon, get_icon_for_extension, get_icon_for_mime_type, get_icon_for_name,
+        get_known_folder_icon, get_symlink_icon, get_volume_icon, nearest_size,
+    };
+    use std::assert_matches;
+    use std::path::Path;
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[test]
+    fn test_icon_new() {
+        let icon = Icon::new(2, 1, vec![1, 2, 3, 4, 5, 6, 7, 8]).expect("create icon");
+
+        assert_eq!(icon.as_bytes(), [1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(Vec::from(icon.clone()), icon.into_pixels());
+        assert_matches!(
+            Icon::new(2, 1, vec![0; 4]),
+            Err(Error::InvalidBuffer {
+                expected: 8,
+                actual: 4
+            })
+        );
+        assert!(Icon::new(0, 0, Vec::new()).is_ok());
+        assert_eq!(
+            Icon::from_rgba(1, 1, vec![1, 2, 3, 4]).expect("create icon"),
+            Icon::new(1, 1, vec![1, 2, 3, 4]).expect("create icon")
+        );
+        assert_matches!(
+            Icon::from_rgba(1, 1, vec![0; 5]),
+            Err(Error::InvalidBuffer {
+                expected: 4,
+                actual: 5
+            })
+        );
+    }
+
+    #[test]
+    fn test_check_buffer() {
+        let icon = |pixels: Vec<u8>| Icon {
+            width: 2,
+            height: 2,
+            pixels,
+            format: PixelFormat::Bgra8,
+            stride: None,
+        };
+
+        assert!(icon(vec![0; 16]).check_buffer().is_ok());
+        assert_matches!(
+            icon(vec![0; 12]).check_buffer(),
+            Err(Error::InvalidBuffer {
+                expected: 16,
+                actual: 12
+            })
+        );
+        assert_matches!(
+            icon(vec![0; 20]).check_buffer(),
+            Err(Error::InvalidBuffer {
+                expected: 16,
+                actual: 20
+            })
+        );
+    }
+
+    #[test]
+    fn test_into_format() {
+        let icon = Icon::new(1, 1, vec![1, 2, 3, 4]).expect("create icon");
+        let bgra = icon.clone().into_format(PixelFormat::Bgra8);
+
+        assert_eq!(bgra.format, PixelFormat::Bgra8);
+        assert_eq!(bgra.pixels, [3, 2, 1, 4]);
+        assert_eq!(bgra.rgba_pixels().as_ref(), icon.pixels);
+        assert_eq!(bgra.clone().into_format(PixelFormat::Bgra8), bgra);
+        assert_eq!(bgra.into_format(PixelFormat::Rgba8), icon);
+    }
+
+    #[test]
+    fn test_get_file_icon_format() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let rgba = get_file_icon(&file_path, 32).expect("get icon");
+        let bgra = get_file_icon_format(&file_path, 32, PixelFormat::Bgra8).expect("get icon");
+
+        assert_eq!(rgba.format, PixelFormat::Rgba8);
+        assert_eq!(bgra.format, PixelFormat::Bgra8);
+        assert_eq!(bgra.into_format(PixelFormat::Rgba8), rgba);
+    }
+
+    #[test]
+    fn test_padded_rows() {
+        let icon = Icon {
+            width: 2,
+            height: 2,
+            pixels: vec![
+                3, 2, 1, 4, 7, 6, 5, 8, 0, 0, //
+                11, 10, 9, 12, 15, 14, 13, 16, 0, 0,
+            ],
+            format: PixelFormat::Bgra8,
+            stride: Some(10),
+        };
+
+        assert!(icon.check_buffer().is_ok());
+        assert_eq!(icon.get_pixel(0, 1), Some([9, 10, 11, 12]));
+        assert_eq!(icon.rgba_pixels(), (1..=16).collect::<Vec<u8>>());
+
+        let rgba = icon.clone().into_format(PixelFormat::Rgba8);
+
+        assert_eq!(rgba.stride, Some(10));
+        assert_eq!(rgba.pixels[8..10], [0, 0]);
+        assert_eq!(rgba.into_packed().pixels, (1..=16).collect::<Vec<u8>>());
+        assert_matches!(
+            Icon {
+                stride: Some(6),
+                ..icon
+            }
+            .check_buffer(),
+            Err(Error::InvalidBuffer {
+                expected: 16,
+                actual: 20
+            })
+        );
+    }
+
+    #[test]
+    fn test_icon_debug_does_not_print_pixels() {
+        let icon = Icon {
+            width: 2,
+            height: 1,
+            pixels: vec![255; 8],
+            format: PixelFormat::Rgba8,
+            stride: None,
+        };
+
+        assert_eq!(
+            format!("{icon:?}"),
+            "Icon { width: 2, height: 1, pixels: [u8; 8], format: Rgba8, stride: None }"
+        );
+    }
+
+    #[test]
+    fn test_icon_clone() {
+        let icon = Icon {
+            width: 1,
+            height: 1,
+            pixels: vec![1, 2, 3, 4],
+            format: PixelFormat::Rgba8,
+            stride: None,
+        };
+
+        assert_eq!(icon.clone(), icon);
+    }
+
+    #[test]
+    fn test_get_file_icon() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+
+        assert!(get_file_icon(file_path, 32).is_ok());
+    }
+
+    #[test]
+    fn test_icon_dimensions_match_pixels() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+
+        for size in [16, 20, 33, 48, 300] {
+            let icon = get_file_icon(&file_path, size).expect("get icon");
+
+            assert_eq!(
+                icon.pixels.len(),
+                icon.bytes_per_row() * icon.height as usize
+            );
+            assert_eq!(
+                icon.clone().into_packed().pixels.len(),
+                icon.width as usize * icon.height as usize * 4
+            );
+        }
+    }
+
+    #[test]
+    fn test_icon_pixel_access() {
+        let mut icon = Icon {
+            width: 3,
+            height: 2,
+            pixels: vec![0; 3 * 2 * 4],
+            format: PixelFormat::Bgra8,
+            stride: None,
+        };
+
+        assert!(icon.set_pixel(2, 1, [1, 2, 3, 4]));
+        assert_eq!(icon.pixels[20..24], [3, 2, 1, 4]);
+        assert_eq!(icon.get_pixel(2, 1), Some([1, 2, 3, 4]));
+        assert_eq!(icon.get_pixel(3, 0), None);
+        assert!(!icon.set_pixel(0, 2, [1, 2, 3, 4]));
+        assert_eq!(icon.pixel_count(), 6);
+        assert!((icon.aspect_ratio() - 1.5).abs() < f32::EPSILON);
+
+        icon.pixels.truncate(20);
+
+        assert_eq!(icon.get_pixel(2, 1), None);
+        assert!(!icon.set_pixel(2, 1, [1, 2, 3, 4]));
+        assert!(Icon::new(0, 0, Vec::new()).unwrap().aspect_ratio().abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn test_unknown_theme_falls_back_to_default() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+
+        assert!(get_file_icon_with_theme(file_path, 32, "NOT EXISTING").is_ok());
+    }
+
+    #[test]
+    fn test_not_existing_file() {
+        assert_matches!(get_file_icon("NOT EXISTING", 32), Err(Error::PathDoesNotExist));
+    }
+
+    #[test]
+    fn test_null_icon_size() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+
+        assert_matches!(get_file_icon(file_path, 0), Err(Error::NullIconSize));
+    }
+
+    #[test]
+    fn test_get_file_icons() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let paths = [
+            file_path.as_path(),
+            Path::new("NOT EXISTING"),
+            file_path.as_path(),
+        ];
+        let icons = get_file_icons(&paths, 32);
+
+        assert_eq!(icons.len(), 3);
+        assert!(icons[0].is_ok());
+        assert_matches!(icons[1], Err(Error::PathDoesNotExist));
+        assert!(icons[2].is_ok());
+        assert_matches!(
+            get_file_icons(&paths, 0)[..],
+            [
+                Err(Error::NullIconSize),
+                Err(Error::PathDoesNotExist),
+                Err(Error::NullIconSize)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_get_file_icons_parallel() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let mut paths = vec![file_path.as_path(); 5];
+
+        paths.insert(2, Path::new("NOT EXISTING"));
+
+        for threads in [0, 1, 4] {
+            let icons = get_file_icons_parallel(&paths, 32, threads);
+
+            assert_eq!(icons.len(), 6);
+            assert_matches!(icons[2], Err(Error::PathDoesNotExist));
+            assert_eq!(
+                icons.iter().filter(|icon| icon.is_ok()).count(),
+                5,
+                "{threads} threads"
+            );
+        }
+        assert_matches!(
+            get_file_icons_parallel(&paths, 0, 4)[..2],
+            [Err(Error::NullIconSize), Err(Error::NullIconSize)]
+        );
+    }
+
+    #[test]
+    fn test_get_icon_for_extension() {
+        assert!(get_icon_for_extension("txt", 32).is_ok());
+        assert!(get_icon_for_extension(".txt", 32).is_ok());
+        assert_matches!(get_icon_for_extension("txt", 0), Err(Error::NullIconSize));
+    }
+
+    // The largest system image list has icons of 256 pixels.
+    #[cfg(all(target_os = "windows", not(feature = "stub")))]
+    #[test]
+    fn test_get_icon_for_extension_above_jumbo_size() {
+        let icon = get_icon_for_extension("txt", 512).expect("icon");
+
+        assert_eq!((icon.width, icon.height), (512, 512));
+    }
+
+    #[test]
+    fn test_get_file_icon_cancellable() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let cancellation = CancellationToken::new();
+
+        assert!(get_file_icon_cancellable(&file_path, 32, &cancellation).is_ok());
+
+        cancellation.clone().cancel();
+        assert!(cancellation.is_cancelled());
+        assert_matches!(
+            get_file_icon_cancellable(&file_path, 32, &cancellation),
+            Err(Error::Cancelled)
+        );
+    }
+
+    #[test]
+    fn test_cancellation_callbacks() {
+        let cancellation = CancellationToken::new();
+        let called = Arc::new(AtomicUsize::new(0));
+        let on_cancel = |called: &Arc<AtomicUsize>| {
+            let called = called.clone();
+
+            move || {
+                called.fetch_add(1, Ordering::SeqCst);
+            }
+        };
+
+        drop(cancellation.on_cancel(on_cancel(&called)));
+        let _guard = cancellation.on_cancel(on_cancel(&called));
+
+        cancellation.cancel();
+        cancellation.cancel();
+        assert_eq!(called.load(Ordering::SeqCst), 1);
+
+        let _guard = cancellation.on_cancel(on_cancel(&called));
+        assert_eq!(called.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_get_file_icon_with_size() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let size = IconSize::new(32).expect("non null size");
+        let icon = get_file_icon_with_size(&file_path, size).expect("get icon");
+
+        assert_eq!(icon, get_file_icon(&file_path, 32).expect("get icon"));
+        assert_eq!(IconSize::new(0), None);
+        assert_matches!(IconSize::try_from(0), Err(Error::NullIconSize));
+        assert_eq!(u16::from(size), 32);
+
+        let provider = Provider::<Rc<Icon>>::with_size(size, Rc::new).expect("create provider");
+
+        assert_eq!(
+            provider.get_file_icon(&file_path).expect("get icon").width,
+            icon.width
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_get_symlink_icon() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let directory = std::env::temp_dir().join(format!(
+            "file_icon_provider_symlink_icon_{}",
+            std::process::id()
+        ));
+        let link_path = directory.join("link");
+        let broken_link_path = directory.join("broken_link");
+
+        std::fs::create_dir_all(&directory).expect("create directory");
+
+        std::os::unix::fs::symlink(&file_path, &link_path).expect("create link");
+        std::os::unix::fs::symlink("NOT EXISTING", &broken_link_path).expect("create link");
+
+        let file_icon = get_file_icon(&file_path, 32).expect("get icon");
+        let link_icon = get_symlink_icon(&link_path, 32);
+        let broken_link_icon = get_symlink_icon(&broken_link_path, 32);
+
+        std::fs::remove_dir_all(&directory).expect("remove directory");
+
+        let link_icon = link_icon.expect("get link icon");
+
+        assert_eq!(
+            get_symlink_icon(&file_path, 32).expect("get icon"),
+            file_icon
+        );
+        assert_eq!(
+            (link_icon.width, link_icon.height),
+            (file_icon.width, file_icon.height)
+        );
+        assert!(broken_link_icon.is_ok());
+        assert_matches!(get_symlink_icon(&file_path, 0), Err(Error::NullIconSize));
+        assert_matches!(
+            get_symlink_icon("NOT EXISTING", 32),
+            Err(Error::PathDoesNotExist)
+        );
+    }
+
+    #[test]
+    fn test_get_file_icon_detailed() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let (icon, source) = get_file_icon_detailed(&file_path, 32).expect("get icon");
+
+        assert_eq!(icon, get_file_icon(&file_path, 32).expect("get icon"));
+        assert_ne!(source, IconSource::Thumbnail);
+        assert_matches!(
+            get_file_icon_detailed("NOT EXISTING", 32),
+            Err(Error::PathDoesNotExist)
+        );
+        assert_matches!(
+            get_file_icon_detailed(&file_path, 0),
+            Err(Error::NullIconSize)
+        );
+    }
+
+    #[test]
+    fn test_get_icon_for_name() {
+        assert!(get_icon_for_name("report.final.pdf", 32).is_ok());
+        assert!(get_icon_for_name("Makefile", 32).is_ok());
+        assert!(get_icon_for_name(".gitignore", 32).is_ok());
+        assert_matches!(get_icon_for_name("", 32), Err(Error::Failed(_)));
+        assert_matches!(get_icon_for_name("..", 32), Err(Error::Failed(_)));
+        assert_matches!(get_icon_for_name("Makefile", 0), Err(Error::NullIconSize));
+    }
+
+    #[test]
+    fn test_get_icon_for_mime_type() {
+        assert!(get_icon_for_mime_type("text/plain", 32).is_ok());
+        assert_matches!(
+            get_icon_for_mime_type("not a mime type", 32),
+            Err(Error::Failed(_))
+        );
+        assert_matches!(get_icon_for_mime_type("text/", 32), Err(Error::Failed(_)));
+    }
+
+    #[test]
+    fn test_get_folder_icon() {
+        assert!(get_folder_icon(32).is_ok());
+        assert_matches!(get_folder_icon(0), Err(Error::NullIconSize));
+    }
+
+    #[test]
+    fn test_get_default_file_icon() {
+        assert!(get_default_file_icon(32).is_ok());
+        assert_matches!(get_default_file_icon(0), Err(Error::NullIconSize));
+    }
+
+    #[test]
+    fn test_get_known_folder_icon() {
+        assert!(get_known_folder_icon(KnownFolder::Documents, 32).is_ok());
+        assert!(get_known_folder_icon(KnownFolder::RecycleBin, 32).is_ok());
+        assert_matches!(
+            get_known_folder_icon(KnownFolder::Desktop, 0),
+            Err(Error::NullIconSize)
+        );
+    }
+
+    #[test]
+    fn test_get_current_process_icon() {
+        let icon = get_current_process_icon(32).expect("process icon");
+
+        assert!(icon.width > 0 && icon.height > 0);
+        assert!(icon.has_valid_buffer());
+        assert_matches!(get_current_process_icon(0), Err(Error::NullIconSize));
+    }
+
+    #[test]
+    fn test_get_volume_icon() {
+        let current_dir = std::env::current_dir().expect("current directory");
+        let root = current_dir.ancestors().last().expect("root directory");
+
+        assert!(get_volume_icon(root, 32).is_ok());
+        assert_matches!(get_volume_icon(root, 0), Err(Error::NullIconSize));
+        assert_matches!(
+            get_volume_icon("NOT EXISTING", 32),
+            Err(Error::PathDoesNotExist)
+        );
+    }
+
+    #[cfg(all(target_os = "windows", not(feature = "stub")))]
+    #[test]
+    fn test_get_known_folder_icon_of_virtual_folder() {
+        assert!(get_known_folder_icon(KnownFolder::ThisPc, 32).is_ok());
+    }
+
+    #[test]
+    fn test_get_file_thumbnail_falls_back_to_icon() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+
+        assert!(get_file_thumbnail(&file_path, 32, true).is_ok());
+        assert_matches!(
+            get_file_thumbnail("NOT EXISTING", 32, true),
+            Err(Error::PathDoesNotExist)
+        );
+    }
+
+    #[test]
+    fn test_get_file_icon_sizes() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let icons = get_file_icon_sizes(&file_path, &[16, 48, 32]).expect("get icons");
+
+        assert_eq!(icons.len(), 3);
+        assert!(icons[0].width <= icons[1].width);
+        assert_matches!(
+            get_file_icon_sizes(&file_path, &[16, 0]),
+            Err(Error::NullIconSize)
+        );
+        assert_matches!(
+            get_file_icon_sizes("NOT EXISTING", &[16]),
+            Err(Error::PathDoesNotExist)
+        );
+    }
+
+    #[test]
+    fn test_failed_error_source() {
+        let error = get_icon_for_mime_type("text/", 32).unwrap_err();
+
+        assert_eq!(
+            std::error::Error::source(&error).map(ToString::to_string),
+            Some("Invalid MIME type 'text/'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_get_file_icon_provider() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let provider =
+            Provider::<Rc<Icon>>::new(32, |icon| Rc::new(icon)).expect("create provider");
+
+        assert!(provider.get_file_icon(file_path).is_ok());
+    }
+
+    #[test]
+    fn test_provider_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+
+        assert_send_sync::<Provider<Arc<Icon>>>();
+    }
+
+    #[test]
+    fn test_provider_weak_generic_icon_stats() {
+        let provider = Provider::weak(32, Arc::new).expect("create provider");
+        let icon = provider
+            .get_file_icon_or_default("NOT EXISTING", 32)
+            .expect("generic icon");
+
+        drop(icon);
+
+        // The converted icon was dropped with its pixels, the request is only counted as a miss.
+        let icon = provider
+            .get_file_icon_or_default("NOT EXISTING", 32)
+            .expect("retrieved again");
+
+        assert!(Arc::ptr_eq(
+            &icon,
+            &provider
+                .get_file_icon_or_default("NOT EXISTING", 32)
+                .expect("shared icon")
+        ));
+        assert_eq!(provider.stats(), CacheStats { hits: 1, misses: 2 });
+    }
+
+    #[test]
+    fn test_provider_weak() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let provider = Provider::weak(32, Arc::new).expect("create provider");
+        let icon = provider.get_file_icon(&file_path).expect("icon");
+
+        assert!(Arc::ptr_eq(
+            &icon,
+            &provider.get_file_icon(&file_path).expect("shared icon")
+        ));
+        assert_eq!(Arc::strong_count(&icon), 1);
+
+        drop(icon);
+
+        // The pixels were released with the icon, it is retrieved again.
+        let icon = provider.get_file_icon(&file_path).expect("retrieved again");
+
+        assert_eq!(Arc::strong_count(&icon), 1);
+        assert_eq!(provider.stats(), CacheStats { hits: 1, misses: 2 });
+        assert_eq!(
+            provider
+                .get_file_icon_owned(&file_path)
+                .expect("owned icon"),
+            *icon
+        );
+    }
+
+    #[test]
+    fn test_provider_capacity() {
+        let manifest_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let source_path = manifest_path.with_file_name("src").join("lib.rs");
+        let provider = Provider::with_capacity(32, Rc::new, 1).expect("create provider");
+
+        assert!(provider.is_empty());
+        assert!(provider.get_file_icon(&manifest_path).is_ok());
+        assert!(provider.get_file_icon(&source_path).is_ok());
+        assert_eq!(provider.len(), 1);
+        assert_eq!(provider.capacity(), Some(1));
+    }
+
+    #[test]
+    fn test_provider_on_evict() {
+        use std::sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        };
+
+        let manifest_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let source_path = manifest_path.with_file_name("src").join("lib.rs");
+        let evicted = Arc::new(AtomicUsize::new(0));
+        let provider = Provider::with_capacity(32, Rc::new, 1)
+            .expect("create provider")
+            .on_evict({
+                let evicted = evicted.clone();
+
+                move |icon: &Rc<Icon>| {
+                    assert!(icon.width > 0);
+                    evicted.fetch_add(1, Ordering::Relaxed);
+                }
+            });
+
+        assert!(provider.get_file_icon(&manifest_path).is_ok());
+        assert!(provider.get_file_icon(&source_path).is_ok());
+        assert_eq!(evicted.load(Ordering::Relaxed), 1);
+
+        provider.clear();
+        assert_eq!(evicted.load(Ordering::Relaxed), 2);
+
+        // The prefetched icons are not converted, there is nothing to release.
+        provider.prefetch(&[&manifest_path], 32);
+        assert_eq!(provider.len(), 1);
+
+        provider.clear();
+        assert_eq!(evicted.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn test_provider_invalidate() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let provider = Provider::<Rc<Icon>>::new(32, Rc::new).expect("create provider");
+
+        assert!(provider.get_file_icon(&file_path).is_ok());
+        assert_eq!(provider.len(), 1);
+
+        provider.invalidate_size(&file_path, 16);
+        assert_eq!(provider.len(), 1);
+
+        provider.invalidate(&file_path);
+        assert!(provider.is_empty());
+    }
+
+    #[test]
+    fn test_provider_contains() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let provider = Provider::<Rc<Icon>>::new(32, Rc::new).expect("create provider");
+
+        assert!(!provider.contains(&file_path, 32));
+        assert!(provider.get_file_icon(&file_path).is_ok());
+        assert!(provider.contains(&file_path, 32));
+        assert!(!provider.contains(&file_path, 16));
+        assert!(!provider.contains("NOT EXISTING", 32));
+        assert_eq!(provider.len(), 1);
+        assert_eq!(provider.stats(), CacheStats { hits: 0, misses: 1 });
+    }
+
+    #[test]
+    fn test_provider_clear() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let provider = Provider::<Rc<Icon>>::new(32, Rc::new).expect("create provider");
+
+        assert!(provider.get_file_icon(&file_path).is_ok());
+        assert!(!provider.is_empty());
+
+        provider.clear();
+        assert!(provider.is_empty());
+    }
+
+    #[test]
+    fn test_provider_cache_hits() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let provider = Provider::<Rc<Icon>>::new(32, Rc::new).expect("create provider");
+
+        let (_, cached) = provider.get_file_icon_cached(&file_path).expect("get icon");
+        assert!(!cached);
+
+        let (_, cached) = provider.get_file_icon_cached(&file_path).expect("get icon");
+        assert!(cached);
+
+        assert_eq!(provider.stats(), CacheStats { hits: 1, misses: 1 });
+    }
+
+    #[test]
+    fn test_provider_color_schemes_are_cached_separately() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let mut provider = Provider::<Rc<Icon>>::new(32, Rc::new).expect("create provider");
+
+        assert_eq!(provider.color_scheme(), ColorScheme::System);
+        assert!(provider.get_file_icon(&file_path).is_ok());
+
+        provider.set_color_scheme(ColorScheme::Dark);
+        assert!(provider.get_file_icon(&file_path).is_ok());
+        assert_eq!(provider.len(), 2);
+
+        provider.invalidate(&file_path);
+        assert!(provider.is_empty());
+    }
+
+    #[test]
+    fn test_provider_sizes_are_cached_separately() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let provider = Provider::<Rc<Icon>>::new(16, Rc::new).expect("create provider");
+
+        assert!(provider.get_file_icon(&file_path).is_ok());
+        assert!(provider.get_file_icon_sized(&file_path, 64).is_ok());
+        assert_eq!(provider.len(), 2);
+        assert_matches!(
+            provider.get_file_icon_sized(&file_path, 0),
+            Err(Error::NullIconSize)
+        );
+
+        provider.invalidate_size(&file_path, 64);
+        assert_eq!(provider.len(), 1);
+    }
+
+    #[test]
+    fn test_provider_get_file_icon_or_default() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let directory_path = file_path.parent().expect("parent directory");
+        let provider = Provider::<Rc<Icon>>::new(32, Rc::new).expect("create provider");
+
+        assert!(provider.get_file_icon_or_default(&file_path, 32).is_ok());
+        assert!(
+            provider
+                .get_file_icon_or_default(directory_path, 32)
+                .is_ok()
+        );
+
+        let generic_icon = provider
+            .get_file_icon_or_default("NOT EXISTING", 32)
+            .expect("generic icon");
+        let stats = provider.stats();
+
+        assert_eq!(
+            provider
+                .get_file_icon_or_default("NOT EXISTING EITHER", 32)
+                .expect("generic icon"),
+            generic_icon
+        );
+        assert_eq!(provider.stats().hits, stats.hits + 1);
+        assert_matches!(
+            provider.get_file_icon_or_default("NOT EXISTING", 0),
+            Err(Error::NullIconSize)
+        );
+    }
+
+    #[test]
+    fn test_provider_cache_failures() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let provider = Provider::<Rc<Icon>>::new(32, Rc::new)
+            .expect("create provider")
+            .cache_failures(true);
+        let failure_key = |size| super::FailureKey {
+            path: file_path.canonicalize().expect("canonicalize path"),
+            size,
+            color_scheme: ColorScheme::System,
+            scale: 1.0f32.to_bits(),
+        };
+
+        // A failure is simulated, the system is not asked again for this file and size.
+        provider
+            .failures()
+            .insert(failure_key(32), "Simulated failure".to_owned());
+        assert_matches!(provider.get_file_icon(&file_path), Err(Error::Failed(_)));
+        assert!(provider.get_file_icon_sized(&file_path, 64).is_ok());
+
+        provider.invalidate_size(&file_path, 32);
+        assert!(provider.get_file_icon(&file_path).is_ok());
+
+        provider
+            .failures()
+            .insert(failure_key(16), "Simulated failure".to_owned());
+        provider.clear();
+        assert!(provider.get_file_icon_sized(&file_path, 16).is_ok());
+    }
+
+    #[test]
+    fn test_provider_equivalent_paths_share_entries() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let directory = file_path.parent().expect("parent");
+        let equivalent_paths = [
+            file_path.clone(),
+            directory.join(".").join("Cargo.toml"),
+            directory.join("src").join("..").join("Cargo.toml"),
+        ];
+        let provider = Provider::<Rc<Icon>>::new(32, Rc::new)
+            .expect("create provider")
+            .cache_failures(true);
+
+        for path in &equivalent_paths {
+            provider.get_file_icon(path).expect("get icon");
+        }
+
+        assert_eq!(provider.len(), 1);
+        assert_eq!(provider.stats().hits, 2);
+
+        provider.failures().insert(
+            super::FailureKey {
+                path: file_path.canonicalize().expect("canonicalize path"),
+                size: 32,
+                color_scheme: ColorScheme::System,
+                scale: 1.0f32.to_bits(),
+            },
+            "Simulated failure".to_owned(),
+        );
+
+        for path in &equivalent_paths {
+            assert_matches!(provider.get_file_icon(path), Err(Error::Failed(_)));
+        }
+
+        provider.invalidate(&equivalent_paths[2]);
+        assert!(provider.get_file_icon(&file_path).is_ok());
+    }
+
+    #[test]
+    fn test_provider_scales_are_cached_separately() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let mut provider = Provider::<Rc<Icon>>::new(16, Rc::new).expect("create provider");
+
+        let icon = provider.get_file_icon(&file_path).expect("get icon");
+        assert!((provider.scale() - 1.0).abs() < f32::EPSILON);
+
+        provider.set_scale(2.0).expect("valid scale");
+        let scaled_icon = provider.get_file_icon(&file_path).expect("get icon");
+        assert!(scaled_icon.width > icon.width);
+        assert_eq!(provider.len(), 2);
+    }
+
+    #[test]
+    fn test_provider_set_invalid_scale() {
+        let mut provider = Provider::<Rc<Icon>>::new(16, Rc::new).expect("create provider");
+
+        for scale in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            assert_matches!(provider.set_scale(scale), Err(Error::InvalidScale(_)));
+        }
+        assert!((provider.scale() - 1.0).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn test_provider_builder() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let provider = Provider::builder()
+            .icon_size(16)
+            .theme("NOT EXISTING")
+            .color_scheme(ColorScheme::Dark)
+            .scale(2.0)
+            .capacity(4)
+            .converter(Rc::new)
+            .build()
+            .expect("create provider");
+
+        assert_eq!(provider.color_scheme(), ColorScheme::Dark);
+        assert!((provider.scale() - 2.0).abs() < f32::EPSILON);
+        assert_eq!(provider.capacity(), Some(4));
+        assert!(provider.get_file_icon(&file_path).expect("get icon").width > 16);
+
+        assert_matches!(
+            Provider::<Rc<Icon>>::builder().icon_size(16).build().err(),
+            Some(Error::Failed(_))
+        );
+        assert_matches!(
+            Provider::builder().converter(Rc::new).build().err(),
+            Some(Error::NullIconSize)
+        );
+        assert_matches!(
+            Provider::builder()
+                .icon_size(16)
+                .scale(0.0)
+                .converter(Rc::new)
+                .build()
+                .err(),
+            Some(Error::InvalidScale(_))
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_provider_hidden_files() {
+        let directory = std::env::temp_dir().join(format!(
+            "file_icon_provider_hidden_files_{}",
+            std::process::id()
+        ));
+        let visible_path = directory.join("visible.txt");
+        let hidden_path = directory.join(".hidden.txt");
+
+        std::fs::create_dir_all(&directory).expect("create directory");
+        std::fs::write(&visible_path, "visible").expect("create file");
+        std::fs::write(&hidden_path, "hidden").expect("create file");
+
+        let provider = Provider::builder()
+            .icon_size(16)
+            .hidden_files(super::HiddenFiles::Dimmed(0))
+            .converter(Rc::new)
+            .build()
+            .expect("create provider");
+        let visible_icon = provider.get_file_icon(&visible_path);
+        let hidden_icon = provider.get_file_icon(&hidden_path);
+
+        std::fs::remove_dir_all(&directory).expect("remove directory");
+
+        let visible_icon = visible_icon.expect("get icon");
+        let hidden_icon = hidden_icon.expect("get icon");
+
+        assert!(
+            visible_icon
+                .pixels
+                .chunks_exact(4)
+                .any(|pixel| pixel[3] > 0)
+        );
+        assert!(
+            hidden_icon
+                .pixels
+                .chunks_exact(4)
+                .all(|pixel| pixel[3] == 0)
+        );
+        assert_eq!(provider.len(), 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_provider_get_file_icon_by_type() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let directory = std::env::temp_dir().join(format!(
+            "file_icon_provider_icon_by_type_{}",
+            std::process::id()
+        ));
+        let scripts = [directory.join("first.sh"), directory.join("second.SH")];
+
+        std::fs::create_dir_all(&directory).expect("create directory");
+
+        for script in &scripts {
+            std::fs::write(script, "#!/bin/sh\n").expect("create script");
+            std::fs::set_permissions(script, std::fs::Permissions::from_mode(0o755))
+                .expect("make script executable");
+        }
+
+        let provider = Provider::<Rc<Icon>>::new(32, Rc::new).expect("create provider");
+        let icons: Vec<_> = scripts
+            .iter()
+            .map(|script| provider.get_file_icon_by_type(script, 32))
+            .collect();
+        let folder_icon = provider.get_file_icon_by_type(&directory, 32);
+
+        std::fs::remove_dir_all(&directory).expect("remove directory");
+
+        for icon in icons {
+            assert!(icon.expect("get icon").width > 0);
+        }
+        assert!(folder_icon.is_ok());
+        assert_eq!(provider.len(), 2);
+        assert_eq!(provider.stats().hits, 1);
+        assert_matches!(
+            provider.get_file_icon_by_type("NOT EXISTING", 32),
+            Err(Error::PathDoesNotExist)
+        );
+    }
+
+    #[test]
+    fn test_provider_export_atlas() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let directory = file_path.parent().expect("parent").to_path_buf();
+        let equivalent_path = directory.join("src").join("..").join("Cargo.toml");
+        let source_path = directory.join("src").join("lib.rs");
+        let provider = Provider::<Rc<Icon>>::new(32, Rc::new).expect("create provider");
+
+        for path in [&file_path, &equivalent_path, &source_path] {
+            assert!(provider.get_file_icon(path).is_ok());
+        }
+        assert!(provider.get_file_icon("NOT EXISTING").is_err());
+
+        let (atlas, rects) = provider.export_atlas();
+        let rect = rects[&file_path];
+        let icon = provider.get_file_icon_owned(&file_path).expect("get icon");
+
+        assert_eq!(rects.len(), 3);
+        assert_eq!(rects[&equivalent_path], rect);
+        assert_eq!(
+            rects[&source_path] == rect,
+            provider.len() == 1,
+            "the files share a rectangle only if they share a cache entry"
+        );
+        assert_eq!((rect.width, rect.height), (icon.width, icon.height));
+        assert_eq!(atlas.format, icon.format);
+
+        for y in 0..icon.height {
+            for x in 0..icon.width {
+                assert_eq!(
+                    atlas.get_pixel(rect.x + x, rect.y + y),
+                    icon.get_pixel(x, y)
+                );
+            }
+        }
+
+        provider.clear();
+
+        let (atlas, rects) = provider.export_atlas();
+
+        assert!(rects.is_empty());
+        assert_eq!((atlas.width, atlas.height), (0, 0));
+    }
+
+    #[test]
+    fn test_get_file_icon_timeout() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+
+        assert!(get_file_icon_timeout(&file_path, 32, Duration::from_secs(10)).is_ok());
+    }
+
+    #[test]
+    fn test_get_file_icon_symbolic() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let icon = get_file_icon_symbolic(&file_path, 16, [255, 0, 0, 255]).expect("icon");
+
+        assert!(icon.width > 0 && icon.height > 0);
+        // Only the Linux backends paint the symbolic icons with the foreground color.
+        #[cfg(all(target_os = "linux", not(feature = "stub")))]
+        assert!(
+            icon.rgba_pixels()
+                .chunks_exact(4)
+                .all(|pixel| pixel[..3] == [255, 0, 0])
+        );
+        assert_matches!(
+            get_file_icon_symbolic("NOT EXISTING", 16, [255, 0, 0, 255]),
+            Err(Error::PathDoesNotExist)
+        );
+    }
+
+    #[test]
+    fn test_get_file_icon_with_dpi() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+
+        assert!(get_file_icon_with_dpi(&file_path, 16, 192).is_ok());
+        assert_matches!(
+            get_file_icon_with_dpi(&file_path, 16, 0),
+            Err(Error::InvalidScale(_))
+        );
+        assert_matches!(
+            get_file_icon_with_dpi(&file_path, 0, 96),
+            Err(Error::NullIconSize)
+        );
+    }
+
+    #[test]
+    fn test_get_file_icon_with_scale_mode() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+
+        for scale_mode in [ScaleMode::ScaleUp, ScaleMode::Native] {
+            let icon = get_file_icon_with_scale_mode(&file_path, 100, scale_mode).expect("icon");
+
+            assert!(icon.width.max(icon.height) <= 100);
+            assert!(icon.has_valid_buffer());
+        }
+        assert_matches!(
+            get_file_icon_with_scale_mode("NOT EXISTING", 32, ScaleMode::Native),
+            Err(Error::PathDoesNotExist)
+        );
+    }
+
+    #[cfg(all(target_os = "windows", not(feature = "stub")))]
+    #[test]
+    fn test_get_file_icon_with_native_scale_mode() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let scaled =
+            get_file_icon_with_scale_mode(&file_path, 512, ScaleMode::ScaleUp).expect("icon");
+        let native =
+            get_file_icon_with_scale_mode(&file_path, 512, ScaleMode::Native).expect("icon");
+
+        assert_eq!((scaled.width, scaled.height), (512, 512));
+        // The largest native icons of the shell are the ones of the jumbo image list.
+        assert!(native.width.max(native.height) <= 256);
+        assert!(native.has_valid_buffer());
+    }
+
+    #[test]
+    fn test_available_sizes() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let sizes = available_sizes(&file_path).expect("sizes");
+
+        assert!(sizes.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(sizes.iter().all(|size| *size > 0));
+        assert_matches!(
+            available_sizes("NOT EXISTING"),
+            Err(Error::PathDoesNotExist)
+        );
+    }
+
+    #[test]
+    fn test_get_file_icon_unchecked() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+
+        assert_eq!(
+            get_file_icon_unchecked(&file_path, 32).expect("unchecked icon"),
+            get_file_icon(&file_path, 32).expect("icon")
+        );
+        assert_matches!(
+            get_file_icon_unchecked(&file_path, 0),
+            Err(Error::NullIconSize)
+        );
+    }
+
+    #[test]
+    fn test_get_file_icon_premultiplied() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let icon = get_file_icon_premultiplied(&file_path, 32).expect("premultiplied icon");
+
+        assert_eq!(
+            icon,
+            get_file_icon(&file_path, 32).expect("icon").premultiplied()
+        );
+        assert_matches!(
+            get_file_icon_premultiplied("NOT EXISTING", 32),
+            Err(Error::PathDoesNotExist)
+        );
+    }
+
+    #[test]
+    fn test_get_file_icon_nearest() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let icon = get_file_icon_nearest(&file_path, 40).expect("nearest icon");
+
+        assert_eq!(icon.width.max(icon.height), 40);
+        assert_eq!(icon.pixels.len(), (icon.width * icon.height * 4) as usize);
+        assert_matches!(
+            get_file_icon_nearest("NOT EXISTING", 40),
+            Err(Error::PathDoesNotExist)
+        );
+        assert_matches!(
+            get_file_icon_nearest(&file_path, 0),
+            Err(Error::NullIconSize)
+        );
+    }
+
+    #[test]
+    fn test_nearest_size() {
+        assert_eq!(nearest_size(&[16, 32, 48, 256], 40), 48);
+        assert_eq!(nearest_size(&[16, 32, 48, 256], 32), 32);
+        assert_eq!(nearest_size(&[16, 32, 48], 64), 48);
+        assert_eq!(nearest_size(&[], 40), 40);
+    }
+
+    #[test]
+    fn test_get_file_type_info() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let info = get_file_type_info(&file_path, 32).expect("file type");
+
+        assert!(!info.identifier.is_empty());
+        assert!(!info.description.is_empty());
+        assert_eq!(
+            info.icon,
+            Some(get_file_icon(&file_path, 32).expect("icon"))
+        );
+        assert_matches!(
+            get_file_type_info("NOT EXISTING", 32),
+            Err(Error::PathDoesNotExist)
+        );
+        assert_matches!(get_file_type_info(&file_path, 0), Err(Error::NullIconSize));
+    }
+
+    #[test]
+    fn test_get_file_icon_with_scale() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+
+        assert!(get_file_icon_with_scale(&file_path, 16, 2.0).is_ok());
+        assert_matches!(
+            get_file_icon_with_scale(&file_path, 16, 0.0),
+            Err(Error::InvalidScale(_))
+        );
+        assert_matches!(
+            get_file_icon_with_scale(&file_path, 0, 2.0),
+            Err(Error::NullIconSize)
+        );
+    }
+
+    #[test]
+    fn test_provider_capturing_converter() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let conversions = Arc::new(AtomicUsize::new(0));
+        let provider = Provider::new(32, {
+            let conversions = conversions.clone();
 
-        Ok(Self {
-            implementation: implementation::Provider::new(icon_size, converter)
-                .ok_or(Error::Failed)?,
+            move |icon| {
+                conversions.fetch_add(1, Ordering::Relaxed);
+                Rc::new(icon)
+            }
         })
+        .expect("create provider");
+
+        assert!(provider.get_file_icon(&file_path).is_ok());
+        assert_eq!(conversions.load(Ordering::Relaxed), 1);
     }
 
-    /// Retrieves the icon for a given file.
-    ///
-    /// # Parameters
-    /// * `path` - A file path for which the icon is to be retrieved.
-    /// # Returns
-    /// * `Ok(Icon)` - If the icon is successfully retrieved.
-    /// * `Err(Error)` - If the icon could not be retrieved.
-    /// # Errors
-    /// See [`Error`] for the reasons of failures.
-    /// # Example
-    /// ```
-    /// use file_icon_provider::{Provider, Icon};
-    /// use std::rc::Rc;
-    ///
-    /// let provider: Provider<Rc<Icon>> = Provider::new(64, Rc::new).unwrap();
-    ///
-    /// if let Ok(icon) = provider.get_file_icon("path/to/file") {
-    ///     println!("Icon dimensions: {}x{}", icon.width, icon.height);
-    /// } else {
-    ///     println!("Failed to retrieve the icon.");
-    /// }
-    /// ```
-    ///
-    /// # Caveats
-    ///
-    /// On linux, this function must be called on the main thread.
-    pub fn get_file_icon(&self, path: impl AsRef<Path>) -> Result<T, Error> {
-        let path = path.as_ref();
+    #[test]
+    fn test_provider_get_file_icon_owned() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let conversions = Arc::new(AtomicUsize::new(0));
+        let provider = Provider::new(32, {
+            let conversions = conversions.clone();
 
-        if !path.exists() {
-            return Err(Error::PathDoesNotExist);
-        }
+            move |icon| {
+                conversions.fetch_add(1, Ordering::Relaxed);
+                Rc::new(icon)
+            }
+        })
+        .expect("create provider");
 
-        self.implementation.get_file_icon(path).ok_or(Error::Failed)
+        let icon = provider.get_file_icon_owned(&file_path).expect("get icon");
+        assert_eq!(conversions.load(Ordering::Relaxed), 0);
+
+        assert_eq!(*provider.get_file_icon(&file_path).expect("get icon"), icon);
+        assert!(provider.get_file_icon(&file_path).is_ok());
+        assert_eq!(conversions.load(Ordering::Relaxed), 1);
+        assert_eq!(provider.stats(), CacheStats { hits: 2, misses: 1 });
     }
-}
 
-mod implementation {
-    #[cfg(target_os = "macos")]
-    mod macos;
+    #[test]
+    fn test_provider_with_icon() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let conversions = Arc::new(AtomicUsize::new(0));
+        let provider = Provider::new(32, {
+            let conversions = conversions.clone();
 
-    #[cfg(target_os = "macos")]
-    pub(crate) use macos::get_file_icon;
+            move |icon| {
+                conversions.fetch_add(1, Ordering::Relaxed);
+                icon
+            }
+        })
+        .expect("create provider");
 
-    #[cfg(target_os = "macos")]
-    pub(crate) use macos::Provider;
+        let len = provider
+            .with_icon(&file_path, 32, |icon| icon.pixels.len())
+            .expect("get icon");
+        assert!(provider.contains(&file_path, 32));
 
-    #[cfg(target_os = "windows")]
-    mod windows;
+        let icon = provider.get_file_icon(&file_path).expect("get icon");
+        assert_eq!(icon.pixels.len(), len);
+        assert_eq!(
+            provider.with_icon(&file_path, 32, Icon::clone).ok(),
+            Some(icon)
+        );
+        assert_eq!(conversions.load(Ordering::Relaxed), 1);
+        assert_matches!(
+            provider.with_icon("NOT EXISTING", 32, |_| ()),
+            Err(Error::PathDoesNotExist)
+        );
+    }
 
-    #[cfg(target_os = "windows")]
-    pub(crate) use windows::get_file_icon;
+    #[test]
+    fn test_provider_refresh() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let provider = Provider::<Rc<Icon>>::new(32, Rc::new).expect("create provider");
 
-    #[cfg(target_os = "windows")]
-    pub(crate) use windows::Provider;
+        assert_matches!(provider.refresh(&file_path, 32), Ok(true));
+        assert!(provider.contains(&file_path, 32));
+        assert_matches!(provider.refresh(&file_path, 32), Ok(false));
+        assert_matches!(provider.refresh(&file_path, 0), Err(Error::NullIconSize));
+        assert_matches!(
+            provider.refresh("NOT EXISTING", 32),
+            Err(Error::PathDoesNotExist)
+        );
+    }
 
-    #[cfg(target_os = "linux")]
-    mod linux;
+    // The icons of executables are not cached on Windows.
+    #[cfg(all(target_os = "windows", not(feature = "stub")))]
+    #[test]
+    fn test_provider_refresh_uncached() {
+        let executable = std::env::current_exe().expect("current executable");
+        let provider = Provider::<Rc<Icon>>::new(32, Rc::new).expect("create provider");
 
-    #[cfg(target_os = "linux")]
-    pub(crate) use linux::get_file_icon;
+        assert_matches!(provider.refresh(&executable, 32), Ok(true));
+        assert!(!provider.contains(&executable, 32));
+        assert_matches!(provider.refresh(&executable, 32), Ok(false));
 
-    #[cfg(target_os = "linux")]
-    pub(crate) use linux::Provider;
+        provider.invalidate(&executable);
+        assert_matches!(provider.refresh(&executable, 32), Ok(true));
+    }
+
+    #[test]
+    fn test_provider_on_theme_changed() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let changes = Arc::new(AtomicUsize::new(0));
+        let mut provider = Provider::<Rc<Icon>>::new(32, Rc::new).expect("create provider");
+
+        provider
+            .on_theme_changed({
+                let changes = changes.clone();
+
+                move || {
+                    changes.fetch_add(1, Ordering::Relaxed);
+                }
+            })
+            .expect("watch the theme");
+        assert!(provider.get_file_icon(&file_path).is_ok());
 
-    #[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
-    pub(crate) fn get_file_icon(path: impl AsRef<Path>, size: u16) -> Option<Icon> {
-        None
+        crate::theme::notify_theme_changed();
+        assert!(changes.load(Ordering::Relaxed) > 0);
+        assert!(!provider.contains(&file_path, 32));
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::{Error, Icon, Provider, get_file_icon};
-    use std::assert_matches;
-    use std::rc::Rc;
+    #[test]
+    fn test_provider_refresh_theme() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let provider = Provider::<Rc<Icon>>::new(32, Rc::new).expect("create provider");
+
+        assert!(provider.get_file_icon(&file_path).is_ok());
+
+        provider.refresh_theme();
+        assert!(provider.is_empty());
+    }
 
     #[test]
-    fn test_get_file_icon() {
+    fn test_provider_conversion_failure() {
         let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let provider =
+            Provider::<Icon>::try_new(32, |_| Err("conversion failed")).expect("create provider");
 
-        assert!(get_file_icon(file_path, 32).is_ok());
+        let error = provider.get_file_icon(&file_path).unwrap_err();
+
+        assert_matches!(error, Error::Conversion(_));
+        assert_eq!(
+            error.to_string(),
+            "Failed to convert icon: conversion failed"
+        );
     }
 
     #[test]
-    fn test_not_existing_file() {
-        assert_matches!(get_file_icon("NOT EXISTING", 32), Err(Error::PathDoesNotExist));
+    fn test_provider_fallible_conversion() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let provider =
+            Provider::try_new(32, |icon| Ok::<_, Error>(Rc::new(icon))).expect("create provider");
+
+        assert!(provider.get_file_icon(&file_path).is_ok());
     }
 
+    // GTK must be used from the main thread.
+    #[cfg(not(target_os = "linux"))]
     #[test]
-    fn test_null_icon_size() {
+    fn test_provider_shared_between_threads() {
         let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let provider = Arc::new(Provider::<Arc<Icon>>::new(32, Arc::new).expect("create provider"));
+        let thread = std::thread::spawn({
+            let provider = provider.clone();
+            let file_path = file_path.clone();
 
-        assert_matches!(get_file_icon(file_path, 0), Err(Error::NullIconSize));
+            move || provider.get_file_icon(file_path).is_ok()
+        });
+
+        assert!(provider.get_file_icon(&file_path).is_ok());
+        assert!(thread.join().expect("join thread"));
     }
 
+    // GTK must be used from the main thread.
+    #[cfg(any(not(target_os = "linux"), feature = "stub"))]
     #[test]
-    fn test_get_file_icon_provider() {
+    fn test_provider_concurrent_requests() {
+        use std::sync::Barrier;
+
+        const THREAD_COUNT: usize = 16;
+
         let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
-        let provider =
-            Provider::<Rc<Icon>>::new(32, |icon| Rc::new(icon)).expect("create provider");
+        let provider = Arc::new(Provider::<Arc<Icon>>::new(32, Arc::new).expect("create provider"));
+        let barrier = Arc::new(Barrier::new(THREAD_COUNT));
+        let threads: Vec<_> = (0..THREAD_COUNT)
+            .map(|_| {
+                let provider = provider.clone();
+                let barrier = barrier.clone();
+                let file_path = file_path.clone();
 
-        assert!(provider.get_file_icon(file_path).is_ok());
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    provider.get_file_icon(file_path).is_ok()
+                })
+            })
+            .collect();
+
+        for thread in threads {
+            assert!(thread.join().expect("join thread"));
+        }
+
+        // Only one of the threads retrieved the icon, the others shared it.
+        assert_eq!(
+            provider.stats(),
+            CacheStats {
+                hits: THREAD_COUNT as u64 - 1,
+                misses: 1
+            }
+        );
     }
 
     #[test]
@@ -233,11 +5670,98 @@ mod tests {
         assert!(get_file_icon(&file_path, 32).is_ok());
     }
 
-    #[cfg(target_os = "windows")]
     #[test]
-    fn test_exe_icons_are_not_cached() {
-        use std::sync::atomic::{AtomicUsize, Ordering};
+    fn test_provider_clear_older_than() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let provider = Provider::<Rc<Icon>>::new(32, Rc::new).expect("create provider");
+
+        assert!(provider.get_file_icon(&file_path).is_ok());
+        provider.clear_older_than(Duration::from_secs(10));
+        assert_eq!(provider.len(), 1);
+
+        std::thread::sleep(Duration::from_millis(2));
+        provider.clear_older_than(Duration::from_millis(1));
+        assert!(provider.is_empty());
+    }
+
+    #[test]
+    fn test_provider_prefetch() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let provider = Provider::<Rc<Icon>>::new(32, Rc::new).expect("create provider");
+
+        provider.prefetch(&[&file_path], 32);
+        provider.prefetch(&[Path::new("NOT EXISTING")], 32);
+        assert_eq!(provider.len(), 1);
+
+        let (_, cached) = provider
+            .get_file_icon_cached(&file_path)
+            .expect("cached icon");
+
+        assert!(cached);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_provider_prefetch_async() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let provider = Arc::new(Provider::<Arc<Icon>>::new(32, Arc::new).expect("create provider"));
+
+        futures::executor::block_on(provider.prefetch_async(vec![file_path], 32));
+
+        // GTK is never used from the background thread, so nothing is prefetched.
+        #[cfg(all(
+            target_os = "linux",
+            not(feature = "linux-freedesktop"),
+            not(feature = "stub")
+        ))]
+        assert!(provider.is_empty());
+        #[cfg(not(all(
+            target_os = "linux",
+            not(feature = "linux-freedesktop"),
+            not(feature = "stub")
+        )))]
+        assert_eq!(provider.len(), 1);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_get_file_icon_async() {
+        use crate::get_file_icon_async;
+
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+
+        assert!(futures::executor::block_on(get_file_icon_async(file_path, 32)).is_ok());
+        assert_matches!(
+            futures::executor::block_on(get_file_icon_async("NOT EXISTING", 32)),
+            Err(Error::PathDoesNotExist)
+        );
+    }
 
+    #[cfg(all(target_os = "windows", not(feature = "stub")))]
+    #[test]
+    fn test_image_factory_failure_does_not_hang() {
+        use std::sync::mpsc::channel;
+
+        let (sender, receiver) = channel();
+
+        // The existence check is bypassed so the request reaches the image factory thread and fails there.
+        std::thread::spawn(move || {
+            let _ = sender.send(crate::implementation::get_file_icon(
+                "NOT EXISTING",
+                32,
+                &crate::Options::default(),
+            ));
+        });
+
+        assert_matches!(
+            receiver.recv_timeout(Duration::from_secs(10)),
+            Ok(Err(Error::Failed(_)))
+        );
+    }
+
+    #[cfg(all(target_os = "windows", not(feature = "stub")))]
+    #[test]
+    fn test_exe_icons_are_not_cached() {
         static FETCHES: AtomicUsize = AtomicUsize::new(0);
 
         fn count_fetches(_icon: Icon) -> usize {
@@ -259,4 +5783,276 @@ mod tests {
             "two different .exe files must be fetched independently (executables must never be cached by extension)"
         );
     }
+
+    #[cfg(all(target_os = "macos", not(feature = "stub")))]
+    #[test]
+    fn test_provider_caches_bundles() {
+        let finder = Path::new("/System/Library/CoreServices/Finder.app");
+        let dock = Path::new("/System/Library/CoreServices/Dock.app");
+        let provider = Provider::<Rc<Icon>>::new(32, Rc::new).expect("create provider");
+
+        assert!(provider.get_file_icon(finder).is_ok());
+        assert!(provider.get_file_icon(finder).is_ok());
+        assert!(provider.get_file_icon(dock).is_ok());
+        assert_eq!(provider.stats(), CacheStats { hits: 1, misses: 2 });
+        assert_eq!(provider.len(), 2);
+    }
+
+    #[cfg(all(target_os = "macos", not(feature = "stub")))]
+    #[test]
+    fn test_icon_rows_are_packed() {
+        let folder = locate_cargo_manifest::locate_manifest()
+            .expect("locate Cargo.toml")
+            .with_file_name("src");
+
+        // Sizes whose rows are not aligned to 16 or 64 bytes, where AppKit may pad them.
+        for size in [17, 33, 65] {
+            let icon = get_file_icon(&folder, size).expect("get icon");
+            let columns: Vec<usize> = icon
+                .pixels
+                .chunks_exact(4)
+                .enumerate()
+                .filter(|(_, pixel)| pixel[3] > 0)
+                .map(|(index, _)| index % icon.width as usize)
+                .collect();
+            let left = columns.iter().min().expect("opaque pixels");
+            let right = icon.width as usize - 1 - columns.iter().max().expect("opaque pixels");
+
+            assert_eq!(
+                icon.pixels.len(),
+                icon.bytes_per_row() * icon.height as usize
+            );
+            // The folder icon is symmetric, padded rows would shift its pixels sideways.
+            assert!(left.abs_diff(right) <= usize::from(size) / 8);
+        }
+    }
+
+    #[cfg(all(target_os = "macos", not(feature = "stub")))]
+    #[test]
+    fn test_decomposed_unicode_path() {
+        // APFS only accepts valid UTF-8 names, the conversion of the bytes is checked with an accent.
+        let file_path = std::env::temp_dir().join(format!(
+            "file_icon_provider_caf\u{e9}_{}.txt",
+            std::process::id()
+        ));
+
+        std::fs::write(&file_path, "").expect("write file");
+
+        let icon = get_file_icon(&file_path, 32);
+
+        let _ = std::fs::remove_file(&file_path);
+        assert!(icon.is_ok());
+    }
+
+    #[cfg(all(target_os = "macos", not(feature = "stub")))]
+    #[test]
+    #[ignore = "APFS rejects names which are not valid UTF-8, set TMPDIR to a volume accepting them"]
+    fn test_non_unicode_path() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        let file_name = OsStr::from_bytes(b"file_icon_provider_\xff.txt");
+        let file_path = std::env::temp_dir().join(file_name);
+
+        std::fs::write(&file_path, "").expect("write file");
+
+        let icon = get_file_icon(&file_path, 32);
+
+        let _ = std::fs::remove_file(&file_path);
+        assert!(icon.is_ok());
+    }
+
+    #[cfg(all(target_os = "windows", not(feature = "stub")))]
+    #[test]
+    fn test_extended_length_path() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        // canonicalize() returns a path with the extended-length prefix, like \\?\C:\...
+        let file_path = file_path.canonicalize().expect("canonicalize path");
+
+        assert!(get_file_icon(&file_path, 32).is_ok());
+    }
+
+    #[cfg(all(target_os = "windows", not(feature = "stub")))]
+    #[test]
+    fn test_long_path() {
+        let root = std::env::temp_dir().join(format!(
+            "file_icon_provider_test_long_path_{}",
+            std::process::id()
+        ));
+        scopeguard::defer!({
+            let _ = std::fs::remove_dir_all(&root);
+        });
+        let mut directory = root.clone();
+
+        while directory.as_os_str().len() < 300 {
+            directory.push("a_directory_with_a_long_name");
+        }
+        std::fs::create_dir_all(&directory).expect("create directories");
+
+        let file_path = directory.join("file.txt");
+
+        std::fs::write(&file_path, "").expect("write file");
+
+        assert!(get_file_icon(&file_path, 32).is_ok());
+    }
+
+    #[cfg(all(target_os = "windows", not(feature = "stub")))]
+    #[test]
+    fn test_unreachable_unc_path() {
+        let path = r"\\NOT-EXISTING-SERVER.invalid\share\file.txt";
+
+        assert_matches!(get_file_icon(path, 32), Err(Error::PathDoesNotExist));
+        // The existence check is bypassed so the shell tries to reach the share.
+        assert_matches!(
+            crate::implementation::get_file_icon(path, 32, &crate::Options::default()),
+            Err(Error::Failed(_))
+        );
+    }
+
+    #[cfg(all(target_os = "windows", not(feature = "stub")))]
+    #[test]
+    fn test_timeout() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+
+        assert_matches!(
+            get_file_icon_timeout(&file_path, 32, Duration::ZERO),
+            Err(Error::Timeout)
+        );
+        // The stale request does not prevent the next ones from being answered.
+        assert!(get_file_icon(&file_path, 32).is_ok());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_get_file_icon_from_fd() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let file = std::fs::File::open(&file_path).expect("open file");
+        let (socket, _) = std::os::unix::net::UnixStream::pair().expect("create sockets");
+
+        assert_eq!(
+            crate::get_file_icon_from_fd(&file, 32).expect("icon from fd"),
+            get_file_icon(&file_path, 32).expect("icon from path")
+        );
+        assert_matches!(
+            crate::get_file_icon_from_fd(&socket, 32),
+            Err(Error::Failed(_))
+        );
+
+        let deleted_path = std::env::temp_dir().join(format!(
+            "file_icon_provider_test_get_file_icon_from_fd_{}.txt",
+            std::process::id()
+        ));
+        let deleted = std::fs::File::create(&deleted_path).expect("create file");
+
+        std::fs::remove_file(&deleted_path).expect("remove file");
+        assert_matches!(
+            crate::get_file_icon_from_fd(&deleted, 32),
+            Err(Error::PathDoesNotExist)
+        );
+    }
+
+    #[cfg(target_os = "windows")]
+    #[test]
+    fn test_get_file_icon_from_handle() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let file = std::fs::File::open(&file_path).expect("open file");
+
+        assert_eq!(
+            crate::get_file_icon_from_handle(&file, 32).expect("icon from handle"),
+            get_file_icon(&file_path, 32).expect("icon from path")
+        );
+    }
+
+    #[cfg(target_os = "windows")]
+    #[test]
+    fn test_shutdown_icon_worker() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+
+        assert!(get_file_icon(&file_path, 32).is_ok());
+        crate::shutdown_icon_worker();
+        crate::shutdown_icon_worker();
+        // The thread is started again by the next request.
+        assert!(get_file_icon(&file_path, 32).is_ok());
+        assert_eq!(get_file_icons(&[&file_path], 32).len(), 1);
+    }
+
+    #[cfg(all(target_os = "windows", not(feature = "stub")))]
+    #[test]
+    fn test_get_shell_item_icon() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+        let control_panel = "::{26EE0668-A00A-44D7-9371-BEB064C98683}";
+
+        assert_eq!(
+            crate::get_shell_item_icon(&file_path, 32).expect("icon of file"),
+            get_file_icon(&file_path, 32).expect("icon from path")
+        );
+        assert!(crate::get_shell_item_icon(control_panel, 32).is_ok());
+        assert_matches!(
+            crate::get_shell_item_icon("NOT EXISTING", 32),
+            Err(Error::Failed(_))
+        );
+        assert_matches!(
+            crate::get_shell_item_icon(control_panel, 0),
+            Err(Error::NullIconSize)
+        );
+    }
+
+    #[cfg(all(target_os = "windows", not(feature = "stub")))]
+    #[test]
+    fn test_jumbo_icon() {
+        let icon = get_file_icon(r"C:\Windows\System32\notepad.exe", 256).expect("notepad icon");
+
+        assert_eq!((icon.width, icon.height), (256, 256));
+    }
+
+    #[cfg(all(target_os = "windows", not(feature = "stub")))]
+    #[test]
+    fn test_get_icon_from_resource() {
+        let shell32 = r"C:\Windows\System32\shell32.dll";
+        let icon = crate::get_icon_from_resource(shell32, 0, 48).expect("first icon");
+
+        assert_eq!((icon.width, icon.height), (48, 48));
+        assert!(crate::get_icon_from_resource(shell32, -4, 32).is_ok());
+        assert_matches!(
+            crate::get_icon_from_resource(shell32, 100_000, 32),
+            Err(Error::Failed(_))
+        );
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_get_icon_from_resource_is_windows_only() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+
+        assert_matches!(
+            crate::get_icon_from_resource(file_path, 0, 32),
+            Err(Error::Unsupported)
+        );
+    }
+
+    #[cfg(target_os = "windows")]
+    #[test]
+    fn test_get_file_icon_sta() {
+        use windows::Win32::System::Com::{
+            COINIT_APARTMENTTHREADED, CoInitializeEx, CoUninitialize,
+        };
+
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+
+        // Without COM the request goes through the image factory thread.
+        std::thread::spawn(move || {
+            assert!(crate::get_file_icon_sta(&file_path, 32).is_ok());
+
+            unsafe { CoInitializeEx(None, COINIT_APARTMENTTHREADED) }
+                .ok()
+                .expect("initialize COM");
+            assert!(crate::get_file_icon_sta(&file_path, 32).is_ok());
+            assert_matches!(
+                crate::get_file_icon_sta(&file_path, 0),
+                Err(Error::NullIconSize)
+            );
+            unsafe { CoUninitialize() };
+        })
+        .join()
+        .expect("join thread");
+    }
 }
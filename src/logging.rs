@@ -0,0 +1,14 @@
+//! Logging macros of the crate, from `log` by default or from `tracing` with the `tracing` feature.
+
+#[cfg(not(feature = "tracing"))]
+#[cfg_attr(
+    any(not(target_os = "windows"), feature = "stub"),
+    allow(unused_imports)
+)]
+pub(crate) use log::{debug, error, warn};
+#[cfg(feature = "tracing")]
+#[cfg_attr(
+    any(not(target_os = "windows"), feature = "stub"),
+    allow(unused_imports)
+)]
+pub(crate) use tracing::{debug, error, warn};
@@ -0,0 +1,95 @@
+//! Batches of icons retrieved by several threads, see [`get_file_icons_parallel`](crate::get_file_icons_parallel).
+
+use std::{path::Path, thread};
+
+use crate::{Error, Icon};
+
+/// Splits `paths` into at most `threads` chunks and calls `get_icons` for each of them on its own thread.
+///
+/// The results are returned in the order of `paths`. The chunks are retrieved on new threads
+/// even if there is only one, so the backends can set up the threads as they need.
+#[cfg_attr(
+    all(
+        target_os = "linux",
+        not(feature = "linux-freedesktop"),
+        not(feature = "stub")
+    ),
+    allow(dead_code)
+)]
+pub(crate) fn get_icons_in_chunks<F>(
+    paths: &[&Path],
+    threads: usize,
+    get_icons: F,
+) -> Vec<Result<Icon, Error>>
+where
+    F: Fn(&[&Path]) -> Vec<Result<Icon, Error>> + Sync,
+{
+    if paths.is_empty() {
+        return Vec::new();
+    }
+
+    let chunk_size = paths.len().div_ceil(threads.max(1));
+    let get_icons = &get_icons;
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = paths
+            .chunks(chunk_size)
+            .map(|chunk| (chunk.len(), scope.spawn(move || get_icons(chunk))))
+            .collect();
+
+        handles
+            .into_iter()
+            .flat_map(|(len, handle)| {
+                handle.join().unwrap_or_else(|_| {
+                    (0..len)
+                        .map(|_| Err(Error::failed("The thread retrieving the icons panicked")))
+                        .collect()
+                })
+            })
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{assert_matches, path::Path};
+
+    use super::get_icons_in_chunks;
+    use crate::{Error, Icon};
+
+    #[test]
+    fn test_get_icons_in_chunks_keeps_order() {
+        let names: Vec<String> = (0..10).map(|index| index.to_string()).collect();
+        let paths: Vec<&Path> = names.iter().map(Path::new).collect();
+
+        for threads in [0, 1, 3, 10, 20] {
+            let icons = get_icons_in_chunks(&paths, threads, |chunk| {
+                chunk
+                    .iter()
+                    .map(|path| {
+                        let index: u32 = path.to_str().unwrap().parse().unwrap();
+
+                        Icon::new(index, 1, vec![0; index as usize * 4])
+                    })
+                    .collect()
+            });
+            let widths: Vec<u32> = icons.into_iter().map(|icon| icon.unwrap().width).collect();
+
+            assert_eq!(widths, (0..10).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn test_get_icons_in_chunks_panic() {
+        let paths = [Path::new("a"), Path::new("b")];
+        let icons = get_icons_in_chunks(&paths, 2, |chunk| {
+            assert_ne!(chunk, [Path::new("b")]);
+
+            vec![Icon::new(1, 1, vec![0; 4])]
+        });
+
+        assert!(icons[0].is_ok());
+        assert_matches!(icons[1], Err(Error::Failed(_)));
+        assert!(get_icons_in_chunks(&[], 4, |_| unreachable!()).is_empty());
+    }
+}
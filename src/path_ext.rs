@@ -0,0 +1,132 @@
+//! Extension trait to retrieve icons from paths.
+
+use std::path::{Path, PathBuf};
+
+use crate::{Error, Icon, get_file_icon};
+
+/// Retrieves icons directly from paths, as a shortcut for the functions of the crate.
+///
+/// This trait is sealed, it is only implemented for [`Path`] and [`PathBuf`].
+///
+/// # Example
+/// ```
+/// use file_icon_provider::FileIconExt;
+/// use std::path::Path;
+///
+/// if let Ok(icon) = Path::new("path/to/file").file_icon(64) {
+///     println!("Icon dimensions: {}x{}", icon.width, icon.height);
+/// }
+/// ```
+pub trait FileIconExt: private::Sealed {
+    /// Retrieves the icon of the file, see [`get_file_icon`].
+    ///
+    /// # Errors
+    /// See [`Error`] for the reasons of failures.
+    ///
+    /// # Example
+    /// ```
+    /// use file_icon_provider::FileIconExt;
+    /// use std::path::PathBuf;
+    ///
+    /// let path = PathBuf::from("path/to/file");
+    ///
+    /// if let Ok(icon) = path.file_icon(64) {
+    ///     println!("Icon dimensions: {}x{}", icon.width, icon.height);
+    /// }
+    /// ```
+    ///
+    /// # Caveats
+    ///
+    /// On linux, this function must be called on the main thread.
+    fn file_icon(&self, size: u16) -> Result<Icon, Error>;
+
+    /// Retrieves the icon of the file from the provider shared by the process,
+    /// see [`global`](crate::global).
+    ///
+    /// # Errors
+    /// See [`Error`] for the reasons of failures.
+    ///
+    /// # Example
+    /// ```
+    /// use file_icon_provider::FileIconExt;
+    /// use std::path::Path;
+    ///
+    /// if let Ok(icon) = Path::new("path/to/file").file_icon_cached(64) {
+    ///     println!("Icon dimensions: {}x{}", icon.width, icon.height);
+    /// }
+    /// ```
+    ///
+    /// # Caveats
+    ///
+    /// On linux, this function must be called on the main thread.
+    #[cfg(feature = "global")]
+    fn file_icon_cached(&self, size: u16) -> Result<Icon, Error>;
+}
+
+impl FileIconExt for Path {
+    fn file_icon(&self, size: u16) -> Result<Icon, Error> {
+        get_file_icon(self, size)
+    }
+
+    #[cfg(feature = "global")]
+    fn file_icon_cached(&self, size: u16) -> Result<Icon, Error> {
+        crate::global().icon(self, size)
+    }
+}
+
+impl FileIconExt for PathBuf {
+    fn file_icon(&self, size: u16) -> Result<Icon, Error> {
+        get_file_icon(self, size)
+    }
+
+    #[cfg(feature = "global")]
+    fn file_icon_cached(&self, size: u16) -> Result<Icon, Error> {
+        crate::global().icon(self, size)
+    }
+}
+
+mod private {
+    use std::path::{Path, PathBuf};
+
+    /// Prevents implementations outside of the crate, so methods can be added to the trait.
+    pub trait Sealed {}
+
+    impl Sealed for Path {}
+    impl Sealed for PathBuf {}
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{assert_matches, path::Path};
+
+    use crate::{Error, FileIconExt, get_file_icon};
+
+    #[test]
+    fn test_file_icon() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+
+        assert_eq!(
+            file_path.file_icon(32).expect("icon from PathBuf"),
+            get_file_icon(&file_path, 32).expect("icon from function")
+        );
+        assert_eq!(
+            file_path.as_path().file_icon(32).expect("icon from Path"),
+            get_file_icon(&file_path, 32).expect("icon from function")
+        );
+        assert_matches!(
+            Path::new("NOT EXISTING").file_icon(32),
+            Err(Error::PathDoesNotExist)
+        );
+    }
+
+    #[cfg(feature = "global")]
+    #[test]
+    fn test_file_icon_cached() {
+        let file_path = locate_cargo_manifest::locate_manifest().expect("locate Cargo.toml");
+
+        assert_eq!(
+            file_path.file_icon_cached(32).expect("cached icon"),
+            file_path.file_icon(32).expect("icon")
+        );
+    }
+}
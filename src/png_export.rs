@@ -0,0 +1,107 @@
+//! PNG encoding of [`Icon`].
+
+use std::{
+    fs::File,
+    io::{BufWriter, ErrorKind},
+    path::Path,
+};
+
+use png::{BitDepth, ColorType, Encoder, EncodingError};
+
+use crate::{Error, Icon};
+
+impl Icon {
+    /// Saves the icon to a PNG file.
+    ///
+    /// # Errors
+    /// Returns [`Error::Io`] if the file can't be written, or if the icon can't be encoded
+    /// because the length of the pixel buffer does not match the dimensions.
+    /// The file is not created if the icon can't be encoded.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use file_icon_provider::get_file_icon;
+    ///
+    /// get_file_icon("path/to/file", 64)?.save_png("icon.png")?;
+    /// # Ok::<(), file_icon_provider::Error>(())
+    /// ```
+    pub fn save_png(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        if !self.has_valid_buffer() {
+            return Err(Error::Io(std::io::Error::new(
+                ErrorKind::InvalidData,
+                "The length of the pixel buffer does not match the dimensions",
+            )));
+        }
+
+        let file = File::create(path).map_err(Error::Io)?;
+        let mut encoder = Encoder::new(BufWriter::new(file), self.width, self.height);
+
+        encoder.set_color(ColorType::Rgba);
+        encoder.set_depth(BitDepth::Eight);
+
+        let mut writer = encoder.write_header().map_err(encoding_error)?;
+
+        writer
+            .write_image_data(&self.rgba_pixels())
+            .map_err(encoding_error)?;
+        writer.finish().map_err(encoding_error)
+    }
+}
+
+fn encoding_error(error: EncodingError) -> Error {
+    match error {
+        EncodingError::IoError(error) => Error::Io(error),
+        error => Error::Io(std::io::Error::other(error)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{assert_matches, fs::File};
+
+    use crate::{Error, Icon, PixelFormat};
+
+    #[test]
+    fn test_save_png() {
+        let path = std::env::temp_dir().join("file_icon_provider_test_save_png.png");
+        let icon = Icon {
+            width: 2,
+            height: 1,
+            pixels: vec![1, 2, 3, 4, 5, 6, 7, 8],
+            format: PixelFormat::Rgba8,
+            stride: None,
+        };
+
+        icon.save_png(&path).expect("save png");
+
+        let decoder = png::Decoder::new(std::io::BufReader::new(
+            File::open(&path).expect("open png"),
+        ));
+        let mut reader = decoder.read_info().expect("read png info");
+        let mut pixels = vec![0; reader.output_buffer_size().expect("buffer size")];
+        let info = reader.next_frame(&mut pixels).expect("read png");
+
+        std::fs::remove_file(&path).expect("remove png");
+
+        assert_eq!((info.width, info.height), (2, 1));
+        assert_eq!(pixels, icon.pixels);
+    }
+
+    #[test]
+    fn test_save_png_invalid_buffer() {
+        let path = std::env::temp_dir().join(format!(
+            "file_icon_provider_test_invalid_buffer_{}.png",
+            std::process::id()
+        ));
+        let icon = Icon {
+            width: 2,
+            height: 2,
+            pixels: vec![0; 4],
+            format: PixelFormat::Rgba8,
+            stride: None,
+        };
+
+        assert_matches!(icon.save_png(&path), Err(Error::Io(_)));
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,326 @@
+//! Resampling of [`Icon`].
+
+use crate::Icon;
+
+/// How the pixels are interpolated when an [`Icon`] is resized, see [`Icon::resize_with_filter`].
+///
+/// The default is [`ResizeFilter::Nearest`], like the filter of the images of the examples.
+/// [`Icon::resize`] uses the default filter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ResizeFilter {
+    /// The nearest source pixel, crisp like pixel art.
+    #[default]
+    Nearest,
+    /// Bilinear interpolation of the 2 x 2 nearest source pixels, smooth.
+    Triangle,
+    /// Bicubic interpolation of the 4 x 4 nearest source pixels, smooth and sharper than [`ResizeFilter::Triangle`].
+    CatmullRom,
+}
+
+impl ResizeFilter {
+    /// Returns the source pixels `target` pixels are interpolated from along one axis of `source` pixels,
+    /// with their weights.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn taps(self, source: u32, target: u32) -> Vec<Vec<(usize, f64)>> {
+        let scale = f64::from(source) / f64::from(target);
+        let last = f64::from(source - 1);
+
+        (0..target)
+            .map(|index| {
+                let center = (f64::from(index) + 0.5) * scale;
+                let offsets: &[f64] = match self {
+                    ResizeFilter::Nearest => {
+                        return vec![(center.floor().min(last) as usize, 1.0)];
+                    }
+                    ResizeFilter::Triangle => &[0.0, 1.0],
+                    ResizeFilter::CatmullRom => &[-1.0, 0.0, 1.0, 2.0],
+                };
+                // Pixel centers are aligned, so the borders of the source and the target match.
+                let position = (center - 0.5).clamp(0.0, last);
+                let first = position.floor();
+                let fraction = position - first;
+
+                offsets
+                    .iter()
+                    .map(|offset| {
+                        let x = (first + offset).clamp(0.0, last) as usize;
+
+                        (x, self.weight(offset - fraction))
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Returns the weight of a source pixel at `distance` from the interpolated position.
+    fn weight(self, distance: f64) -> f64 {
+        let distance = distance.abs();
+
+        match self {
+            ResizeFilter::Nearest => f64::from(u8::from(distance < 0.5)),
+            ResizeFilter::Triangle => (1.0 - distance).max(0.0),
+            ResizeFilter::CatmullRom if distance < 1.0 => {
+                (1.5 * distance - 2.5) * distance * distance + 1.0
+            }
+            ResizeFilter::CatmullRom if distance < 2.0 => {
+                ((-0.5 * distance + 2.5) * distance - 4.0) * distance + 2.0
+            }
+            ResizeFilter::CatmullRom => 0.0,
+        }
+    }
+}
+
+impl Icon {
+    /// Returns a copy of the icon scaled to `width` x `height` with the default filter,
+    /// [`ResizeFilter::Nearest`], which keeps the pixels crisp.
+    ///
+    /// It is the same as [`Icon::resize_with_filter`] with [`ResizeFilter::default`].
+    ///
+    /// If `width` or `height` is 0, the returned icon has no pixels.
+    /// If the icon has no pixels, or if its pixel buffer does not match its dimensions,
+    /// it is returned unchanged.
+    ///
+    /// # Example
+    /// ```
+    /// use file_icon_provider::{Icon, PixelFormat};
+    ///
+    /// let icon = Icon {
+    ///     width: 2,
+    ///     height: 2,
+    ///     pixels: vec![255; 2 * 2 * 4],
+    ///     format: PixelFormat::Rgba8,
+    ///     stride: None,
+    /// };
+    /// let thumbnail = icon.resize(1, 1);
+    ///
+    /// assert_eq!(thumbnail.pixels, vec![255; 4]);
+    /// ```
+    #[must_use]
+    pub fn resize(&self, width: u32, height: u32) -> Icon {
+        self.resize_with_filter(width, height, ResizeFilter::default())
+    }
+
+    /// Returns a copy of the icon scaled to `width` x `height`, interpolated with `filter`.
+    ///
+    /// Colors are interpolated weighted by their alpha, so transparent pixels do not darken the edges.
+    ///
+    /// If `width` or `height` is 0, the returned icon has no pixels.
+    /// If the icon has no pixels, or if its pixel buffer does not match its dimensions,
+    /// it is returned unchanged.
+    ///
+    /// # Example
+    /// ```
+    /// use file_icon_provider::{Icon, PixelFormat, ResizeFilter};
+    ///
+    /// let icon = Icon {
+    ///     width: 2,
+    ///     height: 1,
+    ///     pixels: vec![0, 0, 0, 255, 255, 255, 255, 255],
+    ///     format: PixelFormat::Rgba8,
+    ///     stride: None,
+    /// };
+    /// let enlarged = icon.resize_with_filter(4, 1, ResizeFilter::Nearest);
+    ///
+    /// assert_eq!(enlarged.pixels[4..12], [0, 0, 0, 255, 255, 255, 255, 255]);
+    /// ```
+    #[must_use]
+    pub fn resize_with_filter(&self, width: u32, height: u32, filter: ResizeFilter) -> Icon {
+        if width == 0 || height == 0 {
+            return Icon {
+                width,
+                height,
+                pixels: Vec::new(),
+                format: self.format,
+                stride: None,
+            };
+        }
+
+        if self.width == 0
+            || self.height == 0
+            || !self.has_valid_buffer()
+            || (self.width, self.height) == (width, height)
+        {
+            return self.clone();
+        }
+
+        let columns = filter.taps(self.width, width);
+        let rows = filter.taps(self.height, height);
+        let bytes_per_row = self.bytes_per_row();
+        let mut pixels = Vec::with_capacity(columns.len() * rows.len() * 4);
+
+        for row in &rows {
+            for column in &columns {
+                let mut premultiplied = [0.0; 3];
+                let mut alpha = 0.0;
+
+                for &(y, y_weight) in row {
+                    for &(x, x_weight) in column {
+                        let offset = y * bytes_per_row + x * 4;
+                        let pixel = &self.pixels[offset..offset + 4];
+                        let weighted_alpha = f64::from(pixel[3]) * x_weight * y_weight;
+
+                        for (channel, value) in premultiplied.iter_mut().zip(pixel) {
+                            *channel += f64::from(*value) * weighted_alpha;
+                        }
+                        alpha += weighted_alpha;
+                    }
+                }
+
+                if alpha > 0.0 {
+                    pixels.extend(premultiplied.map(|channel| to_channel(channel / alpha)));
+                } else {
+                    pixels.extend([0, 0, 0]);
+                }
+                pixels.push(to_channel(alpha));
+            }
+        }
+
+        Icon {
+            width,
+            height,
+            pixels,
+            format: self.format,
+            stride: None,
+        }
+    }
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn to_channel(value: f64) -> u8 {
+    value.round().clamp(0.0, 255.0) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Icon, PixelFormat, ResizeFilter};
+
+    #[test]
+    fn test_downscale_averages_pixels() {
+        let icon = Icon {
+            width: 2,
+            height: 1,
+            pixels: vec![0, 0, 0, 255, 255, 255, 255, 255],
+            format: PixelFormat::Rgba8,
+            stride: None,
+        };
+
+        assert_eq!(
+            icon.resize_with_filter(1, 1, ResizeFilter::Triangle).pixels,
+            vec![128, 128, 128, 255]
+        );
+    }
+
+    #[test]
+    fn test_transparent_pixels_do_not_darken() {
+        let icon = Icon {
+            width: 2,
+            height: 1,
+            pixels: vec![0, 0, 0, 0, 255, 0, 0, 255],
+            format: PixelFormat::Rgba8,
+            stride: None,
+        };
+
+        assert_eq!(
+            icon.resize_with_filter(1, 1, ResizeFilter::Triangle).pixels,
+            vec![255, 0, 0, 128]
+        );
+    }
+
+    #[test]
+    fn test_upscale() {
+        let icon = Icon {
+            width: 1,
+            height: 1,
+            pixels: vec![10, 20, 30, 255],
+            format: PixelFormat::Rgba8,
+            stride: None,
+        };
+        let resized = icon.resize(3, 2);
+
+        assert_eq!(resized.width, 3);
+        assert_eq!(resized.height, 2);
+        assert_eq!(resized.pixels, [10, 20, 30, 255].repeat(6));
+    }
+
+    #[test]
+    fn test_nearest_keeps_the_pixels() {
+        let icon = Icon {
+            width: 2,
+            height: 1,
+            pixels: vec![10, 20, 30, 255, 40, 50, 60, 128],
+            format: PixelFormat::Rgba8,
+            stride: None,
+        };
+        let resized = icon.resize_with_filter(4, 2, ResizeFilter::Nearest);
+
+        assert_eq!(
+            resized.pixels,
+            [[10, 20, 30, 255].repeat(2), [40, 50, 60, 128].repeat(2)]
+                .concat()
+                .repeat(2)
+        );
+        assert_eq!(
+            resized.resize_with_filter(2, 1, ResizeFilter::Nearest),
+            icon
+        );
+        assert_eq!(icon.resize(4, 2), resized);
+    }
+
+    #[test]
+    fn test_catmull_rom() {
+        let solid = Icon {
+            width: 3,
+            height: 3,
+            pixels: [10, 20, 30, 255].repeat(9),
+            format: PixelFormat::Bgra8,
+            stride: None,
+        };
+
+        assert_eq!(
+            solid
+                .resize_with_filter(7, 5, ResizeFilter::CatmullRom)
+                .pixels,
+            [10, 20, 30, 255].repeat(35)
+        );
+
+        let step = Icon {
+            width: 4,
+            height: 1,
+            pixels: [[0, 0, 0, 255].repeat(2), [255, 255, 255, 255].repeat(2)].concat(),
+            format: PixelFormat::Rgba8,
+            stride: None,
+        };
+        let resized = step.resize_with_filter(8, 1, ResizeFilter::CatmullRom);
+        let triangle = step.resize_with_filter(8, 1, ResizeFilter::Triangle);
+
+        // The edge is steeper than with the bilinear filtering.
+        assert_eq!(resized.pixels[..4], [0, 0, 0, 255]);
+        assert_eq!(resized.pixels[28..], [255, 255, 255, 255]);
+        assert!(resized.pixels[12] < triangle.pixels[12]);
+        assert!(resized.pixels[16] > triangle.pixels[16]);
+    }
+
+    #[test]
+    fn test_degenerate_sizes() {
+        let icon = Icon {
+            width: 1,
+            height: 1,
+            pixels: vec![10, 20, 30, 255],
+            format: PixelFormat::Rgba8,
+            stride: None,
+        };
+
+        assert!(icon.resize(0, 4).pixels.is_empty());
+        assert_eq!(icon.resize(1, 1), icon);
+
+        let empty = Icon {
+            width: 0,
+            height: 0,
+            pixels: Vec::new(),
+            format: PixelFormat::Rgba8,
+            stride: None,
+        };
+
+        assert_eq!(empty.resize(4, 4), empty);
+    }
+}
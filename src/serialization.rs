@@ -0,0 +1,83 @@
+//! Serde support for [`Icon`].
+
+use serde::Deserialize;
+
+use crate::{Icon, PixelFormat};
+
+/// Icon as it is deserialized, before its pixel buffer is validated.
+#[derive(Deserialize)]
+#[serde(rename = "Icon")]
+pub(crate) struct UncheckedIcon {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+    /// Missing in the icons serialized before the format was recorded, they are RGBA.
+    #[serde(default)]
+    format: PixelFormat,
+    /// Missing in the icons serialized before the stride was recorded, their rows are packed.
+    #[serde(default)]
+    stride: Option<usize>,
+}
+
+impl TryFrom<UncheckedIcon> for Icon {
+    type Error = String;
+
+    fn try_from(icon: UncheckedIcon) -> Result<Self, Self::Error> {
+        let row_len = u64::from(icon.width) * 4;
+        let bytes_per_row = icon.stride.map_or(row_len, |stride| stride as u64);
+        let expected_len = bytes_per_row.max(row_len) * u64::from(icon.height);
+
+        if bytes_per_row < row_len || icon.pixels.len() as u64 != expected_len {
+            return Err(format!(
+                "invalid pixel buffer length for a {}x{} icon: expected {expected_len} bytes, got {}",
+                icon.width,
+                icon.height,
+                icon.pixels.len()
+            ));
+        }
+
+        Ok(Icon {
+            width: icon.width,
+            height: icon.height,
+            pixels: icon.pixels,
+            format: icon.format,
+            stride: icon.stride,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Icon, PixelFormat};
+
+    #[test]
+    fn test_bincode_round_trip() {
+        let icon = Icon {
+            width: 2,
+            height: 2,
+            pixels: (0..16).collect(),
+            format: PixelFormat::Rgba8,
+            stride: None,
+        };
+        let bytes = bincode::serialize(&icon).expect("serialize icon");
+
+        assert_eq!(
+            bincode::deserialize::<Icon>(&bytes).expect("deserialize icon"),
+            icon
+        );
+    }
+
+    #[test]
+    fn test_invalid_pixel_buffer_is_rejected() {
+        let icon = Icon {
+            width: 2,
+            height: 2,
+            pixels: vec![0; 15],
+            format: PixelFormat::Rgba8,
+            stride: None,
+        };
+        let bytes = bincode::serialize(&icon).expect("serialize icon");
+
+        assert!(bincode::deserialize::<Icon>(&bytes).is_err());
+    }
+}
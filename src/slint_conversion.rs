@@ -0,0 +1,45 @@
+//! Conversion from [`Icon`] to the images of `slint`.
+
+use slint::{Image, Rgba8Pixel, SharedPixelBuffer};
+
+use crate::{Error, Icon, PixelFormat};
+
+impl TryFrom<Icon> for Image {
+    type Error = Error;
+
+    /// Fails with [`Error::InvalidBuffer`] if the length of the pixel buffer does not match the dimensions.
+    fn try_from(icon: Icon) -> Result<Self, Self::Error> {
+        icon.check_buffer()?;
+
+        let icon = icon.into_packed().into_format(PixelFormat::Rgba8);
+        let buffer = SharedPixelBuffer::<Rgba8Pixel>::clone_from_slice(
+            &icon.pixels,
+            icon.width,
+            icon.height,
+        );
+
+        Ok(Image::from_rgba8(buffer))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use slint::Image;
+
+    use crate::{Icon, PixelFormat};
+
+    #[test]
+    fn test_into_image() {
+        let icon = Icon {
+            width: 2,
+            height: 1,
+            pixels: vec![1, 2, 3, 4, 5, 6, 7, 8],
+            format: PixelFormat::Bgra8,
+        };
+        let image = Image::try_from(icon).expect("convert icon");
+        let buffer = image.to_rgba8().expect("RGBA buffer");
+
+        assert_eq!((buffer.width(), buffer.height()), (2, 1));
+        assert_eq!(buffer.as_bytes(), [3, 2, 1, 4, 7, 6, 5, 8]);
+    }
+}
@@ -0,0 +1,308 @@
+//! Encoding of [`Icon`] into terminal graphics escape sequences.
+
+use std::{collections::BTreeMap, fmt::Write};
+
+use crate::Icon;
+
+/// The maximum number of colors of a sixel palette.
+const MAX_SIXEL_COLORS: usize = 256;
+
+/// The maximum size of a chunk of the kitty graphics protocol payload.
+const KITTY_CHUNK_SIZE: usize = 4096;
+
+/// Pixels with a lower alpha are not drawn by the sixel encoder.
+const SIXEL_ALPHA_THRESHOLD: u8 = 128;
+
+impl Icon {
+    /// Encodes the icon into a sixel escape sequence, to print it in terminals supporting sixel graphics.
+    ///
+    /// The palette is built from the colors of the icon, they are quantized if there are more
+    /// than 256 colors. Sixel has no partial transparency: pixels with an alpha lower than 128 are not drawn,
+    /// the other pixels are drawn opaque.
+    ///
+    /// Returns an empty string if the length of the pixel buffer does not match the dimensions.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use file_icon_provider::get_file_icon;
+    ///
+    /// print!("{}", get_file_icon("path/to/file", 32)?.to_sixel());
+    /// # Ok::<(), file_icon_provider::Error>(())
+    /// ```
+    #[must_use]
+    pub fn to_sixel(&self) -> String {
+        if !self.has_valid_buffer() {
+            return String::new();
+        }
+
+        let width = self.width as usize;
+        let height = self.height as usize;
+        let (palette, indices) = self.sixel_palette();
+        // The second parameter selects a transparent background for the pixels not drawn.
+        let mut sixel = format!("\x1bP0;1;0q\"1;1;{width};{height}");
+
+        for (index, [red, green, blue]) in palette.iter().enumerate() {
+            let [red, green, blue] = [red, green, blue].map(|channel| percent(*channel));
+            let _ = write!(sixel, "#{index};2;{red};{green};{blue}");
+        }
+
+        for band_top in (0..height).step_by(6) {
+            let band = band_top..(band_top + 6).min(height);
+            let mut first_color = true;
+
+            for color in 0..palette.len() {
+                // Each column of the band is a character whose bits are the rows drawn with this color.
+                let columns: Vec<u8> = (0..width)
+                    .map(|x| {
+                        band.clone()
+                            .filter(|y| indices[y * width + x] == Some(color))
+                            .fold(0, |bits, y| bits | 1 << (y - band_top))
+                    })
+                    .collect();
+
+                if columns.iter().all(|bits| *bits == 0) {
+                    continue;
+                }
+
+                if !first_color {
+                    // Goes back to the start of the band to draw the next color.
+                    sixel.push('$');
+                }
+                first_color = false;
+
+                let _ = write!(sixel, "#{color}");
+                push_sixel_run_lengths(&mut sixel, &columns);
+            }
+
+            sixel.push('-');
+        }
+
+        sixel.push_str("\x1b\\");
+        sixel
+    }
+
+    /// Encodes the icon into a kitty graphics protocol escape sequence, to print it in terminals
+    /// supporting this protocol, like kitty, `WezTerm` or Ghostty.
+    ///
+    /// The pixels are sent as RGBA, the transparency is preserved.
+    ///
+    /// Returns an empty string if the length of the pixel buffer does not match the dimensions.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use file_icon_provider::get_file_icon;
+    ///
+    /// print!("{}", get_file_icon("path/to/file", 32)?.to_kitty());
+    /// # Ok::<(), file_icon_provider::Error>(())
+    /// ```
+    #[must_use]
+    pub fn to_kitty(&self) -> String {
+        if !self.has_valid_buffer() {
+            return String::new();
+        }
+
+        let payload = base64(&self.rgba_pixels());
+        let chunks: Vec<&[u8]> = payload.as_bytes().chunks(KITTY_CHUNK_SIZE).collect();
+        let chunk_count = chunks.len().max(1);
+        let mut kitty = String::with_capacity(payload.len() + chunk_count * 16);
+
+        for index in 0..chunk_count {
+            let chunk = chunks.get(index).copied().unwrap_or_default();
+            let more = u8::from(index + 1 < chunk_count);
+
+            kitty.push_str("\x1b_G");
+            // Only the first chunk carries the description of the image.
+            if index == 0 {
+                let _ = write!(kitty, "a=T,f=32,s={},v={},", self.width, self.height);
+            }
+            let _ = write!(kitty, "m={more};");
+            kitty.push_str(std::str::from_utf8(chunk).unwrap_or_default());
+            kitty.push_str("\x1b\\");
+        }
+
+        kitty
+    }
+
+    /// Returns the palette of the icon and the palette index of each pixel,
+    /// `None` for the pixels that are not drawn.
+    fn sixel_palette(&self) -> (Vec<[u8; 3]>, Vec<Option<usize>>) {
+        let pixels = self.rgba_pixels();
+        let drawn = |pixel: &[u8]| pixel[3] >= SIXEL_ALPHA_THRESHOLD;
+        let mut shift = 0;
+
+        // The least significant bits of the colors are dropped until the palette is small enough.
+        loop {
+            let mut buckets: BTreeMap<[u8; 3], ([u64; 3], u64)> = BTreeMap::new();
+
+            for pixel in pixels.chunks_exact(4).filter(|pixel| drawn(pixel)) {
+                let (sum, count) = buckets
+                    .entry([pixel[0] >> shift, pixel[1] >> shift, pixel[2] >> shift])
+                    .or_default();
+
+                for (sum, channel) in sum.iter_mut().zip(pixel) {
+                    *sum += u64::from(*channel);
+                }
+                *count += 1;
+            }
+
+            if buckets.len() > MAX_SIXEL_COLORS {
+                shift += 1;
+                continue;
+            }
+
+            let bucket_indices: BTreeMap<[u8; 3], usize> = buckets
+                .keys()
+                .enumerate()
+                .map(|(index, key)| (*key, index))
+                .collect();
+            let palette = buckets
+                .values()
+                .map(|(sum, count)| {
+                    sum.map(|sum| u8::try_from((sum + count / 2) / count).unwrap_or(u8::MAX))
+                })
+                .collect();
+            let indices = pixels
+                .chunks_exact(4)
+                .map(|pixel| {
+                    drawn(pixel).then(|| {
+                        bucket_indices[&[pixel[0] >> shift, pixel[1] >> shift, pixel[2] >> shift]]
+                    })
+                })
+                .collect();
+
+            return (palette, indices);
+        }
+    }
+}
+
+/// Appends the sixel characters of `columns`, with run-length encoding of repeated characters.
+fn push_sixel_run_lengths(sixel: &mut String, columns: &[u8]) {
+    let mut index = 0;
+
+    while index < columns.len() {
+        let bits = columns[index];
+        let run = columns[index..]
+            .iter()
+            .take_while(|other| **other == bits)
+            .count();
+        let character = char::from(b'?' + bits);
+
+        if run > 3 {
+            let _ = write!(sixel, "!{run}{character}");
+        } else {
+            sixel.extend(std::iter::repeat_n(character, run));
+        }
+        index += run;
+    }
+}
+
+/// Converts a color channel to the percentage used by sixel palettes.
+fn percent(channel: u8) -> u16 {
+    (u16::from(channel) * 100 + 127) / 255
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (index, byte)| {
+            group | u32::from(*byte) << (16 - index * 8)
+        });
+
+        for index in 0..4 {
+            if index <= chunk.len() {
+                encoded.push(char::from(
+                    ALPHABET[(group >> (18 - index * 6)) as usize & 0x3f],
+                ));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Icon, PixelFormat};
+
+    use super::base64;
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn test_to_sixel() {
+        let icon = Icon {
+            width: 2,
+            height: 1,
+            pixels: vec![255, 0, 0, 255, 0, 0, 0, 0],
+            format: PixelFormat::Rgba8,
+            stride: None,
+        };
+        let sixel = icon.to_sixel();
+
+        assert!(sixel.starts_with("\x1bP0;1;0q\"1;1;2;1"));
+        assert!(sixel.ends_with("\x1b\\"));
+        assert_eq!(sixel, "\x1bP0;1;0q\"1;1;2;1#0;2;100;0;0#0@?-\x1b\\");
+    }
+
+    #[test]
+    fn test_to_sixel_quantizes_colors() {
+        let pixels: Vec<u8> = (0..=u8::MAX)
+            .flat_map(|red| [red, 255 - red, 0, 255, red, 0, 255 - red, 255])
+            .collect();
+        let icon = Icon {
+            width: 512,
+            height: 1,
+            pixels,
+            format: PixelFormat::Rgba8,
+            stride: None,
+        };
+        let sixel = icon.to_sixel();
+        let palette_size = sixel.matches(";2;").count();
+
+        assert!(palette_size <= 256);
+        assert!(sixel.ends_with("\x1b\\"));
+    }
+
+    #[test]
+    fn test_to_kitty() {
+        let icon = Icon {
+            width: 1,
+            height: 1,
+            pixels: vec![1, 2, 3, 4],
+            format: PixelFormat::Rgba8,
+            stride: None,
+        };
+
+        assert_eq!(icon.to_kitty(), "\x1b_Ga=T,f=32,s=1,v=1,m=0;AQIDBA==\x1b\\");
+    }
+
+    #[test]
+    fn test_to_kitty_is_chunked() {
+        let icon = Icon {
+            width: 64,
+            height: 64,
+            pixels: vec![255; 64 * 64 * 4],
+            format: PixelFormat::Rgba8,
+            stride: None,
+        };
+        let kitty = icon.to_kitty();
+
+        assert!(kitty.starts_with("\x1b_Ga=T,f=32,s=64,v=64,m=1;"));
+        assert!(kitty.contains("\x1b\\\x1b_Gm=1;"));
+        assert!(kitty.contains("\x1b\\\x1b_Gm=0;"));
+        assert!(kitty.ends_with("\x1b\\"));
+        assert_eq!(kitty.matches("\x1b_G").count(), 6);
+    }
+}
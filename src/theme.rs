@@ -0,0 +1,153 @@
+//! Notifications of the changes of the icons of the system, see [`Provider::on_theme_changed`](crate::Provider::on_theme_changed).
+//!
+//! The backends watch their notification source once for the whole process,
+//! and forward the changes to the listeners of all the providers.
+
+use std::sync::{
+    Arc, Mutex, MutexGuard, PoisonError, Weak,
+    atomic::{AtomicBool, Ordering},
+};
+
+use crate::{Error, implementation};
+
+/// The listeners of the providers, the ones of the dropped providers are removed on the next change.
+static LISTENERS: Listeners = Listeners::new();
+
+/// Listens to the changes of the icons of the system for a provider.
+pub(crate) struct ThemeListener {
+    /// Set when the icons changed, the provider clears its cache on its next request.
+    changed: AtomicBool,
+    #[cfg_attr(feature = "stub", allow(dead_code))]
+    callback: Box<dyn Fn() + Send + Sync>,
+    /// Set if the listener is registered in [`LISTENERS`], the last one stops the watch when dropped.
+    watching: bool,
+}
+
+impl ThemeListener {
+    /// Returns `true` if the icons changed since the last call.
+    pub(crate) fn take_changed(&self) -> bool {
+        self.changed.swap(false, Ordering::AcqRel)
+    }
+}
+
+impl Drop for ThemeListener {
+    fn drop(&mut self) {
+        if !self.watching {
+            return;
+        }
+
+        let mut listeners = LISTENERS.lock();
+
+        listeners.retain(|listener| listener.strong_count() > 0);
+
+        // The lock is held so a concurrent call to `listen` can't register a listener meanwhile.
+        if listeners.is_empty() {
+            implementation::unwatch_theme();
+        }
+    }
+}
+
+/// Registers a listener, the notification source of the backend is watched on the first call.
+///
+/// The notification source is watched until all the listeners are dropped.
+pub(crate) fn listen(
+    callback: impl Fn() + Send + Sync + 'static,
+) -> Result<Arc<ThemeListener>, Error> {
+    let mut listeners = LISTENERS.lock();
+
+    implementation::watch_theme()?;
+
+    Ok(register(&mut listeners, Box::new(callback), true))
+}
+
+/// Notifies the listeners that the icons of the system changed, called by the backends.
+///
+/// The stub backend never calls it, its icons never change.
+#[cfg_attr(feature = "stub", allow(dead_code))]
+pub(crate) fn notify_theme_changed() {
+    LISTENERS.notify();
+}
+
+/// A registry of listeners.
+struct Listeners(Mutex<Vec<Weak<ThemeListener>>>);
+
+impl Listeners {
+    const fn new() -> Self {
+        Self(Mutex::new(Vec::new()))
+    }
+
+    #[cfg_attr(feature = "stub", allow(dead_code))]
+    fn notify(&self) {
+        let listeners: Vec<Arc<ThemeListener>> = {
+            let mut listeners = self.lock();
+
+            listeners.retain(|listener| listener.strong_count() > 0);
+            listeners.iter().filter_map(Weak::upgrade).collect()
+        };
+
+        // The callbacks run without the lock, so they can use the providers.
+        for listener in listeners {
+            listener.changed.store(true, Ordering::Release);
+            (listener.callback)();
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<Weak<ThemeListener>>> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+fn register(
+    listeners: &mut Vec<Weak<ThemeListener>>,
+    callback: Box<dyn Fn() + Send + Sync>,
+    watching: bool,
+) -> Arc<ThemeListener> {
+    let listener = Arc::new(ThemeListener {
+        changed: AtomicBool::new(false),
+        callback,
+        watching,
+    });
+
+    listeners.push(Arc::downgrade(&listener));
+
+    listener
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    use super::{Listeners, register};
+
+    #[test]
+    fn test_notify_theme_changed() {
+        // A registry of its own, the providers of the other tests notify the global one.
+        let listeners = Listeners::new();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let listener = register(
+            &mut listeners.lock(),
+            Box::new({
+                let calls = calls.clone();
+
+                move || {
+                    calls.fetch_add(1, Ordering::Relaxed);
+                }
+            }),
+            false,
+        );
+
+        assert!(!listener.take_changed());
+
+        listeners.notify();
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+        assert!(listener.take_changed());
+        assert!(!listener.take_changed());
+
+        drop(listener);
+        listeners.notify();
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+    }
+}
@@ -0,0 +1,49 @@
+//! Conversion from [`Icon`] to the window icons of `winit`.
+
+use crate::{Error, Icon, PixelFormat};
+
+impl Icon {
+    /// Converts the icon to a `winit` icon, for example to use the icon of a document as window icon.
+    ///
+    /// # Errors
+    /// Fails with [`Error::InvalidBuffer`] if the length of the pixel buffer does not match
+    /// the dimensions, or with [`Error::Conversion`] if `winit` rejects the icon for another reason,
+    /// the `winit::window::BadIcon` is available with [`std::error::Error::source`].
+    ///
+    /// # Example
+    /// ```no_run
+    /// use file_icon_provider::get_file_icon;
+    ///
+    /// fn set_document_icon(window: &winit::window::Window) {
+    ///     if let Ok(icon) = get_file_icon("path/to/file", 32) {
+    ///         window.set_window_icon(icon.to_winit_icon().ok());
+    ///     }
+    /// }
+    /// ```
+    pub fn to_winit_icon(&self) -> Result<winit::window::Icon, Error> {
+        self.check_buffer()?;
+
+        let icon = self.clone().into_packed().into_format(PixelFormat::Rgba8);
+
+        winit::window::Icon::from_rgba(icon.pixels, icon.width, icon.height)
+            .map_err(|error| Error::Conversion(error.into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Icon, PixelFormat};
+
+    #[test]
+    fn test_to_winit_icon() {
+        let icon = Icon {
+            width: 2,
+            height: 1,
+            pixels: vec![0; 2 * 4],
+            format: PixelFormat::Bgra8,
+            stride: None,
+        };
+
+        assert!(icon.to_winit_icon().is_ok());
+    }
+}
@@ -0,0 +1,136 @@
+//! Background thread running blocking work on behalf of async callers.
+
+use std::{
+    future::Future,
+    panic::{AssertUnwindSafe, catch_unwind},
+    pin::Pin,
+    sync::{
+        Arc, LazyLock, Mutex, MutexGuard, PoisonError,
+        mpsc::{Sender, channel},
+    },
+    task::{Context, Poll, Waker},
+};
+
+use crate::logging::debug;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+static JOB_SENDER: LazyLock<Sender<Job>> = LazyLock::new(start_worker_thread);
+
+fn start_worker_thread() -> Sender<Job> {
+    let (sender, receiver) = channel::<Job>();
+
+    std::thread::spawn(move || {
+        debug!("Start async worker thread");
+        for job in &receiver {
+            // A panicking job must not stop the thread, its task completes without result.
+            let _ = catch_unwind(AssertUnwindSafe(job));
+        }
+        debug!("Async worker thread stopped");
+    });
+
+    sender
+}
+
+struct State<R> {
+    result: Option<R>,
+    completed: bool,
+    waker: Option<Waker>,
+}
+
+/// Future resolving to the result of a job executed by the worker thread.
+///
+/// It resolves to `None` if the job could not complete.
+pub(crate) struct Task<R> {
+    state: Arc<Mutex<State<R>>>,
+}
+
+/// Completes a task, without result if it is dropped before [`Completion::complete`] is called.
+struct Completion<R> {
+    state: Arc<Mutex<State<R>>>,
+}
+
+impl<R> Completion<R> {
+    fn complete(&self, result: Option<R>) {
+        let mut state = lock(&self.state);
+
+        if state.completed {
+            return;
+        }
+
+        state.result = result;
+        state.completed = true;
+
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl<R> Drop for Completion<R> {
+    fn drop(&mut self) {
+        self.complete(None);
+    }
+}
+
+/// Runs `work` on the worker thread.
+///
+/// If the returned task is dropped before the worker thread picks the job, the work is skipped.
+pub(crate) fn spawn<R, F>(work: F) -> Task<R>
+where
+    R: Send + 'static,
+    F: FnOnce() -> R + Send + 'static,
+{
+    let state = Arc::new(Mutex::new(State {
+        result: None,
+        completed: false,
+        waker: None,
+    }));
+    let completion = Completion {
+        state: state.clone(),
+    };
+    let job: Job = Box::new(move || {
+        // Nobody is waiting for the result anymore.
+        if Arc::strong_count(&completion.state) == 1 {
+            return;
+        }
+
+        completion.complete(Some(work()));
+    });
+
+    // If the job can't be sent it is dropped, which completes the task.
+    let _ = JOB_SENDER.send(job);
+
+    Task { state }
+}
+
+/// Returns a task already completed with `result`, for the work that must run on the calling thread.
+pub(crate) fn ready<R>(result: R) -> Task<R> {
+    Task {
+        state: Arc::new(Mutex::new(State {
+            result: Some(result),
+            completed: true,
+            waker: None,
+        })),
+    }
+}
+
+impl<R> Future for Task<R> {
+    type Output = Option<R>;
+
+    fn poll(self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = lock(&self.state);
+
+        if state.completed {
+            return Poll::Ready(state.result.take());
+        }
+
+        state.waker = Some(context.waker().clone());
+
+        Poll::Pending
+    }
+}
+
+fn lock<R>(state: &Mutex<State<R>>) -> MutexGuard<'_, State<R>> {
+    state.lock().unwrap_or_else(PoisonError::into_inner)
+}